@@ -1,4 +1,10 @@
-use clap::Parser;
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+use std::ffi::OsString;
+use std::io::IsTerminal;
+use std::path::PathBuf;
+
+use crate::types::DiffMode;
 
 /// Real-time Git-powered directory monitor with line-by-line diff visualization
 #[derive(Parser, Debug)]
@@ -6,17 +12,139 @@ use clap::Parser;
 #[command(version, about, long_about = None)]
 pub struct Args {
     /// Directory to watch (defaults to current directory)
-    #[arg(short, long, default_value = ".")]
+    #[arg(short, long, default_value = ".", global = true)]
     pub path: String,
 
     /// Increase verbosity (-v, -vv, -vvv)
     #[arg(short, long, action = clap::ArgAction::Count)]
     pub verbose: u8,
+
+    /// Run without the TUI, writing each change to stdout as a line of JSON
+    #[arg(long, visible_alias = "headless")]
+    pub json: bool,
+
+    /// Run without the TUI, printing each change as a unified patch
+    #[arg(long, conflicts_with = "json")]
+    pub plain: bool,
+
+    /// Record every event of the session to FILE (JSON Lines; `.gz` to compress)
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["json", "plain"])]
+    pub record: Option<PathBuf>,
+
+    /// When to color --plain and `diff` output
+    #[arg(long, value_enum, default_value_t = ColorChoice::Auto, global = true)]
+    pub color: ColorChoice,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Print the current diffs once and exit
+    Diff(DiffArgs),
+    /// Step through a session recorded with --record
+    Replay {
+        /// Recording to open
+        file: PathBuf,
+    },
+    /// Work with the notes written on hunks and lines
+    Notes {
+        #[command(subcommand)]
+        command: NotesCommand,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum NotesCommand {
+    /// Print the repository's notes
+    Export {
+        #[arg(short, long, value_enum, default_value_t = NotesFormat::Markdown)]
+        format: NotesFormat,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NotesFormat {
+    /// A section per file
+    Markdown,
+    /// Review comments, `{"comments": [...]}`
+    Json,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct DiffArgs {
+    /// Files or directories to diff (defaults to every changed file)
+    pub paths: Vec<PathBuf>,
+
+    /// Base to diff against
+    #[arg(short, long, value_enum, default_value_t = ModeArg::All)]
+    pub mode: ModeArg,
+
+    /// Print one JSON object per file instead of patches
+    #[arg(long)]
+    pub json: bool,
+}
+
+/// `DiffMode` as spelled on the command line.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ModeArg {
+    /// Working tree vs HEAD
+    All,
+    /// Index vs HEAD
+    Staged,
+    /// Working tree vs index
+    Unstaged,
+}
+
+impl From<ModeArg> for DiffMode {
+    fn from(mode: ModeArg) -> Self {
+        match mode {
+            ModeArg::All => Self::All,
+            ModeArg::Staged => Self::Staged,
+            ModeArg::Unstaged => Self::Unstaged,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorChoice {
+    /// Color when stdout is a terminal and `NO_COLOR` is unset
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    pub fn enabled(self) -> bool {
+        match self {
+            Self::Auto => std::env::var_os("NO_COLOR").is_none() && std::io::stdout().is_terminal(),
+            Self::Always => true,
+            Self::Never => false,
+        }
+    }
 }
 
 impl Args {
     pub fn parse_args() -> Self {
-        Self::parse()
+        Self::try_parse_args_from(std::env::args_os()).unwrap_or_else(|e| e.exit())
+    }
+
+    /// Parse `args`, also rejecting what clap cannot express: only the TUI
+    /// session records, so `--record` conflicts with every subcommand.
+    pub fn try_parse_args_from<I, T>(args: I) -> Result<Self, clap::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        let args = Self::try_parse_from(args)?;
+        if args.record.is_some() && args.command.is_some() {
+            return Err(Self::command().error(
+                ErrorKind::ArgumentConflict,
+                "the argument '--record <FILE>' cannot be used with a subcommand",
+            ));
+        }
+        Ok(args)
     }
 }
 
@@ -29,6 +157,11 @@ mod tests {
         let args = Args::parse_from(["gwatch"]);
         assert_eq!(args.path, ".");
         assert_eq!(args.verbose, 0);
+        assert!(!args.json);
+        assert!(!args.plain);
+        assert_eq!(args.color, ColorChoice::Auto);
+        assert!(args.command.is_none());
+        assert!(args.record.is_none());
     }
 
     #[test]
@@ -55,6 +188,91 @@ mod tests {
         assert_eq!(args.verbose, 3);
     }
 
+    #[test]
+    fn test_json_flag() {
+        assert!(Args::parse_from(["gwatch", "--json"]).json);
+        assert!(Args::parse_from(["gwatch", "--headless"]).json);
+    }
+
+    #[test]
+    fn test_plain_flag() {
+        let args = Args::parse_from(["gwatch", "--plain", "--color", "always"]);
+        assert!(args.plain);
+        assert_eq!(args.color, ColorChoice::Always);
+        assert!(Args::try_parse_from(["gwatch", "--plain", "--json"]).is_err());
+    }
+
+    #[test]
+    fn test_diff_subcommand() {
+        let args = Args::parse_from(["gwatch", "diff", "src", "README.md", "--mode", "staged"]);
+        let Some(Command::Diff(diff)) = args.command else {
+            panic!("expected the diff subcommand");
+        };
+        assert_eq!(
+            diff.paths,
+            [PathBuf::from("src"), PathBuf::from("README.md")]
+        );
+        assert_eq!(DiffMode::from(diff.mode), DiffMode::Staged);
+        assert!(!diff.json);
+
+        let args = Args::parse_from(["gwatch", "diff", "--json", "-p", "/tmp/repo"]);
+        assert_eq!(args.path, "/tmp/repo");
+        assert!(matches!(
+            args.command,
+            Some(Command::Diff(DiffArgs { json: true, .. }))
+        ));
+    }
+
+    #[test]
+    fn test_record_and_replay() {
+        let args = Args::parse_from(["gwatch", "--record", "session.jsonl.gz"]);
+        assert_eq!(args.record, Some(PathBuf::from("session.jsonl.gz")));
+
+        let args = Args::parse_from(["gwatch", "replay", "session.jsonl"]);
+        assert!(matches!(
+            args.command,
+            Some(Command::Replay { file }) if file.as_os_str() == "session.jsonl"
+        ));
+    }
+
+    #[test]
+    fn test_record_only_with_tui() {
+        for rejected in [
+            &["gwatch", "--record", "s.jsonl", "--json"][..],
+            &["gwatch", "--record", "s.jsonl", "--plain"],
+            &["gwatch", "--record", "s.jsonl", "diff"],
+            &["gwatch", "--record", "s.jsonl", "replay", "old.jsonl"],
+            &["gwatch", "--record", "s.jsonl", "notes", "export"],
+        ] {
+            let err = Args::try_parse_args_from(rejected).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::ArgumentConflict, "{rejected:?}");
+        }
+        assert!(Args::try_parse_args_from(["gwatch", "--record", "s.jsonl", "-v"]).is_ok());
+    }
+
+    #[test]
+    fn test_notes_export() {
+        let args = Args::parse_from(["gwatch", "notes", "export"]);
+        assert!(matches!(
+            args.command,
+            Some(Command::Notes {
+                command: NotesCommand::Export {
+                    format: NotesFormat::Markdown
+                }
+            })
+        ));
+
+        let args = Args::parse_from(["gwatch", "notes", "export", "--format", "json"]);
+        assert!(matches!(
+            args.command,
+            Some(Command::Notes {
+                command: NotesCommand::Export {
+                    format: NotesFormat::Json
+                }
+            })
+        ));
+    }
+
     #[test]
     fn test_combined_args() {
         let args = Args::parse_from(["gwatch", "-p", "/tmp", "-vv"]);
@@ -3,14 +3,27 @@ use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::PathBuf;
 
+use crate::types::DiffLayout;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
     pub theme: ThemeConfig,
     pub editor: EditorConfig,
     pub watcher: WatcherConfig,
     pub display: DisplayConfig,
+    #[serde(default)]
     pub keybindings: KeybindingConfig,
     pub diff_viewer: DiffViewerConfig,
+    #[serde(default)]
+    pub review: ReviewConfig,
+}
+
+/// Where review marks are kept. Marks always belong to one repository;
+/// `per_branch` additionally keeps a separate set for each branch.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct ReviewConfig {
+    pub per_branch: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -61,15 +74,53 @@ impl Default for DiffViewerConfig {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// A user-defined theme, selectable as `name` (default `"custom"`).
+///
+/// Colors are layered on top of the `base` theme, so only the ones being
+/// changed need to be given. Each color is a hex string (`"#a3be8c"`,
+/// `"#abc"`), a named ANSI color (`"red"`, `"darkgray"`) or a 256-color
+/// index (`"208"`).
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
 pub struct CustomColors {
-    pub added_line: String,
-    pub deleted_line: String,
-    pub context_line: String,
-    pub line_number: String,
-    pub border: String,
-    pub text: String,
-    pub background: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub base: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub added_line: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deleted_line: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub context_line: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line_number: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub border: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub border_focused: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text_dim: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub background: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub header_bg: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub footer_bg: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status_paused: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status_running: Option<String>,
+}
+
+impl CustomColors {
+    pub const DEFAULT_NAME: &'static str = "custom";
+
+    pub fn name(&self) -> &str {
+        self.name.as_deref().unwrap_or(Self::DEFAULT_NAME)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -93,21 +144,138 @@ pub struct DisplayConfig {
     pub show_line_numbers: bool,
     pub show_file_path: bool,
     pub use_nerd_font_icons: bool,
+    /// Color code by language, detected from the file extension.
+    #[serde(default = "default_syntax_highlighting")]
+    pub syntax_highlighting: bool,
+    /// Initial layout of the diff pane (`split` or `unified`).
+    #[serde(default)]
+    pub diff_layout: DiffLayout,
+    /// Select the newest event when resuming; otherwise stay on the event
+    /// that was selected while paused.
+    #[serde(default = "default_jump_to_newest_on_resume")]
+    pub jump_to_newest_on_resume: bool,
+}
+
+fn default_syntax_highlighting() -> bool {
+    true
+}
+
+fn default_jump_to_newest_on_resume() -> bool {
+    true
 }
 
+/// Key bindings, one entry per action.
+///
+/// Each value is a comma-separated list of alternatives. An alternative is one
+/// or more whitespace-separated chords (`"g g"`), and a chord is a key name with
+/// optional `ctrl+`, `alt+` or `shift+` modifiers (`"ctrl+s"`, `"shift+r"`).
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct KeybindingConfig {
+    pub quit: String,
     pub pause_resume: String,
     pub scroll_up: String,
     pub scroll_down: String,
+    pub page_up: String,
+    pub page_down: String,
+    pub scroll_left: String,
+    pub scroll_right: String,
+    pub prev_event: String,
+    pub next_event: String,
+    pub toggle_files_view: String,
+    pub cycle_file_sort: String,
+    pub next_hunk: String,
+    pub prev_hunk: String,
+    pub toggle_hunk: String,
+    pub toggle_context: String,
+    pub grow_context: String,
+    pub shrink_context: String,
+    pub toggle_reviewed: String,
+    pub toggle_hunk_reviewed: String,
+    pub add_note: String,
+    pub save_note: String,
+    pub clear_reviewed: String,
+    pub cycle_diff_mode: String,
+    pub toggle_layout: String,
+    pub toggle_comparison: String,
+    pub select_lines: String,
+    pub stage_lines: String,
+    pub unstage_lines: String,
+    pub commit: String,
+    pub submit_commit: String,
+    pub toggle_amend: String,
+    pub open_diff_viewer: String,
     pub open_editor: String,
     pub theme_selector: String,
     pub settings: String,
+    pub save_settings: String,
     pub clear_history: String,
-    pub quit: String,
     pub help: String,
 }
 
+impl Default for KeybindingConfig {
+    fn default() -> Self {
+        Self {
+            quit: "q, esc".to_string(),
+            pause_resume: "space".to_string(),
+            scroll_up: "up, k".to_string(),
+            scroll_down: "down, j".to_string(),
+            page_up: "pageup".to_string(),
+            page_down: "pagedown".to_string(),
+            scroll_left: "left, h".to_string(),
+            scroll_right: "right, l".to_string(),
+            prev_event: "p".to_string(),
+            next_event: "n".to_string(),
+            toggle_files_view: "f".to_string(),
+            cycle_file_sort: "o".to_string(),
+            next_hunk: "]".to_string(),
+            prev_hunk: "[".to_string(),
+            toggle_hunk: "z".to_string(),
+            toggle_context: "Z".to_string(),
+            grow_context: "+, =".to_string(),
+            shrink_context: "-".to_string(),
+            toggle_reviewed: "r".to_string(),
+            toggle_hunk_reviewed: "M".to_string(),
+            add_note: "N".to_string(),
+            save_note: "ctrl+s".to_string(),
+            clear_reviewed: "R".to_string(),
+            cycle_diff_mode: "m".to_string(),
+            toggle_layout: "v".to_string(),
+            toggle_comparison: "b".to_string(),
+            select_lines: "V".to_string(),
+            stage_lines: "a".to_string(),
+            unstage_lines: "u".to_string(),
+            commit: "C".to_string(),
+            submit_commit: "ctrl+s".to_string(),
+            toggle_amend: "ctrl+a".to_string(),
+            open_diff_viewer: "d".to_string(),
+            open_editor: "enter".to_string(),
+            theme_selector: "t".to_string(),
+            settings: "s".to_string(),
+            save_settings: "ctrl+s".to_string(),
+            clear_history: "c".to_string(),
+            help: "?".to_string(),
+        }
+    }
+}
+
+impl KeybindingConfig {
+    /// Read the single keys that configs written before keys could be
+    /// remapped hold as the defaults they stood for: `esc`, `k` and `j`
+    /// worked on top of them back then.
+    fn upgrade_legacy(&mut self) {
+        for (binding, old, new) in [
+            (&mut self.quit, "q", "q, esc"),
+            (&mut self.scroll_up, "up", "up, k"),
+            (&mut self.scroll_down, "down", "down, j"),
+        ] {
+            if binding == old {
+                *binding = new.to_string();
+            }
+        }
+    }
+}
+
 impl Default for Config {
     fn default() -> Self {
         Self {
@@ -137,19 +305,13 @@ impl Default for Config {
                 show_line_numbers: true,
                 show_file_path: true,
                 use_nerd_font_icons: true,
+                syntax_highlighting: true,
+                diff_layout: DiffLayout::default(),
+                jump_to_newest_on_resume: true,
             },
-            keybindings: KeybindingConfig {
-                pause_resume: "space".to_string(),
-                scroll_up: "up".to_string(),
-                scroll_down: "down".to_string(),
-                open_editor: "enter".to_string(),
-                theme_selector: "t".to_string(),
-                settings: "s".to_string(),
-                clear_history: "c".to_string(),
-                quit: "q".to_string(),
-                help: "?".to_string(),
-            },
+            keybindings: KeybindingConfig::default(),
             diff_viewer: DiffViewerConfig::default(),
+            review: ReviewConfig::default(),
         }
     }
 }
@@ -170,7 +332,7 @@ impl Config {
 
         if config_path.exists() {
             let content = fs::read_to_string(&config_path)?;
-            match serde_json::from_str(&content) {
+            match Self::parse(&content) {
                 Ok(config) => Ok(config),
                 Err(e) => {
                     tracing::warn!("Config JSON invalid, using defaults. Error: {}", e);
@@ -184,6 +346,21 @@ impl Config {
         }
     }
 
+    /// Parse the contents of a config file. A keybinding section saved
+    /// before keys could be remapped lacks most actions, `page_up` among
+    /// them, and its bindings are upgraded.
+    pub fn parse(content: &str) -> serde_json::Result<Self> {
+        let value: serde_json::Value = serde_json::from_str(content)?;
+        let legacy = value
+            .get("keybindings")
+            .is_some_and(|keybindings| keybindings.get("page_up").is_none());
+        let mut config: Self = serde_json::from_value(value)?;
+        if legacy {
+            config.keybindings.upgrade_legacy();
+        }
+        Ok(config)
+    }
+
     pub fn save(&self) -> Result<()> {
         let config_dir = Self::config_dir();
         fs::create_dir_all(&config_dir)?;
@@ -0,0 +1,252 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::{mpsc, Semaphore};
+
+use crate::git_engine::GitEngine;
+use crate::types::{DiffRequest, FileDiff, SaveSnapshot};
+
+/// A diff to compute off the UI loop.
+#[derive(Debug, Clone)]
+pub enum DiffJob {
+    /// A file changed on disk; the diff becomes a new event.
+    NewEvent(DiffRequest),
+    /// A file was already changed when gwatch started.
+    Seed(DiffRequest),
+    /// Recompute an event already in the history.
+    Refresh(DiffRequest),
+    /// HEAD, the index or a ref moved; recompute an event and drop it if the
+    /// file no longer differs from its base.
+    Resync(DiffRequest),
+}
+
+impl DiffJob {
+    pub fn request(&self) -> &DiffRequest {
+        match self {
+            Self::NewEvent(request)
+            | Self::Seed(request)
+            | Self::Refresh(request)
+            | Self::Resync(request) => request,
+        }
+    }
+
+    /// Jobs with the same key supersede each other: only the most recently
+    /// submitted one is delivered.
+    fn key(&self) -> JobKey {
+        match self {
+            // A save during the startup scan supersedes the scanned diff.
+            Self::NewEvent(request) | Self::Seed(request) => (request.file_path.clone(), None),
+            Self::Refresh(request) | Self::Resync(request) => {
+                (request.file_path.clone(), Some(request.timestamp))
+            }
+        }
+    }
+}
+
+type JobKey = (PathBuf, Option<DateTime<Utc>>);
+
+pub struct DiffOutcome {
+    pub job: DiffJob,
+    pub relative_path: String,
+    pub result: Result<FileDiff>,
+    pub snapshot: SaveSnapshot,
+    seq: u64,
+}
+
+thread_local! {
+    /// Each blocking thread opens its own repository; `git2::Repository` is
+    /// not `Sync`, and reopening it for every job is wasteful.
+    static ENGINE: RefCell<Option<GitEngine>> = const { RefCell::new(None) };
+}
+
+/// Runs diffs on tokio's blocking pool and reports them over a channel.
+pub struct DiffWorker {
+    repo_root: PathBuf,
+    results: mpsc::UnboundedSender<DiffOutcome>,
+    latest: Arc<Mutex<HashMap<JobKey, u64>>>,
+    permits: Arc<Semaphore>,
+    next_seq: u64,
+    /// Moves on with every file event or resync; the engines only share
+    /// rename scans between jobs that run within the same generation.
+    generation: Arc<AtomicU64>,
+}
+
+impl DiffWorker {
+    pub fn new(repo_root: PathBuf, results: mpsc::UnboundedSender<DiffOutcome>) -> Self {
+        let workers = std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(4);
+        Self {
+            repo_root,
+            results,
+            latest: Arc::new(Mutex::new(HashMap::new())),
+            permits: Arc::new(Semaphore::new(workers)),
+            next_seq: 0,
+            generation: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Queue `job`. Must be called from within a tokio runtime.
+    pub fn submit(&mut self, job: DiffJob) {
+        self.next_seq += 1;
+        let seq = self.next_seq;
+        let key = job.key();
+        lock(&self.latest).insert(key.clone(), seq);
+        // Files or the index changed, so earlier scans may miss a rename.
+        if matches!(job, DiffJob::NewEvent(_) | DiffJob::Resync(_)) {
+            self.generation.fetch_add(1, Ordering::SeqCst);
+        }
+
+        let repo_root = self.repo_root.clone();
+        let results = self.results.clone();
+        let latest = Arc::clone(&self.latest);
+        let permits = Arc::clone(&self.permits);
+        let generation = Arc::clone(&self.generation);
+
+        tokio::spawn(async move {
+            let job_key = key.clone();
+            let job_latest = Arc::clone(&latest);
+            let Ok(_permit) = permits.acquire_owned().await else {
+                return;
+            };
+            let computed = tokio::task::spawn_blocking(move || {
+                // A newer job for the same target was queued while this one
+                // waited for a worker; skip the work entirely.
+                if lock(&job_latest).get(&job_key) != Some(&seq) {
+                    return None;
+                }
+                let request = job.request();
+                let computed = with_engine(&repo_root, |engine| {
+                    engine.reuse_rename_scans(generation.load(Ordering::SeqCst));
+                    let content = save_content(engine, &job);
+                    (
+                        engine.relative_path(&request.file_path),
+                        compute(engine, request, content.as_deref()),
+                        save_snapshot(engine, request, content),
+                    )
+                });
+                let (relative_path, result, snapshot) = match computed {
+                    Ok(computed) => computed,
+                    Err(e) => (String::new(), Err(e), SaveSnapshot::default()),
+                };
+                Some(DiffOutcome {
+                    job,
+                    relative_path,
+                    result,
+                    snapshot,
+                    seq,
+                })
+            })
+            .await;
+
+            match computed {
+                Ok(Some(outcome)) => {
+                    let _ = results.send(outcome);
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    tracing::error!("Diff worker failed for {:?}: {}", key.0, e);
+                    let mut latest = lock(&latest);
+                    if latest.get(&key) == Some(&seq) {
+                        latest.remove(&key);
+                    }
+                }
+            }
+        });
+    }
+
+    /// Hand back `outcome` if it is the latest for its target, or `None` if a
+    /// newer job has superseded it.
+    pub fn accept(&self, outcome: DiffOutcome) -> Option<DiffOutcome> {
+        let key = outcome.job.key();
+        let mut latest = lock(&self.latest);
+        if latest.get(&key) != Some(&outcome.seq) {
+            tracing::debug!("Discarding stale diff for {:?}", key.0);
+            return None;
+        }
+        latest.remove(&key);
+        Some(outcome)
+    }
+
+    /// Files with a diff still being computed.
+    pub fn pending_paths(&self) -> HashSet<PathBuf> {
+        lock(&self.latest)
+            .keys()
+            .map(|(path, _)| path.clone())
+            .collect()
+    }
+}
+
+/// Content of the save a job is for. Events already in the history keep
+/// the save they were made for; new events read the file once, so their
+/// diff and their snapshot come from the same save. `None` for saves whose
+/// content is not kept (deleted, binary or too large).
+fn save_content(engine: &GitEngine, job: &DiffJob) -> Option<Arc<str>> {
+    match job {
+        DiffJob::NewEvent(request) | DiffJob::Seed(request) => request
+            .content
+            .clone()
+            .or_else(|| engine.read_snapshot(&request.file_path).map(Arc::from)),
+        DiffJob::Refresh(request) | DiffJob::Resync(request) => request.content.clone(),
+    }
+}
+
+/// Diff of `content`, the save a request is for. Without it the file on disk
+/// is diffed.
+fn compute(engine: &GitEngine, request: &DiffRequest, content: Option<&str>) -> Result<FileDiff> {
+    match content {
+        Some(content) => engine.compute_for_mode_with_content(
+            &request.file_path,
+            request.mode,
+            content,
+            request.context_lines,
+        ),
+        None => engine.compute_for_mode(&request.file_path, request.mode, request.context_lines),
+    }
+}
+
+/// The save a request is for, and its diff since the previous save when the
+/// request carries one.
+fn save_snapshot(
+    engine: &GitEngine,
+    request: &DiffRequest,
+    content: Option<Arc<str>>,
+) -> SaveSnapshot {
+    let diff = match (&request.previous, &content) {
+        (Some(previous), Some(content)) => engine
+            .diff_contents(previous, content, request.context_lines)
+            .map_err(|e| {
+                tracing::warn!(
+                    "Failed to diff {:?} against its previous save: {}",
+                    request.file_path,
+                    e
+                )
+            })
+            .ok(),
+        _ => None,
+    };
+    SaveSnapshot {
+        content,
+        previous: request.previous.clone(),
+        diff,
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn with_engine<R>(repo_root: &Path, f: impl FnOnce(&GitEngine) -> R) -> Result<R> {
+    ENGINE.with(|cell| {
+        let mut engine = cell.borrow_mut();
+        let engine = match engine.take() {
+            Some(open) if open.repo_root() == repo_root => engine.insert(open),
+            _ => engine.insert(GitEngine::new(repo_root)?),
+        };
+        Ok(f(engine))
+    })
+}
@@ -1,18 +1,44 @@
-use anyhow::{Context, Result};
-use git2::Repository;
-use similar::{ChangeTag, TextDiff};
-use std::path::Path;
-
-use crate::types::{DiffHunk, DiffKind, DiffLine, DiffStats, FileDiff};
+use anyhow::{bail, Context, Result};
+use git2::{
+    ApplyLocation, Delta, DiffFindOptions, DiffOptions, ObjectType, Repository, Status,
+    StatusOptions,
+};
+use similar::{ChangeTag, DiffOp, InlineChange, TextDiff};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use crate::types::{
+    CommitInfo, DiffHunk, DiffKind, DiffLine, DiffMode, DiffStats, FileDiff, HeadInfo, IndexAction,
+    StagedFile,
+};
 
 const LARGE_FILE_WARN_SIZE: u64 = 1024 * 1024; // 1MB
 const LARGE_FILE_SKIP_SIZE: u64 = 10 * 1024 * 1024; // 10MB
 const MAX_DIFF_LINES: usize = 5000;
 const TRUNCATE_KEEP_LINES: usize = 100;
+pub const DEFAULT_CONTEXT_LINES: usize = 3;
+
+/// Scans for rename candidates kept for reuse; see
+/// [`GitEngine::reuse_rename_scans`].
+#[derive(Default)]
+struct RenameScans {
+    generation: Option<u64>,
+    scans: Vec<RenameScan>,
+}
+
+struct RenameScan {
+    mode: DiffMode,
+    counterpart: Delta,
+    paths: Rc<[PathBuf]>,
+}
 
 pub struct GitEngine {
     repo: Repository,
     repo_root: std::path::PathBuf,
+    rename_scans: RefCell<RenameScans>,
 }
 
 impl GitEngine {
@@ -25,13 +51,223 @@ impl GitEngine {
             .context("Repository has no working directory")?
             .to_path_buf();
 
-        Ok(Self { repo, repo_root })
+        Ok(Self {
+            repo,
+            repo_root,
+            rename_scans: RefCell::default(),
+        })
     }
 
     pub fn repo_root(&self) -> &Path {
         &self.repo_root
     }
 
+    /// The `.git` directory, holding `HEAD` and the index.
+    pub fn git_dir(&self) -> &Path {
+        self.repo.path()
+    }
+
+    /// Directory holding refs shared by all worktrees; the same as
+    /// [`GitEngine::git_dir`] outside linked worktrees.
+    pub fn common_dir(&self) -> &Path {
+        self.repo.commondir()
+    }
+
+    /// Branch and commit HEAD currently points at.
+    pub fn head_info(&self) -> HeadInfo {
+        match self.repo.head() {
+            Ok(head) => HeadInfo {
+                branch: head
+                    .is_branch()
+                    .then(|| head.shorthand().map(str::to_string))
+                    .flatten(),
+                commit: head
+                    .peel_to_commit()
+                    .ok()
+                    .and_then(|c| c.as_object().short_id().ok())
+                    .and_then(|id| id.as_str().map(str::to_string)),
+            },
+            // An unborn branch: HEAD names a branch that has no commit yet.
+            Err(_) => HeadInfo {
+                branch: self
+                    .repo
+                    .find_reference("HEAD")
+                    .ok()
+                    .and_then(|r| r.symbolic_target().map(str::to_string))
+                    .map(|target| {
+                        target
+                            .strip_prefix("refs/heads/")
+                            .unwrap_or(&target)
+                            .to_string()
+                    }),
+                commit: None,
+            },
+        }
+    }
+
+    /// A name for the repository that survives it being moved or cloned: the
+    /// id of the commit its history starts from, following first parents
+    /// from HEAD. Before the first commit, a hash of its path stands in.
+    ///
+    /// The id is worked out once and kept in `ids`, a JSON map from
+    /// repository paths to ids, so it stays the same after the first commit
+    /// and the history is not walked again.
+    pub fn repository_id(&self, ids: &Path) -> String {
+        let key = self
+            .repo_root
+            .canonicalize()
+            .unwrap_or_else(|_| self.repo_root.clone());
+        let known = read_repository_ids(ids);
+        if let Some(id) = known
+            .as_ref()
+            .ok()
+            .and_then(|known| known.get(&key))
+            .filter(|id| !id.is_empty())
+        {
+            return id.clone();
+        }
+        let id = match self.root_commit() {
+            Ok(oid) => oid.to_string(),
+            Err(_) => git2::Oid::hash_object(ObjectType::Blob, key.to_string_lossy().as_bytes())
+                .map(|oid| oid.to_string())
+                .unwrap_or_default(),
+        };
+        // Ids of other repositories are not thrown away over a file that
+        // cannot be read.
+        match known {
+            Ok(mut known) => {
+                known.insert(key, id.clone());
+                if let Err(e) = write_repository_ids(ids, &known) {
+                    tracing::warn!("Failed to save the repository id to {:?}: {}", ids, e);
+                }
+            }
+            Err(e) => tracing::warn!("Failed to read repository ids {:?}: {}", ids, e),
+        }
+        id
+    }
+
+    fn root_commit(&self) -> Result<git2::Oid> {
+        let mut walk = self.repo.revwalk()?;
+        walk.push_head()?;
+        walk.simplify_first_parent()?;
+        let mut root = None;
+        for oid in walk {
+            root = Some(oid?);
+        }
+        root.context("No commits")
+    }
+
+    /// Files staged for the next commit.
+    pub fn staged_files(&self) -> Result<Vec<StagedFile>> {
+        let head_tree = self.repo.head().ok().and_then(|h| h.peel_to_tree().ok());
+        let mut index = self.repo.index()?;
+        index.read(false)?;
+        let mut diff = self
+            .repo
+            .diff_tree_to_index(head_tree.as_ref(), Some(&index), None)?;
+        diff.find_similar(Some(DiffFindOptions::new().renames(true)))?;
+        Ok(diff
+            .deltas()
+            .filter_map(|delta| {
+                let status = match delta.status() {
+                    Delta::Added => 'A',
+                    Delta::Modified => 'M',
+                    Delta::Deleted => 'D',
+                    Delta::Renamed => 'R',
+                    Delta::Typechange => 'T',
+                    _ => return None,
+                };
+                let path = delta.new_file().path().or(delta.old_file().path())?;
+                Some(StagedFile {
+                    status,
+                    path: path.to_string_lossy().into_owned(),
+                })
+            })
+            .collect())
+    }
+
+    /// Staged files, signature and HEAD message for the commit editor.
+    pub fn commit_info(&self) -> CommitInfo {
+        let staged = self.staged_files().unwrap_or_else(|e| {
+            tracing::warn!("Failed to list staged files: {}", e);
+            Vec::new()
+        });
+        let signature = self
+            .repo
+            .signature()
+            .map(|s| {
+                format!(
+                    "{} <{}>",
+                    s.name().unwrap_or_default(),
+                    s.email().unwrap_or_default()
+                )
+            })
+            .map_err(|e| e.message().to_string());
+        let head_message = self
+            .repo
+            .head()
+            .ok()
+            .and_then(|h| h.peel_to_commit().ok())
+            .and_then(|c| c.message().map(str::to_string));
+        CommitInfo {
+            staged,
+            signature,
+            head_message,
+        }
+    }
+
+    /// Commit the index with `message`, signed with `user.name` and
+    /// `user.email` from the git config. With `amend` the HEAD commit is
+    /// replaced instead, keeping its author. Returns the new commit's short
+    /// id.
+    pub fn commit(&self, message: &str, amend: bool) -> Result<String> {
+        let message = git2::message_prettify(message, None)?;
+        if message.trim().is_empty() {
+            bail!("The commit message is empty");
+        }
+        let signature = self
+            .repo
+            .signature()
+            .context("Set user.name and user.email in the git config to commit")?;
+
+        let mut index = self.repo.index()?;
+        index.read(false)?;
+        let tree = self.repo.find_tree(index.write_tree()?)?;
+        let head = self.repo.head().ok().and_then(|h| h.peel_to_commit().ok());
+        let oid = if amend {
+            let head = head.context("There is no commit to amend")?;
+            head.amend(
+                Some("HEAD"),
+                None,
+                Some(&signature),
+                None,
+                Some(&message),
+                Some(&tree),
+            )?
+        } else {
+            let unchanged = match &head {
+                Some(head) => head.tree_id() == tree.id(),
+                None => index.is_empty(),
+            };
+            if unchanged {
+                bail!("Nothing staged to commit");
+            }
+            let parents: Vec<_> = head.iter().collect();
+            self.repo.commit(
+                Some("HEAD"),
+                &signature,
+                &signature,
+                &message,
+                &tree,
+                &parents,
+            )?
+        };
+
+        let commit = self.repo.find_commit(oid)?;
+        let short_id = commit.as_object().short_id()?;
+        Ok(short_id.as_str().unwrap_or_default().to_string())
+    }
+
     pub fn relative_path(&self, path: &Path) -> String {
         path.strip_prefix(&self.repo_root)
             .unwrap_or(path)
@@ -39,16 +275,191 @@ impl GitEngine {
             .to_string()
     }
 
+    /// Diff `file_path` against the base selected by `mode`, keeping
+    /// `context_lines` unchanged lines around each change.
+    pub fn compute_for_mode(
+        &self,
+        file_path: &Path,
+        mode: DiffMode,
+        context_lines: usize,
+    ) -> Result<FileDiff> {
+        match mode {
+            DiffMode::All => self.compute_diff_with_context(file_path, context_lines),
+            DiffMode::Staged => self.compute_staged_diff_with_context(file_path, context_lines),
+            DiffMode::Unstaged => self.compute_unstaged_diff_with_context(file_path, context_lines),
+        }
+    }
+
+    /// Like [`compute_for_mode`](Self::compute_for_mode), but diffs
+    /// `content`, the file as it was at an earlier save, instead of the file
+    /// on disk. Staged diffs do not involve the working tree and are computed
+    /// as usual.
+    pub fn compute_for_mode_with_content(
+        &self,
+        file_path: &Path,
+        mode: DiffMode,
+        content: &str,
+        context_lines: usize,
+    ) -> Result<FileDiff> {
+        if mode == DiffMode::Staged {
+            return self.compute_staged_diff_with_context(file_path, context_lines);
+        }
+        let relative_path = self.to_relative_path(file_path);
+        let diff = match self.get_base_content(&relative_path, mode)? {
+            Some(old) => self.diff_strings(&old, content, context_lines)?,
+            None => self.added_diff(&relative_path, mode, content, context_lines)?,
+        };
+        self.finalize_diff(diff, content.len() as u64, false)
+    }
+
     pub fn compute_diff(&self, file_path: &Path) -> Result<FileDiff> {
+        self.compute_diff_with_context(file_path, DEFAULT_CONTEXT_LINES)
+    }
+
+    pub fn compute_staged_diff(&self, file_path: &Path) -> Result<FileDiff> {
+        self.compute_staged_diff_with_context(file_path, DEFAULT_CONTEXT_LINES)
+    }
+
+    pub fn compute_unstaged_diff(&self, file_path: &Path) -> Result<FileDiff> {
+        self.compute_unstaged_diff_with_context(file_path, DEFAULT_CONTEXT_LINES)
+    }
+
+    /// Files that differ from the base of `mode`, as absolute paths, most
+    /// recently modified first. Ignored files are skipped, as in `git status`.
+    pub fn changed_files(&self, mode: DiffMode) -> Result<Vec<PathBuf>> {
+        let index = Status::INDEX_NEW
+            | Status::INDEX_MODIFIED
+            | Status::INDEX_DELETED
+            | Status::INDEX_RENAMED
+            | Status::INDEX_TYPECHANGE;
+        let worktree = Status::WT_NEW
+            | Status::WT_MODIFIED
+            | Status::WT_DELETED
+            | Status::WT_RENAMED
+            | Status::WT_TYPECHANGE;
+        let wanted = match mode {
+            DiffMode::All => index | worktree | Status::CONFLICTED,
+            DiffMode::Unstaged => worktree,
+            DiffMode::Staged => index,
+        };
+
+        let mut opts = StatusOptions::new();
+        opts.include_untracked(true)
+            .recurse_untracked_dirs(true)
+            .include_ignored(false)
+            .exclude_submodules(true);
+        let statuses = self.repo.statuses(Some(&mut opts))?;
+
+        let mut files: Vec<(PathBuf, Option<std::time::SystemTime>)> = statuses
+            .iter()
+            .filter(|entry| entry.status().intersects(wanted))
+            .filter_map(|entry| entry.path().map(|p| self.repo_root.join(p)))
+            .map(|path| {
+                let modified = std::fs::metadata(&path).and_then(|m| m.modified()).ok();
+                (path, modified)
+            })
+            .collect();
+        // Deleted files have no modification time and sort last.
+        files.sort_by_key(|(_, modified)| std::cmp::Reverse(*modified));
+        Ok(files.into_iter().map(|(path, _)| path).collect())
+    }
+
+    /// Let the diffs that follow share their scans for rename candidates
+    /// while `generation` stays the same, so the events of one burst, such
+    /// as a whole directory being deleted, scan the working tree once between
+    /// them. Callers move `generation` on whenever files or the index may
+    /// have changed. Without it every diff scans afresh.
+    pub fn reuse_rename_scans(&self, generation: u64) {
+        let mut rename_scans = self.rename_scans.borrow_mut();
+        if rename_scans.generation != Some(generation) {
+            rename_scans.generation = Some(generation);
+            rename_scans.scans.clear();
+        }
+    }
+
+    /// Text content of `file_path` as saved, or `None` when it is missing,
+    /// binary or too large to keep in memory.
+    pub fn read_snapshot(&self, file_path: &Path) -> Option<String> {
+        let size = std::fs::metadata(file_path).ok()?.len();
+        if size > LARGE_FILE_WARN_SIZE {
+            return None;
+        }
+        let bytes = std::fs::read(file_path).ok()?;
+        if bytes.contains(&0) {
+            return None;
+        }
+        Some(String::from_utf8_lossy(&bytes).into_owned())
+    }
+
+    /// Diff two versions of a file held in memory, such as consecutive saves.
+    pub fn diff_contents(&self, old: &str, new: &str, context_lines: usize) -> Result<FileDiff> {
+        let diff = self.diff_strings(old, new, context_lines)?;
+        self.finalize_diff(diff, new.len() as u64, false)
+    }
+
+    /// Stage or unstage the changed lines among `lines`, given as indices
+    /// into the lines of hunk `hunk_index` of `diff`, the diff of
+    /// `file_path` computed in `mode`. Fails if the file has changed so that
+    /// the lines no longer apply.
+    pub fn apply_lines(
+        &self,
+        file_path: &Path,
+        diff: &FileDiff,
+        mode: DiffMode,
+        hunk_index: usize,
+        lines: RangeInclusive<usize>,
+        action: IndexAction,
+    ) -> Result<()> {
+        let relative = self.to_relative_path(file_path);
+        let mut patch = hunk_patch(diff, hunk_index, lines, action == IndexAction::Unstage)
+            .context("No changed lines to apply")?;
+        if mode == DiffMode::All {
+            patch.from = self.index_line(&relative, patch.from, action)?;
+        }
+        let patch = patch.render(&relative.to_string_lossy());
+        let patch = git2::Diff::from_buffer(patch.as_bytes())?;
+        // Another engine or git itself may have written the index since it
+        // was last read here.
+        self.repo.index()?.read(true)?;
+        self.repo
+            .apply(&patch, ApplyLocation::Index, None)
+            .context("The lines no longer apply; the file changed since it was diffed")
+    }
+
+    /// Where line `line` of the side a diff in `DiffMode::All` is applied
+    /// from is in the index: HEAD's content when staging, the working tree's
+    /// when unstaging. Changes already staged above it shift it, and libgit2
+    /// only applies a patch at the exact line it names.
+    fn index_line(&self, relative_path: &Path, line: usize, action: IndexAction) -> Result<usize> {
+        let source = match action {
+            IndexAction::Stage => self.get_head_content(relative_path)?,
+            IndexAction::Unstage => std::fs::read(self.repo_root.join(relative_path))
+                .ok()
+                .map(|bytes| String::from_utf8_lossy(&bytes).into_owned()),
+        };
+        let (Some(source), Some(index)) = (source, self.get_index_content(relative_path)?) else {
+            return Ok(line);
+        };
+        match map_line(&source, &index, line) {
+            Some(line) => Ok(line),
+            None if action == IndexAction::Stage => {
+                bail!("Part of these lines is already staged; stage them from the Unstaged mode")
+            }
+            None => bail!("Part of these lines is not staged; unstage them from the Staged mode"),
+        }
+    }
+
+    fn compute_diff_with_context(
+        &self,
+        file_path: &Path,
+        context_lines: usize,
+    ) -> Result<FileDiff> {
         let relative_path = self.to_relative_path(file_path);
 
         let metadata = match std::fs::metadata(file_path) {
             Ok(m) => m,
             Err(_) => {
-                return Ok(FileDiff {
-                    is_deleted: true,
-                    ..Default::default()
-                });
+                return self.deleted_diff(&relative_path, DiffMode::All, context_lines);
             }
         };
 
@@ -91,60 +502,62 @@ impl GitEngine {
                 String::from_utf8_lossy(&bytes).to_string()
             }
             Err(_) => {
-                return Ok(FileDiff {
-                    is_deleted: true,
-                    ..Default::default()
-                });
+                return self.deleted_diff(&relative_path, DiffMode::All, context_lines);
             }
         };
 
         let head_content = self.get_head_content(&relative_path)?;
 
         let diff = match head_content {
-            Some(old_content) => self.diff_strings(&old_content, &current_content)?,
-            None => {
-                let mut diff = self.diff_strings("", &current_content)?;
-                diff.is_new_file = true;
-                diff
+            Some(old_content) => {
+                self.diff_strings(&old_content, &current_content, context_lines)?
             }
+            None => self.added_diff(
+                &relative_path,
+                DiffMode::All,
+                &current_content,
+                context_lines,
+            )?,
         };
 
         self.finalize_diff(diff, file_size, warn_large_file)
     }
 
-    pub fn compute_staged_diff(&self, file_path: &Path) -> Result<FileDiff> {
+    fn compute_staged_diff_with_context(
+        &self,
+        file_path: &Path,
+        context_lines: usize,
+    ) -> Result<FileDiff> {
         let relative_path = self.to_relative_path(file_path);
 
         let index_content = self.get_index_content(&relative_path)?;
         let head_content = self.get_head_content(&relative_path)?;
 
         let diff = match (head_content, index_content) {
-            (Some(old), Some(new)) => self.diff_strings(&old, &new)?,
+            (Some(old), Some(new)) => self.diff_strings(&old, &new, context_lines)?,
             (None, Some(new)) => {
-                let mut diff = self.diff_strings("", &new)?;
-                diff.is_new_file = true;
-                diff
+                self.added_diff(&relative_path, DiffMode::Staged, &new, context_lines)?
+            }
+            (Some(_), None) => {
+                self.deleted_diff(&relative_path, DiffMode::Staged, context_lines)?
             }
-            (Some(_), None) => FileDiff {
-                is_deleted: true,
-                ..Default::default()
-            },
             (None, None) => FileDiff::default(),
         };
 
         Ok(diff)
     }
 
-    pub fn compute_unstaged_diff(&self, file_path: &Path) -> Result<FileDiff> {
+    fn compute_unstaged_diff_with_context(
+        &self,
+        file_path: &Path,
+        context_lines: usize,
+    ) -> Result<FileDiff> {
         let relative_path = self.to_relative_path(file_path);
 
         let metadata = match std::fs::metadata(file_path) {
             Ok(m) => m,
             Err(_) => {
-                return Ok(FileDiff {
-                    is_deleted: true,
-                    ..Default::default()
-                });
+                return self.deleted_diff(&relative_path, DiffMode::Unstaged, context_lines);
             }
         };
         let file_size = metadata.len();
@@ -160,27 +573,25 @@ impl GitEngine {
                 String::from_utf8_lossy(&bytes).to_string()
             }
             Err(_) => {
-                return Ok(FileDiff {
-                    is_deleted: true,
-                    ..Default::default()
-                });
+                return self.deleted_diff(&relative_path, DiffMode::Unstaged, context_lines);
             }
         };
 
         let index_content = self.get_index_content(&relative_path)?;
 
         let diff = match index_content {
-            Some(old) => self.diff_strings(&old, &current_content)?,
+            Some(old) => self.diff_strings(&old, &current_content, context_lines)?,
             None => {
                 // Not in index, check if it's in HEAD
                 let head_content = self.get_head_content(&relative_path)?;
                 match head_content {
-                    Some(old) => self.diff_strings(&old, &current_content)?,
-                    None => {
-                        let mut diff = self.diff_strings("", &current_content)?;
-                        diff.is_new_file = true;
-                        diff
-                    }
+                    Some(old) => self.diff_strings(&old, &current_content, context_lines)?,
+                    None => self.added_diff(
+                        &relative_path,
+                        DiffMode::Unstaged,
+                        &current_content,
+                        context_lines,
+                    )?,
                 }
             }
         };
@@ -188,6 +599,172 @@ impl GitEngine {
         self.finalize_diff(diff, file_size, false)
     }
 
+    /// Diff for a file that has no base version. If git's similarity
+    /// detection pairs it with a deleted file, it is diffed as a rename of
+    /// that file instead of as all-new content.
+    fn added_diff(
+        &self,
+        relative_path: &Path,
+        mode: DiffMode,
+        content: &str,
+        context_lines: usize,
+    ) -> Result<FileDiff> {
+        if let Some((from, _)) = self.find_rename(relative_path, mode, Delta::Deleted)? {
+            if let Some(old) = self.get_base_content(&from, mode)? {
+                let mut diff = self.diff_strings(&old, content, context_lines)?;
+                diff.renamed_from = Some(from.to_string_lossy().to_string());
+                return Ok(diff);
+            }
+        }
+
+        let mut diff = self.diff_strings("", content, context_lines)?;
+        diff.is_new_file = true;
+        Ok(diff)
+    }
+
+    /// Diff for a file that no longer exists on the side selected by `mode`:
+    /// its whole base content, removed. Untracked files and files that were
+    /// renamed yield an empty diff; the event for the new path shows a rename.
+    fn deleted_diff(
+        &self,
+        relative_path: &Path,
+        mode: DiffMode,
+        context_lines: usize,
+    ) -> Result<FileDiff> {
+        let Some(old) = self.get_base_content(relative_path, mode)? else {
+            return Ok(FileDiff::default());
+        };
+        if self
+            .find_rename(relative_path, mode, Delta::Added)?
+            .is_some()
+        {
+            return Ok(FileDiff::default());
+        }
+        let mut diff = self.diff_strings(&old, "", context_lines)?;
+        diff.is_deleted = true;
+        Ok(diff)
+    }
+
+    /// Content `mode` diffs against: the index (falling back to HEAD) for
+    /// unstaged changes, HEAD otherwise.
+    fn get_base_content(&self, relative_path: &Path, mode: DiffMode) -> Result<Option<String>> {
+        match mode {
+            DiffMode::Unstaged => match self.get_index_content(relative_path)? {
+                Some(content) => Ok(Some(content)),
+                None => self.get_head_content(relative_path),
+            },
+            DiffMode::All | DiffMode::Staged => self.get_head_content(relative_path),
+        }
+    }
+
+    /// Find a rename involving `relative_path` on either side, using git's
+    /// similarity-based detection. `counterpart` is the kind of change the
+    /// other side of the rename would be: `Deleted` for a new file, `Added`
+    /// for a deleted one. Returns `(from, to)`.
+    ///
+    /// Only `relative_path` and the files changed that way are compared, so
+    /// a save does not cost a similarity search over the whole repository.
+    fn find_rename(
+        &self,
+        relative_path: &Path,
+        mode: DiffMode,
+        counterpart: Delta,
+    ) -> Result<Option<(PathBuf, PathBuf)>> {
+        let candidates = self.rename_candidates(mode, counterpart)?;
+        if candidates.is_empty() {
+            return Ok(None);
+        }
+
+        let mut opts = DiffOptions::new();
+        opts.include_untracked(true)
+            .recurse_untracked_dirs(true)
+            .disable_pathspec_match(true)
+            .pathspec(relative_path);
+        for candidate in candidates.iter() {
+            opts.pathspec(candidate);
+        }
+        let mut diff = self.diff_for_mode(mode, &mut opts)?;
+        let mut find = DiffFindOptions::new();
+        find.renames(true).for_untracked(true);
+        diff.find_similar(Some(&mut find))?;
+
+        for delta in diff.deltas() {
+            if delta.status() != Delta::Renamed {
+                continue;
+            }
+            let (Some(from), Some(to)) = (delta.old_file().path(), delta.new_file().path()) else {
+                continue;
+            };
+            if from == relative_path || to == relative_path {
+                return Ok(Some((from.to_path_buf(), to.to_path_buf())));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Files deleted (`counterpart` is `Deleted`) or added, untracked ones
+    /// included, in the diff of `mode`. Reused within a generation set by
+    /// [`reuse_rename_scans`](Self::reuse_rename_scans).
+    fn rename_candidates(&self, mode: DiffMode, counterpart: Delta) -> Result<Rc<[PathBuf]>> {
+        if let Some(scan) = self
+            .rename_scans
+            .borrow()
+            .scans
+            .iter()
+            .find(|scan| scan.mode == mode && scan.counterpart == counterpart)
+        {
+            return Ok(Rc::clone(&scan.paths));
+        }
+
+        // Deletions are all tracked, so looking for them skips the walk
+        // through untracked directories.
+        let added = counterpart == Delta::Added;
+        let mut opts = DiffOptions::new();
+        opts.include_untracked(added).recurse_untracked_dirs(added);
+        let diff = self.diff_for_mode(mode, &mut opts)?;
+        let paths: Rc<[PathBuf]> = diff
+            .deltas()
+            .filter(|delta| match counterpart {
+                Delta::Added => matches!(delta.status(), Delta::Added | Delta::Untracked),
+                _ => delta.status() == counterpart,
+            })
+            .filter_map(|delta| {
+                let file = if added {
+                    delta.new_file()
+                } else {
+                    delta.old_file()
+                };
+                file.path().map(Path::to_path_buf)
+            })
+            .collect();
+
+        let mut rename_scans = self.rename_scans.borrow_mut();
+        if rename_scans.generation.is_some() {
+            rename_scans.scans.push(RenameScan {
+                mode,
+                counterpart,
+                paths: Rc::clone(&paths),
+            });
+        }
+        Ok(paths)
+    }
+
+    /// Diff of the whole repository between the two sides `mode` compares.
+    fn diff_for_mode(&self, mode: DiffMode, opts: &mut DiffOptions) -> Result<git2::Diff<'_>> {
+        let head_tree = self.repo.head().ok().and_then(|h| h.peel_to_tree().ok());
+        let diff = match mode {
+            DiffMode::All => self
+                .repo
+                .diff_tree_to_workdir_with_index(head_tree.as_ref(), Some(opts))?,
+            DiffMode::Unstaged => self.repo.diff_index_to_workdir(None, Some(opts))?,
+            DiffMode::Staged => {
+                self.repo
+                    .diff_tree_to_index(head_tree.as_ref(), None, Some(opts))?
+            }
+        };
+        Ok(diff)
+    }
+
     fn to_relative_path(&self, path: &Path) -> std::path::PathBuf {
         match path.strip_prefix(&self.repo_root) {
             Ok(p) => p.to_path_buf(),
@@ -297,8 +874,8 @@ impl GitEngine {
 
         let tree = head.peel_to_tree()?;
         let entry = match tree.get_path(relative_path) {
-            Ok(e) => e,
-            Err(_) => return Ok(None),
+            Ok(e) if e.kind() == Some(ObjectType::Blob) => e,
+            _ => return Ok(None),
         };
 
         let blob = self.repo.find_blob(entry.id())?;
@@ -329,13 +906,13 @@ impl GitEngine {
         Ok(Some(content))
     }
 
-    fn diff_strings(&self, old: &str, new: &str) -> Result<FileDiff> {
+    fn diff_strings(&self, old: &str, new: &str, context_lines: usize) -> Result<FileDiff> {
         let text_diff = TextDiff::from_lines(old, new);
 
         let mut hunks = Vec::new();
         let mut stats = DiffStats::default();
 
-        for group in text_diff.grouped_ops(3) {
+        for group in text_diff.grouped_ops(context_lines) {
             let mut hunk_lines = Vec::new();
             let mut old_start = 0;
             let mut old_count = 0;
@@ -344,7 +921,7 @@ impl GitEngine {
             let mut first = true;
 
             for op in group {
-                for change in text_diff.iter_changes(&op) {
+                for change in text_diff.iter_inline_changes(&op) {
                     let old_ln = change.old_index().map(|i| i + 1);
                     let new_ln = change.new_index().map(|i| i + 1);
 
@@ -373,11 +950,14 @@ impl GitEngine {
                         new_count += 1;
                     }
 
+                    let (content, emphasis) = inline_segments(&change);
                     hunk_lines.push(DiffLine {
                         old_line_number: old_ln,
                         new_line_number: new_ln,
                         kind,
-                        content: change.value().trim_end_matches('\n').to_string(),
+                        content,
+                        emphasis,
+                        missing_newline: change.missing_newline(),
                     });
                 }
             }
@@ -402,10 +982,204 @@ impl GitEngine {
             is_truncated: false,
             omitted_lines: 0,
             truncation_reason: None,
+            context_lines,
+            renamed_from: None,
         })
     }
 }
 
+/// A single-hunk patch, starting at line `from` of the content it applies to.
+struct HunkPatch {
+    from: usize,
+    from_count: usize,
+    to_count: usize,
+    body: String,
+}
+
+impl HunkPatch {
+    fn render(&self, path: &str) -> String {
+        let Self {
+            from,
+            from_count,
+            to_count,
+            body,
+        } = self;
+        // The patch has no other hunks, so both sides start at the same
+        // place. libgit2 looks the change up at line `to - 1` and nowhere
+        // else, so a pure insertion starts one line after the line it follows.
+        let to = if *from_count == 0 { from + 1 } else { *from };
+        format!(
+            "diff --git a/{path} b/{path}\n--- a/{path}\n+++ b/{path}\n@@ -{from},{from_count} +{to},{to_count} @@\n{body}"
+        )
+    }
+}
+
+/// A patch holding only the changes among `lines` of hunk `hunk_index` of
+/// `diff`. `reverse` swaps its sides, to undo the change instead of making
+/// it. An unselected line the patch would remove stays as context, and one
+/// it would add is left out. `None` when nothing would change.
+fn hunk_patch(
+    diff: &FileDiff,
+    hunk_index: usize,
+    lines: RangeInclusive<usize>,
+    reverse: bool,
+) -> Option<HunkPatch> {
+    let hunk = diff.hunks.get(hunk_index)?;
+
+    // A side with no lines has no line number of its own; as in unified
+    // diffs it names the line the change comes after. Earlier hunks shift
+    // the new side by the lines they add or remove.
+    let shift: isize = diff.hunks[..hunk_index]
+        .iter()
+        .map(|h| h.new_count as isize - h.old_count as isize)
+        .sum();
+    let old_start = if hunk.old_count == 0 {
+        (hunk.new_start as isize - 1 - shift).max(0) as usize
+    } else {
+        hunk.old_start
+    };
+    let new_start = if hunk.new_count == 0 {
+        (hunk.old_start as isize - 1 + shift).max(0) as usize
+    } else {
+        hunk.new_start
+    };
+    let from = if reverse { new_start } else { old_start };
+
+    // Each line the patch keeps, with whether it is on the side the patch
+    // applies to and on the side it produces.
+    let mut kept: Vec<(&DiffLine, bool, bool)> = Vec::new();
+    let mut changes = 0;
+    for (i, line) in hunk.lines.iter().enumerate() {
+        let selected = lines.contains(&i);
+        let adds = matches!(
+            (&line.kind, reverse),
+            (DiffKind::Added, false) | (DiffKind::Deleted, true)
+        );
+        if line.kind != DiffKind::Context && selected {
+            changes += 1;
+        }
+        match line.kind {
+            DiffKind::Context => kept.push((line, true, true)),
+            _ if !selected && adds => {}
+            _ if !selected => kept.push((line, true, true)),
+            _ => kept.push((line, !adds, adds)),
+        }
+    }
+    if changes == 0 {
+        return None;
+    }
+
+    // Only the last line of a side can lack its newline. A line the patch
+    // leaves alone but that gains or loses the newline is a change. libgit2
+    // takes the "\ No newline" marker only once no old lines are left, so
+    // each run of changes lists its removals before its additions.
+    let last_from = kept.iter().rposition(|(_, from, _)| *from);
+    let last_to = kept.iter().rposition(|(_, _, to)| *to);
+    let mut body = String::new();
+    let mut added = String::new();
+    let (mut from_count, mut to_count) = (0, 0);
+    let push = |out: &mut String, prefix: char, content: &str, missing_newline: bool| {
+        out.push(prefix);
+        out.push_str(content);
+        out.push('\n');
+        if missing_newline {
+            out.push_str("\\ No newline at end of file\n");
+        }
+    };
+    for (i, (line, from, to)) in kept.iter().enumerate() {
+        let from_missing = *from && line.missing_newline && Some(i) == last_from;
+        let to_missing = *to && line.missing_newline && Some(i) == last_to;
+        if *from {
+            from_count += 1;
+        }
+        if *to {
+            to_count += 1;
+        }
+        match (from, to) {
+            (true, true) if from_missing == to_missing => {
+                body.push_str(&std::mem::take(&mut added));
+                push(&mut body, ' ', &line.content, from_missing);
+            }
+            (true, true) => {
+                push(&mut body, '-', &line.content, from_missing);
+                push(&mut added, '+', &line.content, to_missing);
+            }
+            (true, false) => push(&mut body, '-', &line.content, from_missing),
+            _ => push(&mut added, '+', &line.content, to_missing),
+        }
+    }
+    body.push_str(&added);
+    Some(HunkPatch {
+        from,
+        from_count,
+        to_count,
+        body,
+    })
+}
+
+/// The line of `target` that line `line` of `source` became, both 1-based;
+/// 0 stands for the start of the file. `None` when the line itself differs.
+fn map_line(source: &str, target: &str, line: usize) -> Option<usize> {
+    if line == 0 {
+        return Some(0);
+    }
+    let diff = TextDiff::from_lines(source, target);
+    diff.ops().iter().find_map(|op| match *op {
+        DiffOp::Equal {
+            old_index,
+            new_index,
+            len,
+        } if (old_index..old_index + len).contains(&(line - 1)) => {
+            Some(new_index + line - old_index)
+        }
+        _ => None,
+    })
+}
+
+/// Joins the word-level segments of a changed line and records which char
+/// ranges similar flagged as emphasized. Lines where everything (or nothing)
+/// changed carry no emphasis, since highlighting the whole line adds nothing.
+fn inline_segments(change: &InlineChange<'_, str>) -> (String, Vec<(usize, usize)>) {
+    let mut content = String::new();
+    let mut emphasis: Vec<(usize, usize)> = Vec::new();
+    let mut pos = 0;
+
+    for (emphasized, value) in change.iter_strings_lossy() {
+        let value = value.trim_end_matches('\n');
+        let len = value.chars().count();
+        if emphasized && len > 0 {
+            match emphasis.last_mut() {
+                Some(last) if last.1 == pos => last.1 = pos + len,
+                _ => emphasis.push((pos, pos + len)),
+            }
+        }
+        content.push_str(value);
+        pos += len;
+    }
+
+    if emphasis.len() == 1 && emphasis[0] == (0, pos) {
+        emphasis.clear();
+    }
+
+    (content, emphasis)
+}
+
+fn read_repository_ids(path: &Path) -> Result<BTreeMap<PathBuf, String>> {
+    if !path.exists() {
+        return Ok(BTreeMap::new());
+    }
+    let content = std::fs::read_to_string(path)?;
+    Ok(serde_json::from_str(&content)?)
+}
+
+fn write_repository_ids(path: &Path, ids: &BTreeMap<PathBuf, String>) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, serde_json::to_string_pretty(ids)?)?;
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -507,4 +1281,32 @@ mod tests {
         assert_eq!(diff.stats.added_count, 0);
         assert_eq!(diff.stats.deleted_count, 0);
     }
+
+    #[test]
+    fn test_map_line() {
+        let source = "a\nb\nc\n";
+        let target = "new\na\nB\nc\n";
+        assert_eq!(map_line(source, target, 0), Some(0));
+        assert_eq!(map_line(source, target, 1), Some(2));
+        assert_eq!(map_line(source, target, 2), None);
+        assert_eq!(map_line(source, target, 3), Some(4));
+    }
+
+    #[test]
+    fn test_diff_strings_marks_changed_words() {
+        let temp = TempDir::new().unwrap();
+        init_git_repo(temp.path());
+        let engine = GitEngine::new(temp.path()).unwrap();
+
+        let diff = engine
+            .diff_strings("let value = 1;\n", "let value = 2;\n", 3)
+            .unwrap();
+        let lines = &diff.hunks[0].lines;
+        assert_eq!(lines[0].content, "let value = 1;");
+        assert_eq!(lines[0].emphasis, vec![(12, 14)]);
+        assert_eq!(lines[1].emphasis, vec![(12, 14)]);
+
+        let diff = engine.diff_strings("alpha\n", "omega\n", 3).unwrap();
+        assert!(diff.hunks[0].lines.iter().all(|l| l.emphasis.is_empty()));
+    }
 }
@@ -0,0 +1,409 @@
+use anyhow::Result;
+use chrono::Utc;
+use crossterm::style::{Attribute, Stylize};
+use ratatui::backend::IntoCrossterm;
+use ratatui::style::Color;
+use std::fmt::Write as _;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use tokio::sync::mpsc;
+
+use crate::config::Config;
+use crate::diff_worker::{DiffJob, DiffOutcome, DiffWorker};
+use crate::git_engine::GitEngine;
+use crate::types::{DiffKind, DiffMode, DiffRequest, DisplayedEvent, FileChangeEvent};
+use crate::ui::theme::Theme;
+use crate::watcher::FileWatcher;
+
+/// How changes are written when running without the TUI.
+pub enum Output {
+    /// One JSON object per line.
+    Json,
+    /// A unified patch per change, colored with `theme` when set.
+    Plain { theme: Option<Theme> },
+}
+
+impl Output {
+    fn write<W: Write>(&self, out: &mut W, event: &DisplayedEvent) -> Result<()> {
+        match self {
+            Self::Json => write_event(out, event),
+            Self::Plain { theme } => write_patch(out, event, theme.as_ref()),
+        }
+    }
+}
+
+/// Watch `repo_root` without a terminal and write every change to stdout.
+/// Runs until interrupted or stdout is closed.
+pub async fn run(repo_root: PathBuf, config: &Config, output: Output) -> Result<()> {
+    let (tx, mut files) = mpsc::unbounded_channel::<FileChangeEvent>();
+    let (diff_tx, mut diffs) = mpsc::unbounded_channel::<DiffOutcome>();
+    let mut worker = DiffWorker::new(repo_root.clone(), diff_tx);
+    let _watcher = FileWatcher::new(repo_root, &config.watcher, tx)?;
+
+    let stdout = io::stdout();
+    let interrupted = tokio::signal::ctrl_c();
+    tokio::pin!(interrupted);
+    loop {
+        tokio::select! {
+            Some(file_event) = files.recv() => {
+                worker.submit(DiffJob::NewEvent(DiffRequest {
+                    file_path: file_event.path,
+                    timestamp: Utc::now(),
+                    mode: DiffMode::default(),
+                    context_lines: config.display.context_lines,
+                    previous: None,
+                    content: None,
+                }));
+            }
+            Some(outcome) = diffs.recv() => {
+                let Some(event) = worker.accept(outcome).and_then(into_event) else {
+                    continue;
+                };
+                match output.write(&mut stdout.lock(), &event) {
+                    Ok(()) => {}
+                    Err(e) if is_broken_pipe(&e) => return Ok(()),
+                    Err(e) => return Err(e),
+                }
+            }
+            _ = &mut interrupted => return Ok(()),
+        }
+    }
+}
+
+/// Diffs computed once for `paths`, or for every changed file when `paths` is
+/// empty. A directory stands for the changed files under it; files without
+/// changes are left out.
+pub fn snapshot(
+    engine: &GitEngine,
+    paths: &[PathBuf],
+    mode: DiffMode,
+    context_lines: usize,
+) -> Result<Vec<DisplayedEvent>> {
+    let changed = engine.changed_files(mode)?;
+    let mut files: Vec<PathBuf> = Vec::new();
+    if paths.is_empty() {
+        files = changed;
+    } else {
+        for path in paths {
+            let path = absolute(path)?;
+            if path.is_dir() {
+                files.extend(changed.iter().filter(|f| f.starts_with(&path)).cloned());
+            } else {
+                files.push(path);
+            }
+        }
+        let mut seen = std::collections::HashSet::new();
+        files.retain(|f| seen.insert(f.clone()));
+    }
+
+    // Nothing is watched while the snapshot is taken, so all files can
+    // share one scan for renames.
+    engine.reuse_rename_scans(0);
+    let mut events = Vec::new();
+    for file_path in files {
+        let diff = engine.compute_for_mode(&file_path, mode, context_lines)?;
+        if !diff.has_changes() {
+            continue;
+        }
+        let timestamp = std::fs::metadata(&file_path)
+            .and_then(|m| m.modified())
+            .map(chrono::DateTime::<Utc>::from)
+            .unwrap_or_else(|_| Utc::now());
+        events.push(DisplayedEvent {
+            relative_path: engine.relative_path(&file_path),
+            file_path,
+            timestamp,
+            diff,
+            mode,
+            snapshot: Default::default(),
+        });
+    }
+    Ok(events)
+}
+
+/// Write `events` to stdout in one go, as `gwatch diff` does.
+pub fn print(events: &[DisplayedEvent], output: &Output) -> Result<()> {
+    let mut stdout = io::stdout().lock();
+    for event in events {
+        match output.write(&mut stdout, event) {
+            Ok(()) => {}
+            Err(e) if is_broken_pipe(&e) => return Ok(()),
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+/// `path` made absolute against the working directory, resolving symlinks
+/// when it exists so it lines up with the repository root.
+fn absolute(path: &Path) -> Result<PathBuf> {
+    let path = std::env::current_dir()?.join(path);
+    Ok(path.canonicalize().unwrap_or(path))
+}
+
+/// Write `event` as a single line of JSON and flush, so consumers see each
+/// change as soon as it happens.
+pub fn write_event<W: Write>(out: &mut W, event: &DisplayedEvent) -> Result<()> {
+    let line = serde_json::to_string(event)?;
+    writeln!(out, "{line}")?;
+    out.flush()?;
+    Ok(())
+}
+
+/// Write `event` as a unified patch and flush.
+pub fn write_patch<W: Write>(
+    out: &mut W,
+    event: &DisplayedEvent,
+    theme: Option<&Theme>,
+) -> Result<()> {
+    out.write_all(format_patch(event, theme).as_bytes())?;
+    out.flush()?;
+    Ok(())
+}
+
+/// `event` as a unified patch with `---`/`+++`/`@@` headers and the
+/// extended headers for new, deleted and renamed files, as `git diff` prints
+/// it, so `git apply` takes it. With a theme, lines get ANSI colors from it.
+pub fn format_patch(event: &DisplayedEvent, theme: Option<&Theme>) -> String {
+    let paint = |text: &str, color: fn(&Theme) -> Color, bold: bool| match theme {
+        Some(theme) => {
+            let styled = text.with(color(theme).into_crossterm());
+            if bold {
+                styled.attribute(Attribute::Bold).to_string()
+            } else {
+                styled.to_string()
+            }
+        }
+        None => text.to_string(),
+    };
+
+    let diff = &event.diff;
+    let new_path = &event.relative_path;
+    let old_path = diff.renamed_from.as_deref().unwrap_or(new_path);
+    let old_side = if diff.is_new_file {
+        "/dev/null".to_string()
+    } else {
+        format!("a/{old_path}")
+    };
+    let new_side = if diff.is_deleted {
+        "/dev/null".to_string()
+    } else {
+        format!("b/{new_path}")
+    };
+
+    let mut patch = String::new();
+    let header = |text: String| paint(&text, |t| t.text, true);
+    let _ = writeln!(
+        patch,
+        "{}",
+        header(format!("diff --git a/{old_path} b/{new_path}"))
+    );
+    // gwatch does not track file modes; new and deleted files are taken to
+    // be regular files.
+    if diff.is_new_file {
+        let _ = writeln!(patch, "{}", header("new file mode 100644".to_string()));
+    } else if diff.is_deleted {
+        let _ = writeln!(patch, "{}", header("deleted file mode 100644".to_string()));
+    } else if let Some(from) = &diff.renamed_from {
+        let _ = writeln!(patch, "{}", header(format!("rename from {from}")));
+        let _ = writeln!(patch, "{}", header(format!("rename to {new_path}")));
+    }
+    if diff.is_binary {
+        let _ = writeln!(patch, "Binary files {old_side} and {new_side} differ");
+        return patch;
+    }
+    if diff.hunks.is_empty() {
+        return patch;
+    }
+    let _ = writeln!(patch, "{}", header(format!("--- {old_side}")));
+    let _ = writeln!(patch, "{}", header(format!("+++ {new_side}")));
+
+    // A side with no lines names the line the change comes after, 0 for
+    // the start of the file. Earlier hunks shift the new side by the lines
+    // they add or remove.
+    let mut shift: isize = 0;
+    for hunk in &diff.hunks {
+        let old_start = if hunk.old_count == 0 {
+            (hunk.new_start as isize - 1 - shift).max(0) as usize
+        } else {
+            hunk.old_start
+        };
+        let new_start = if hunk.new_count == 0 {
+            (hunk.old_start as isize - 1 + shift).max(0) as usize
+        } else {
+            hunk.new_start
+        };
+        shift += hunk.new_count as isize - hunk.old_count as isize;
+        let range = format!(
+            "@@ -{},{} +{},{} @@",
+            old_start, hunk.old_count, new_start, hunk.new_count
+        );
+        let _ = writeln!(patch, "{}", paint(&range, |t| t.line_number, false));
+        for line in &hunk.lines {
+            let text = match line.kind {
+                DiffKind::Added => paint(&format!("+{}", line.content), |t| t.added, false),
+                DiffKind::Deleted => paint(&format!("-{}", line.content), |t| t.deleted, false),
+                DiffKind::Context => format!(" {}", line.content),
+            };
+            let _ = writeln!(patch, "{text}");
+            if line.missing_newline {
+                let _ = writeln!(patch, "\\ No newline at end of file");
+            }
+        }
+    }
+    patch
+}
+
+fn into_event(outcome: DiffOutcome) -> Option<DisplayedEvent> {
+    let request = outcome.job.request().clone();
+    let diff = match outcome.result {
+        Ok(diff) => diff,
+        Err(e) => {
+            tracing::warn!("Failed to compute diff for {:?}: {}", request.file_path, e);
+            return None;
+        }
+    };
+    diff.has_changes().then_some(DisplayedEvent {
+        file_path: request.file_path,
+        relative_path: outcome.relative_path,
+        timestamp: request.timestamp,
+        diff,
+        mode: request.mode,
+        snapshot: outcome.snapshot,
+    })
+}
+
+fn is_broken_pipe(e: &anyhow::Error) -> bool {
+    e.downcast_ref::<io::Error>()
+        .is_some_and(|e| e.kind() == io::ErrorKind::BrokenPipe)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{DiffHunk, DiffLine, FileDiff, SaveSnapshot};
+
+    #[test]
+    fn test_write_event_is_one_line() {
+        let event = DisplayedEvent {
+            file_path: PathBuf::from("/repo/src/main.rs"),
+            relative_path: "src/main.rs".to_string(),
+            timestamp: Utc::now(),
+            diff: FileDiff::default(),
+            mode: DiffMode::Staged,
+            snapshot: SaveSnapshot::default(),
+        };
+        let mut out = Vec::new();
+        write_event(&mut out, &event).unwrap();
+        write_event(&mut out, &event).unwrap();
+
+        let text = String::from_utf8(out).unwrap();
+        let lines: Vec<_> = text.lines().collect();
+        assert_eq!(lines.len(), 2);
+        let value: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(value["relative_path"], "src/main.rs");
+        assert_eq!(value["mode"], "Staged");
+    }
+
+    fn event_with_hunk() -> DisplayedEvent {
+        DisplayedEvent {
+            file_path: PathBuf::from("/repo/lib.rs"),
+            relative_path: "lib.rs".to_string(),
+            timestamp: Utc::now(),
+            diff: FileDiff {
+                hunks: vec![DiffHunk {
+                    old_start: 1,
+                    old_count: 2,
+                    new_start: 1,
+                    new_count: 2,
+                    lines: vec![
+                        line(DiffKind::Context, "fn main() {"),
+                        line(DiffKind::Deleted, "    old();"),
+                        line(DiffKind::Added, "    new();"),
+                    ],
+                }],
+                ..FileDiff::default()
+            },
+            mode: DiffMode::All,
+            snapshot: SaveSnapshot::default(),
+        }
+    }
+
+    fn line(kind: DiffKind, content: &str) -> DiffLine {
+        DiffLine {
+            old_line_number: None,
+            new_line_number: None,
+            kind,
+            content: content.to_string(),
+            emphasis: Vec::new(),
+            missing_newline: false,
+        }
+    }
+
+    #[test]
+    fn test_format_patch_plain() {
+        let patch = format_patch(&event_with_hunk(), None);
+        assert_eq!(
+            patch,
+            "diff --git a/lib.rs b/lib.rs\n\
+             --- a/lib.rs\n\
+             +++ b/lib.rs\n\
+             @@ -1,2 +1,2 @@\n \
+             fn main() {\n\
+             -    old();\n\
+             +    new();\n"
+        );
+    }
+
+    #[test]
+    fn test_format_patch_marks_missing_newline() {
+        let mut event = event_with_hunk();
+        event.diff.hunks[0].lines[2].missing_newline = true;
+        let patch = format_patch(&event, None);
+        assert!(patch.ends_with("+    new();\n\\ No newline at end of file\n"));
+    }
+
+    #[test]
+    fn test_format_patch_new_file_and_color() {
+        let mut event = event_with_hunk();
+        event.diff.is_new_file = true;
+        event.diff.hunks[0] = DiffHunk {
+            old_start: 1,
+            old_count: 0,
+            new_start: 1,
+            new_count: 1,
+            lines: vec![line(DiffKind::Added, "fn main() {}")],
+        };
+        let patch = format_patch(&event, None);
+        assert!(
+            patch.contains("new file mode 100644\n--- /dev/null\n+++ b/lib.rs\n@@ -0,0 +1,1 @@\n")
+        );
+
+        let colored = format_patch(&event, Some(&Theme::nord()));
+        assert!(colored.contains('\x1b'));
+        assert!(colored.contains("+fn main() {}"));
+    }
+
+    #[test]
+    fn test_format_patch_deleted_and_renamed_headers() {
+        let mut event = event_with_hunk();
+        event.diff.is_deleted = true;
+        event.diff.hunks[0] = DiffHunk {
+            old_start: 1,
+            old_count: 1,
+            new_start: 1,
+            new_count: 0,
+            lines: vec![line(DiffKind::Deleted, "fn main() {}")],
+        };
+        let patch = format_patch(&event, None);
+        assert!(patch.contains("deleted file mode 100644\n--- a/lib.rs\n+++ /dev/null\n"));
+        assert!(patch.contains("@@ -1,1 +0,0 @@\n"));
+
+        let mut event = event_with_hunk();
+        event.diff.renamed_from = Some("old.rs".to_string());
+        let patch = format_patch(&event, None);
+        assert!(patch.starts_with(
+            "diff --git a/old.rs b/lib.rs\nrename from old.rs\nrename to lib.rs\n--- a/old.rs\n"
+        ));
+    }
+}
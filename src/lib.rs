@@ -1,8 +1,12 @@
 pub mod cli;
 pub mod config;
 pub mod diff_viewer;
+pub mod diff_worker;
 pub mod git_engine;
+pub mod headless;
+pub mod notes;
 pub mod review_state;
+pub mod session;
 pub mod types;
 pub mod ui;
 pub mod watcher;
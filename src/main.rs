@@ -1,5 +1,5 @@
 use anyhow::Result;
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use crossterm::{
     event::{
         self, Event, KeyEventKind, KeyboardEnhancementFlags, PopKeyboardEnhancementFlags,
@@ -10,18 +10,24 @@ use crossterm::{
 };
 use notify::{RecommendedWatcher, RecursiveMode, Watcher};
 use ratatui::prelude::*;
-use std::io::{self, stdout};
+use std::io::{self, stdout, Write};
 use std::time::Duration;
 use tokio::sync::mpsc;
 use tracing_appender::rolling::{RollingFileAppender, Rotation};
 use tracing_subscriber::{fmt, prelude::*, EnvFilter};
 
-use gwatch::cli::Args;
+use gwatch::cli::{Args, ColorChoice, Command, NotesCommand, NotesFormat};
 use gwatch::config::Config;
+use gwatch::diff_worker::{DiffJob, DiffOutcome, DiffWorker};
 use gwatch::git_engine::GitEngine;
-use gwatch::types::{DiffMode, DisplayedEvent, FileChangeEvent};
+use gwatch::headless::{self, Output};
+use gwatch::notes;
+use gwatch::review_state::{ReviewScope, ReviewState};
+use gwatch::session::{self, SessionRecorder};
+use gwatch::types::{DiffRequest, DisplayedEvent, FileChangeEvent, IndexRequest};
+use gwatch::ui::theme::{Theme, ThemeRegistry};
 use gwatch::ui::{draw_ui, handle_key_event, App};
-use gwatch::watcher::FileWatcher;
+use gwatch::watcher::{ExtraIgnores, FileWatcher, RepoStateWatcher};
 
 fn setup_logging(_config: &Config, verbose: u8) -> Result<()> {
     let log_dir = Config::config_dir();
@@ -43,10 +49,12 @@ fn setup_logging(_config: &Config, verbose: u8) -> Result<()> {
     Ok(())
 }
 
-fn install_panic_hook() {
+fn install_panic_hook(tui: bool) {
     let default = std::panic::take_hook();
     std::panic::set_hook(Box::new(move |info| {
-        let _ = restore_terminal();
+        if tui {
+            let _ = restore_terminal();
+        }
         let msg = format!("gwatch panic: {info}");
         eprintln!("{msg}");
         if let Some(loc) = info.location() {
@@ -90,8 +98,13 @@ fn restore_terminal() -> Result<()> {
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    install_panic_hook();
     let args = Args::parse_args();
+    let tui = match args.command {
+        None => !args.json && !args.plain,
+        Some(Command::Replay { .. }) => true,
+        Some(Command::Diff(_) | Command::Notes { .. }) => false,
+    };
+    install_panic_hook(tui);
     let config = Config::load()?;
     setup_logging(&config, args.verbose)?;
 
@@ -105,31 +118,154 @@ async fn main() -> Result<()> {
         anyhow::bail!("Path does not exist: {}", args.path);
     }
 
+    if let Some(Command::Replay { file }) = &args.command {
+        let events = session::load(file)?;
+        tracing::info!("Replaying {} events from {:?}", events.len(), file);
+        let review_state = GitEngine::new(&current_dir)
+            .map(|engine| load_review_state(&engine, &config))
+            .unwrap_or_default();
+        let mut app = App::new(
+            config,
+            current_dir.clone(),
+            review_state,
+            ThemeRegistry::load(),
+        );
+        app.start_replay(file.clone(), events);
+
+        // Nothing live feeds a replay: files, the repository and diffs stay
+        // silent.
+        let (_, files) = mpsc::unbounded_channel::<FileChangeEvent>();
+        let (config_tx, config_changes) = mpsc::unbounded_channel::<()>();
+        let (_, repo) = mpsc::unbounded_channel::<()>();
+        let (diff_tx, diffs) = mpsc::unbounded_channel::<DiffOutcome>();
+        let mut worker = DiffWorker::new(current_dir, diff_tx);
+        let _config_watcher = setup_config_watcher(config_tx);
+        let mut inputs = Inputs {
+            files,
+            config: config_changes,
+            repo,
+            diffs,
+        };
+        return run_tui(&mut app, &mut inputs, &mut worker, None, None).await;
+    }
+
     let git_engine = GitEngine::new(&current_dir)?;
     let repo_root = git_engine.repo_root().to_path_buf();
 
     tracing::info!("Starting gwatch in repository: {:?}", repo_root);
 
-    let (tx, mut rx) = mpsc::unbounded_channel::<FileChangeEvent>();
-    let (config_tx, mut config_rx) = mpsc::unbounded_channel::<()>();
+    if let Some(Command::Diff(diff)) = &args.command {
+        let events = headless::snapshot(
+            &git_engine,
+            &diff.paths,
+            diff.mode.into(),
+            config.display.context_lines,
+        )?;
+        let output = if diff.json {
+            Output::Json
+        } else {
+            Output::Plain {
+                theme: plain_theme(&config, args.color),
+            }
+        };
+        return headless::print(&events, &output);
+    }
+    if let Some(Command::Notes {
+        command: NotesCommand::Export { format },
+    }) = &args.command
+    {
+        let notes = load_review_state(&git_engine, &config).notes;
+        let text = match format {
+            NotesFormat::Markdown => notes::to_markdown(&notes),
+            NotesFormat::Json => notes::to_review_json(&notes)?,
+        };
+        // Like `headless::print`, a reader that stops early is not an error.
+        let mut out = stdout().lock();
+        return match out.write_all(text.as_bytes()).and_then(|()| out.flush()) {
+            Err(e) if e.kind() != io::ErrorKind::BrokenPipe => Err(e.into()),
+            _ => Ok(()),
+        };
+    }
+    if args.json {
+        return headless::run(repo_root, &config, Output::Json).await;
+    }
+    if args.plain {
+        let theme = plain_theme(&config, args.color);
+        return headless::run(repo_root, &config, Output::Plain { theme }).await;
+    }
+
+    let (tx, files) = mpsc::unbounded_channel::<FileChangeEvent>();
+    let (config_tx, config_changes) = mpsc::unbounded_channel::<()>();
+    let (repo_tx, repo) = mpsc::unbounded_channel::<()>();
+    let (diff_tx, diffs) = mpsc::unbounded_channel::<DiffOutcome>();
+    let mut worker = DiffWorker::new(repo_root.clone(), diff_tx);
 
     let _watcher = FileWatcher::new(repo_root.clone(), &config.watcher, tx)?;
     let _config_watcher = setup_config_watcher(config_tx);
+    let _repo_watcher = RepoStateWatcher::new(
+        git_engine.git_dir(),
+        git_engine.common_dir(),
+        &config.watcher,
+        repo_tx,
+    )
+    .map_err(|e| tracing::warn!("Failed to watch repository state: {}", e))
+    .ok();
 
-    let review_state = gwatch::review_state::ReviewState::load();
-    let mut terminal = setup_terminal()?;
-    let mut app = App::new(config, repo_root.clone(), review_state);
+    let recorder = args
+        .record
+        .as_deref()
+        .map(SessionRecorder::create)
+        .transpose()?;
 
+    let review_state = load_review_state(&git_engine, &config);
+    let mut app = App::new(
+        config,
+        repo_root.clone(),
+        review_state,
+        ThemeRegistry::load(),
+    );
+    app.head = git_engine.head_info();
+    seed_history(&app, &mut worker, &git_engine);
+
+    let mut inputs = Inputs {
+        files,
+        config: config_changes,
+        repo,
+        diffs,
+    };
+    run_tui(
+        &mut app,
+        &mut inputs,
+        &mut worker,
+        Some(&git_engine),
+        recorder,
+    )
+    .await
+}
+
+/// Run the TUI until the user quits. Without a git engine the session is a
+/// replay and nothing touches the repository.
+async fn run_tui(
+    app: &mut App,
+    inputs: &mut Inputs,
+    worker: &mut DiffWorker,
+    git_engine: Option<&GitEngine>,
+    mut recorder: Option<SessionRecorder>,
+) -> Result<()> {
+    let mut terminal = setup_terminal()?;
     let result = run_app(
         &mut terminal,
-        &mut app,
-        &mut rx,
-        &mut config_rx,
-        &git_engine,
+        app,
+        inputs,
+        worker,
+        git_engine,
+        &mut recorder,
     )
     .await;
 
     restore_terminal()?;
+    // Finish the recording before a possible exit below skips destructors.
+    drop(recorder);
 
     if let Err(e) = result {
         eprintln!("Error: {e}");
@@ -139,6 +275,17 @@ async fn main() -> Result<()> {
     Ok(())
 }
 
+/// Theme for patch output, or `None` when it should not be colored.
+fn plain_theme(config: &Config, color: ColorChoice) -> Option<Theme> {
+    color.enabled().then(|| {
+        let (theme, errors) = Theme::from_config(&config.theme, &ThemeRegistry::load());
+        for error in &errors {
+            tracing::warn!("Theme issue: {}", error);
+        }
+        theme
+    })
+}
+
 fn setup_config_watcher(tx: mpsc::UnboundedSender<()>) -> Option<RecommendedWatcher> {
     let config_path = Config::config_path();
     let config_dir = Config::config_dir();
@@ -176,67 +323,272 @@ fn setup_config_watcher(tx: mpsc::UnboundedSender<()>) -> Option<RecommendedWatc
     }
 }
 
+/// Everything the main loop waits on besides the terminal.
+struct Inputs {
+    files: mpsc::UnboundedReceiver<FileChangeEvent>,
+    config: mpsc::UnboundedReceiver<()>,
+    repo: mpsc::UnboundedReceiver<()>,
+    diffs: mpsc::UnboundedReceiver<DiffOutcome>,
+}
+
 async fn run_app(
     terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
     app: &mut App,
-    rx: &mut mpsc::UnboundedReceiver<FileChangeEvent>,
-    config_rx: &mut mpsc::UnboundedReceiver<()>,
-    git_engine: &GitEngine,
+    inputs: &mut Inputs,
+    worker: &mut DiffWorker,
+    git_engine: Option<&GitEngine>,
+    recorder: &mut Option<SessionRecorder>,
 ) -> Result<()> {
     loop {
-        if let Err(e) = terminal.draw(|f| draw_ui(f, app)) {
-            tracing::error!("Draw error: {}", e);
-            return Err(e.into());
+        match terminal.draw(|f| draw_ui(f, app)) {
+            Ok(frame) => app.terminal_width = Some(frame.area.width),
+            Err(e) => {
+                tracing::error!("Draw error: {}", e);
+                return Err(e.into());
+            }
         }
 
         tokio::select! {
             _ = tokio::time::sleep(Duration::from_millis(16)) => {
-                let mut last_key: Option<crossterm::event::KeyEvent> = None;
+                // Every key counts: text typed faster than a frame must not
+                // lose characters.
                 while event::poll(Duration::from_millis(0))? {
-                    if let Event::Key(key) = event::read()? {
-                        if key.kind == KeyEventKind::Press {
-                            last_key = Some(key);
-                        }
+                    let Event::Key(key) = event::read()? else {
+                        continue;
+                    };
+                    if key.kind != KeyEventKind::Press {
+                        continue;
                     }
-                }
-                if let Some(key) = last_key {
                     handle_key_event(app, key)?;
                     if app.should_quit {
                         return Ok(());
                     }
+                    for request in app.take_diff_requests() {
+                        worker.submit(DiffJob::Refresh(request));
+                    }
+                    if let Some(git_engine) = git_engine {
+                        run_git_requests(app, worker, git_engine);
+                    }
                 }
             }
-            Some(file_event) = rx.recv() => {
-                if !app.is_paused() || app.events.is_empty() {
-                    let diff_result = match app.diff_mode {
-                        DiffMode::All => git_engine.compute_diff(&file_event.path),
-                        DiffMode::Staged => git_engine.compute_staged_diff(&file_event.path),
-                        DiffMode::Unstaged => git_engine.compute_unstaged_diff(&file_event.path),
-                    };
-
-                    match diff_result {
-                        Ok(diff) => {
-                            if diff.stats.added_count > 0 || diff.stats.deleted_count > 0 || diff.is_new_file || diff.is_truncated {
-                                let displayed = DisplayedEvent {
-                                    file_path: file_event.path.clone(),
-                                    relative_path: git_engine.relative_path(&file_event.path),
-                                    timestamp: Utc::now(),
-                                    diff,
-                                };
-                                app.add_event(displayed);
-                                tracing::debug!("Processed change ({:?}): {:?}", app.diff_mode, file_event.path);
-                            }
-                        }
-                        Err(e) => {
-                            tracing::warn!("Failed to compute diff for {:?}: {}", file_event.path, e);
-                        }
+            Some(file_event) = inputs.files.recv() => {
+                let previous = app.latest_snapshot(&file_event.path);
+                worker.submit(DiffJob::NewEvent(DiffRequest {
+                    file_path: file_event.path,
+                    timestamp: Utc::now(),
+                    mode: app.diff_mode,
+                    context_lines: app.config.display.context_lines,
+                    previous,
+                    content: None,
+                }));
+            }
+            Some(outcome) = inputs.diffs.recv() => {
+                if let Some(outcome) = worker.accept(outcome) {
+                    apply_outcome(app, outcome, recorder.as_mut());
+                }
+            }
+            Some(_) = inputs.repo.recv() => {
+                tracing::info!("Repository state changed, refreshing events");
+                if let Some(git_engine) = git_engine {
+                    let head = git_engine.head_info();
+                    let switched = head.branch != app.head.branch;
+                    app.head = head;
+                    if switched && app.config.review.per_branch {
+                        app.review_state = load_review_state(git_engine, &app.config);
                     }
                 }
+                for request in app.resync_requests() {
+                    worker.submit(DiffJob::Resync(request));
+                }
             }
-            Some(_) = config_rx.recv() => {
+            Some(_) = inputs.config.recv() => {
                 tracing::info!("Config file changed, reloading...");
+                let per_branch = app.config.review.per_branch;
                 app.reload_config();
+                if let Some(git_engine) = git_engine {
+                    if app.config.review.per_branch != per_branch {
+                        app.review_state = load_review_state(git_engine, &app.config);
+                    }
+                }
+            }
+        }
+
+        app.computing = worker.pending_paths();
+    }
+}
+
+/// Review marks of the repository, and of its current branch when
+/// `review.per_branch` is set.
+fn load_review_state(git_engine: &GitEngine, config: &Config) -> ReviewState {
+    let scope = ReviewScope {
+        repository: git_engine.repository_id(&ReviewState::repository_ids_path()),
+        branch: config
+            .review
+            .per_branch
+            .then(|| git_engine.head_info().branch)
+            .flatten(),
+    };
+    ReviewState::load(&scope, git_engine.repo_root())
+}
+
+/// Queue a diff for every file that is already changed, so the history is
+/// useful before the first save.
+fn seed_history(app: &App, worker: &mut DiffWorker, git_engine: &GitEngine) {
+    let files = match git_engine.changed_files(app.diff_mode) {
+        Ok(files) => files,
+        Err(e) => {
+            tracing::warn!("Initial scan failed: {}", e);
+            return;
+        }
+    };
+    let ignores = ExtraIgnores::new(&app.config.watcher.ignore_patterns);
+    let repo_root = git_engine.repo_root();
+    let files: Vec<_> = files
+        .into_iter()
+        .filter(|path| !ignores.matches(path.strip_prefix(repo_root).unwrap_or(path)))
+        .take(app.max_events)
+        .collect();
+    tracing::info!("Initial scan found {} changed files", files.len());
+
+    for path in files {
+        let timestamp = std::fs::metadata(&path)
+            .and_then(|m| m.modified())
+            .map(DateTime::<Utc>::from)
+            .unwrap_or_else(|_| Utc::now());
+        worker.submit(DiffJob::Seed(DiffRequest {
+            file_path: path,
+            timestamp,
+            mode: app.diff_mode,
+            context_lines: app.config.display.context_lines,
+            previous: None,
+            content: None,
+        }));
+    }
+}
+
+/// Carry out what the UI asked of the repository: staging, reading what the
+/// commit editor shows, and committing. Events are recomputed when the index
+/// or HEAD moved.
+fn run_git_requests(app: &mut App, worker: &mut DiffWorker, git_engine: &GitEngine) {
+    let requests = app.take_index_requests();
+    let mut changed = !requests.is_empty();
+    for request in requests {
+        apply_index_request(app, git_engine, &request);
+    }
+
+    if app.take_commit_info_request() {
+        app.set_commit_info(git_engine.commit_info());
+    }
+    if let Some(request) = app.take_commit_request() {
+        let result = git_engine.commit(&request.message, request.amend);
+        match &result {
+            Ok(short_id) => tracing::info!("Committed {}", short_id),
+            Err(e) => tracing::warn!("Commit failed: {:#}", e),
+        }
+        app.finish_commit(result.map_err(|e| format!("{e:#}")));
+        app.head = git_engine.head_info();
+        changed = true;
+    }
+
+    if changed {
+        for request in app.resync_requests() {
+            worker.submit(DiffJob::Resync(request));
+        }
+    }
+}
+
+/// Stage or unstage as the user asked, reporting the outcome in the footer.
+fn apply_index_request(app: &mut App, git_engine: &GitEngine, request: &IndexRequest) {
+    let result = git_engine.apply_lines(
+        &request.file_path,
+        &request.diff,
+        request.mode,
+        request.hunk,
+        request.lines.clone(),
+        request.action,
+    );
+    match result {
+        Ok(()) => {
+            tracing::info!("{} {}", request.action.past_tense(), request.target());
+            app.set_status(
+                format!("{} {}", request.action.past_tense(), request.target()),
+                false,
+            );
+        }
+        Err(e) => {
+            tracing::warn!(
+                "Failed to {} {}: {:#}",
+                request.action.verb(),
+                request.target(),
+                e
+            );
+            app.set_status(
+                format!(
+                    "Could not {} {}: {e}",
+                    request.action.verb(),
+                    request.target()
+                ),
+                true,
+            );
+        }
+    }
+}
+
+fn apply_outcome(app: &mut App, outcome: DiffOutcome, recorder: Option<&mut SessionRecorder>) {
+    let diff = match outcome.result {
+        Ok(diff) => diff,
+        Err(e) => {
+            tracing::warn!(
+                "Failed to compute diff for {:?}: {}",
+                outcome.job.request().file_path,
+                e
+            );
+            return;
+        }
+    };
+
+    let seeded = matches!(outcome.job, DiffJob::Seed(_));
+    match outcome.job {
+        DiffJob::NewEvent(request) | DiffJob::Seed(request) => {
+            app.note_save(
+                &request.file_path,
+                request.timestamp,
+                outcome.snapshot.content.clone(),
+            );
+            if diff.has_changes() {
+                tracing::debug!(
+                    "Processed change ({:?}): {:?}",
+                    request.mode,
+                    request.file_path
+                );
+                let event = DisplayedEvent {
+                    file_path: request.file_path,
+                    relative_path: outcome.relative_path,
+                    timestamp: request.timestamp,
+                    diff,
+                    mode: request.mode,
+                    snapshot: outcome.snapshot,
+                };
+                if let Some(recorder) = recorder {
+                    if let Err(e) = recorder.record(&event) {
+                        tracing::warn!("Failed to record event: {}", e);
+                    }
+                }
+                if seeded {
+                    app.seed_event(event);
+                } else {
+                    app.add_event(event);
+                }
+            } else {
+                app.mark_clean(request.file_path, request.timestamp);
+            }
+        }
+        DiffJob::Resync(request) if !diff.has_changes() => app.remove_event(&request),
+        DiffJob::Refresh(request) | DiffJob::Resync(request) => {
+            if let Some(since_previous) = outcome.snapshot.diff {
+                app.apply_since_previous(&request, since_previous);
             }
+            app.apply_diff_result(&request, diff);
         }
     }
 }
@@ -0,0 +1,344 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+use crate::review_state::hunk_key;
+use crate::types::{DiffHunk, DiffKind, DiffLine};
+
+/// A free-text note on a hunk, or on one line of it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Note {
+    /// File path relative to the repository root.
+    pub path: PathBuf,
+    /// [`hunk_key`] of the hunk the note was made on; the note is shown for
+    /// as long as a hunk with the same changes is.
+    pub hunk: String,
+    /// Where the hunk was when the note was made.
+    pub range: HunkRange,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub line: Option<NoteLine>,
+    pub text: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HunkRange {
+    pub old_start: usize,
+    pub old_count: usize,
+    pub new_start: usize,
+    pub new_count: usize,
+}
+
+/// The line a note is attached to, as it was when the note was made.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NoteLine {
+    pub kind: DiffKind,
+    pub old_line_number: Option<usize>,
+    pub new_line_number: Option<usize>,
+    pub content: String,
+}
+
+impl Note {
+    /// An empty note on `hunk` of `path`, or on its `line` when given.
+    pub fn new(path: &Path, hunk: &DiffHunk, line: Option<&DiffLine>) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            hunk: hunk_key(hunk),
+            range: HunkRange {
+                old_start: hunk.old_start,
+                old_count: hunk.old_count,
+                new_start: hunk.new_start,
+                new_count: hunk.new_count,
+            },
+            line: line.map(|line| NoteLine {
+                kind: line.kind.clone(),
+                old_line_number: line.old_line_number,
+                new_line_number: line.new_line_number,
+                content: line.content.clone(),
+            }),
+            text: String::new(),
+            created_at: Utc::now(),
+        }
+    }
+
+    /// Index in `hunk` of the line the note is on. Line numbers shift as the
+    /// file is edited elsewhere, so the line is found by its content,
+    /// preferring the one at the recorded position.
+    pub fn line_index(&self, hunk: &DiffHunk) -> Option<usize> {
+        let note_line = self.line.as_ref()?;
+        let mut candidates = hunk
+            .lines
+            .iter()
+            .enumerate()
+            .filter(|(_, l)| l.kind == note_line.kind && l.content == note_line.content);
+        let first = candidates.clone().next().map(|(i, _)| i);
+        candidates
+            .find(|(_, l)| {
+                l.old_line_number == note_line.old_line_number
+                    && l.new_line_number == note_line.new_line_number
+            })
+            .map(|(i, _)| i)
+            .or(first)
+    }
+
+    /// Whether `other` is the same note, in a possibly edited version.
+    pub fn is_same(&self, other: &Note) -> bool {
+        self.path == other.path
+            && self.hunk == other.hunk
+            && self.line == other.line
+            && self.created_at == other.created_at
+    }
+
+    /// Side and lines of the new or old file the note refers to.
+    fn position(&self) -> Position {
+        match &self.line {
+            Some(line) if line.kind == DiffKind::Deleted => Position {
+                side: Side::Left,
+                start: line.old_line_number.unwrap_or(self.range.old_start),
+                end: line.old_line_number.unwrap_or(self.range.old_start),
+            },
+            Some(line) => Position {
+                side: Side::Right,
+                start: line.new_line_number.unwrap_or(self.range.new_start),
+                end: line.new_line_number.unwrap_or(self.range.new_start),
+            },
+            // Only removals: the hunk has no lines on the new side.
+            None if self.range.new_count == 0 => Position {
+                side: Side::Left,
+                start: self.range.old_start,
+                end: self.range.old_start + self.range.old_count.max(1) - 1,
+            },
+            None => Position {
+                side: Side::Right,
+                start: self.range.new_start,
+                end: self.range.new_start + self.range.new_count - 1,
+            },
+        }
+    }
+
+    /// "line 12", "old line 7" or "lines 10–14", for headings and titles.
+    pub fn location(&self) -> String {
+        let position = self.position();
+        let old = if position.side == Side::Left {
+            "old "
+        } else {
+            ""
+        };
+        if position.start == position.end {
+            format!("{old}line {}", position.start)
+        } else {
+            format!("{old}lines {}–{}", position.start, position.end)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "UPPERCASE")]
+enum Side {
+    Left,
+    Right,
+}
+
+struct Position {
+    side: Side,
+    start: usize,
+    end: usize,
+}
+
+/// A note as a pull request review comment: `side` is `LEFT` for lines of
+/// the old file, and a range of lines has a `start_line`.
+#[derive(Debug, Serialize)]
+struct ReviewComment<'a> {
+    path: String,
+    body: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    start_line: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    start_side: Option<Side>,
+    line: usize,
+    side: Side,
+}
+
+/// `notes` ordered by file and position.
+fn sorted(notes: &[Note]) -> Vec<&Note> {
+    let mut notes: Vec<&Note> = notes.iter().collect();
+    notes.sort_by_key(|note| {
+        let position = note.position();
+        (note.path.clone(), position.start, note.created_at)
+    });
+    notes
+}
+
+/// `notes` as Markdown, with a section per file.
+pub fn to_markdown(notes: &[Note]) -> String {
+    let mut out = String::from("# Review notes\n");
+    if notes.is_empty() {
+        out.push_str("\nNo notes.\n");
+        return out;
+    }
+    let mut current: Option<&Path> = None;
+    for note in sorted(notes) {
+        if current != Some(note.path.as_path()) {
+            let _ = writeln!(out, "\n## {}\n", note.path.display());
+            current = Some(&note.path);
+        }
+        let location = note.location();
+        let mut location = location[..1].to_uppercase() + &location[1..];
+        if let Some(line) = &note.line {
+            let prefix = match line.kind {
+                DiffKind::Added => '+',
+                DiffKind::Deleted => '-',
+                DiffKind::Context => ' ',
+            };
+            let _ = write!(
+                location,
+                " {}",
+                code_span(&format!("{prefix}{}", line.content))
+            );
+        }
+        let _ = writeln!(out, "- **{location}**");
+        for text_line in note.text.lines() {
+            if text_line.is_empty() {
+                out.push('\n');
+            } else {
+                let _ = writeln!(out, "  {text_line}");
+            }
+        }
+    }
+    out
+}
+
+/// `notes` as `{"comments": [...]}`, the shape a pull request review takes.
+pub fn to_review_json(notes: &[Note]) -> Result<String> {
+    let comments: Vec<ReviewComment> = sorted(notes)
+        .into_iter()
+        .map(|note| {
+            let position = note.position();
+            let range = position.start != position.end;
+            ReviewComment {
+                path: note.path.to_string_lossy().replace('\\', "/"),
+                body: &note.text,
+                start_line: range.then_some(position.start),
+                start_side: range.then_some(position.side),
+                line: position.end,
+                side: position.side,
+            }
+        })
+        .collect();
+    let mut json = serde_json::to_string_pretty(&serde_json::json!({ "comments": comments }))?;
+    json.push('\n');
+    Ok(json)
+}
+
+/// `text` as Markdown inline code, fenced so backticks inside survive.
+fn code_span(text: &str) -> String {
+    if text.contains('`') {
+        format!("`` {text} ``")
+    } else {
+        format!("`{text}`")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(kind: DiffKind, old: Option<usize>, new: Option<usize>, content: &str) -> DiffLine {
+        DiffLine {
+            old_line_number: old,
+            new_line_number: new,
+            kind,
+            content: content.to_string(),
+            emphasis: Vec::new(),
+            missing_newline: false,
+        }
+    }
+
+    fn hunk() -> DiffHunk {
+        DiffHunk {
+            old_start: 10,
+            old_count: 2,
+            new_start: 10,
+            new_count: 3,
+            lines: vec![
+                line(DiffKind::Context, Some(10), Some(10), "}"),
+                line(DiffKind::Deleted, Some(11), None, "old();"),
+                line(DiffKind::Added, None, Some(11), "new();"),
+                line(DiffKind::Added, None, Some(12), "}"),
+            ],
+        }
+    }
+
+    #[test]
+    fn test_line_index_prefers_the_recorded_position() {
+        let hunk = hunk();
+        let mut note = Note::new(Path::new("lib.rs"), &hunk, Some(&hunk.lines[3]));
+        assert_eq!(note.line_index(&hunk), Some(3));
+
+        // The file moved down by a line: found by content.
+        note.line.as_mut().unwrap().new_line_number = Some(40);
+        assert_eq!(note.line_index(&hunk), Some(3));
+        assert_eq!(
+            Note::new(Path::new("lib.rs"), &hunk, None).line_index(&hunk),
+            None
+        );
+    }
+
+    #[test]
+    fn test_location() {
+        let hunk = hunk();
+        let path = Path::new("lib.rs");
+        assert_eq!(Note::new(path, &hunk, None).location(), "lines 10–12");
+        assert_eq!(
+            Note::new(path, &hunk, Some(&hunk.lines[1])).location(),
+            "old line 11"
+        );
+        assert_eq!(
+            Note::new(path, &hunk, Some(&hunk.lines[2])).location(),
+            "line 11"
+        );
+    }
+
+    #[test]
+    fn test_exports() {
+        let hunk = hunk();
+        let mut on_hunk = Note::new(Path::new("src/lib.rs"), &hunk, None);
+        on_hunk.text = "Why the rename?".to_string();
+        let mut on_line = Note::new(Path::new("src/lib.rs"), &hunk, Some(&hunk.lines[1]));
+        on_line.text = "Still called from main.rs\n\nCheck before merging.".to_string();
+
+        let markdown = to_markdown(&[on_line.clone(), on_hunk.clone()]);
+        assert_eq!(
+            markdown,
+            "# Review notes\n\
+             \n\
+             ## src/lib.rs\n\
+             \n\
+             - **Lines 10–12**\n  \
+             Why the rename?\n\
+             - **Old line 11 `-old();`**\n  \
+             Still called from main.rs\n\
+             \n  \
+             Check before merging.\n"
+        );
+
+        let json: serde_json::Value =
+            serde_json::from_str(&to_review_json(&[on_line, on_hunk]).unwrap()).unwrap();
+        assert_eq!(
+            json["comments"][0],
+            serde_json::json!({
+                "path": "src/lib.rs",
+                "body": "Why the rename?",
+                "start_line": 10,
+                "start_side": "RIGHT",
+                "line": 12,
+                "side": "RIGHT",
+            })
+        );
+        assert_eq!(json["comments"][1]["side"], "LEFT");
+        assert_eq!(json["comments"][1]["line"], 11);
+        assert!(json["comments"][1].get("start_line").is_none());
+    }
+}
@@ -1,14 +1,56 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 
 use crate::config::Config;
+use crate::notes::Note;
+use crate::types::{DiffHunk, DiffKind, FileDiff};
 
+/// Review marks, per file and per hunk. Files are keyed by their path
+/// relative to the repository root, and hunks by [`hunk_key`], a hash of
+/// their changes, so editing a reviewed hunk makes it unreviewed again.
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct ReviewState {
+    #[serde(default)]
+    pub reviewed_hunks: HashMap<PathBuf, HashMap<String, ReviewEntry>>,
+    /// Whole-file marks from before hunks could be marked. One stands for
+    /// every hunk the file had when it was marked, so it only holds while
+    /// the file is unmodified since; see [`ReviewState::adopt_file_mark`].
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
     pub reviewed_files: HashMap<PathBuf, ReviewEntry>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub notes: Vec<Note>,
+    /// Where [`ReviewState::save`] writes to; marks stay in memory without it.
+    #[serde(skip)]
+    store: Option<Store>,
+}
+
+/// Which saved marks a [`ReviewState`] holds.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReviewScope {
+    /// From [`GitEngine::repository_id`](crate::git_engine::GitEngine::repository_id).
+    pub repository: String,
+    /// Set when marks are kept per branch.
+    pub branch: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+struct Store {
+    file: PathBuf,
+    branch: Option<String>,
+}
+
+/// Everything saved for one repository.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct RepositoryReviews {
+    /// Marks shared by all branches.
+    #[serde(default)]
+    shared: ReviewState,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    branches: BTreeMap<String, ReviewState>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -16,85 +58,375 @@ pub struct ReviewEntry {
     pub reviewed_at: chrono::DateTime<chrono::Utc>,
 }
 
+/// Hash of the lines `hunk` adds and removes. Context is left out, so a mark
+/// survives the hunk moving or its context radius changing. Hunks of one file
+/// making the very same change share a key, so marking one marks them all.
+pub fn hunk_key(hunk: &DiffHunk) -> String {
+    let mut changes = String::new();
+    for line in &hunk.lines {
+        let prefix = match line.kind {
+            DiffKind::Added => '+',
+            DiffKind::Deleted => '-',
+            DiffKind::Context => continue,
+        };
+        changes.push(prefix);
+        changes.push_str(&line.content);
+        changes.push('\n');
+    }
+    hash(&changes)
+}
+
+/// Keys that all have to be marked for `diff` to count as reviewed: one per
+/// hunk, or a single key summarising a diff without hunks, such as a binary
+/// file or a pure rename.
+pub fn review_keys(diff: &FileDiff) -> Vec<String> {
+    if !diff.hunks.is_empty() {
+        return diff.hunks.iter().map(hunk_key).collect();
+    }
+    let summary = format!(
+        "new:{} deleted:{} binary:{} truncated:{} omitted:{} renamed:{:?} +{} -{}",
+        diff.is_new_file,
+        diff.is_deleted,
+        diff.is_binary,
+        diff.is_truncated,
+        diff.omitted_lines,
+        diff.renamed_from,
+        diff.stats.added_count,
+        diff.stats.deleted_count,
+    );
+    vec![hash(&summary)]
+}
+
+/// Git's blob id for `text`; unlike `std`'s hashers it is stable across
+/// builds, which matters for keys saved to disk.
+fn hash(text: &str) -> String {
+    git2::Oid::hash_object(git2::ObjectType::Blob, text.as_bytes())
+        .map(|oid| oid.to_string())
+        .unwrap_or_default()
+}
+
 impl ReviewState {
     #[cfg(test)]
     pub fn new() -> Self {
         Self::default()
     }
 
+    /// The single file earlier versions kept marks for every repository in,
+    /// keyed by absolute path. It is only read to migrate from.
     pub fn state_path() -> PathBuf {
         Config::config_dir().join("review_state.json")
     }
 
-    pub fn load() -> Self {
-        Self::load_from(&Self::state_path()).unwrap_or_default()
+    /// Directory with one file of marks per repository.
+    pub fn reviews_dir() -> PathBuf {
+        Config::config_dir().join("reviews")
     }
 
-    pub fn load_from(path: &Path) -> Result<Self> {
-        if !path.exists() {
-            return Ok(Self::default());
+    /// File that keeps the id of each repository the marks are saved under.
+    pub fn repository_ids_path() -> PathBuf {
+        Config::config_dir().join("repositories.json")
+    }
+
+    /// Marks for `scope`, taking over any the old global file holds for files
+    /// under `repo_root`.
+    pub fn load(scope: &ReviewScope, repo_root: &Path) -> Self {
+        Self::load_in(&Self::reviews_dir(), &Self::state_path(), scope, repo_root)
+    }
+
+    pub fn load_in(dir: &Path, legacy: &Path, scope: &ReviewScope, repo_root: &Path) -> Self {
+        let file = dir.join(format!("{}.json", scope.repository));
+        let mut reviews = read_json::<RepositoryReviews>(&file).unwrap_or_else(|e| {
+            tracing::warn!("Failed to read review state {:?}: {}", file, e);
+            RepositoryReviews::default()
+        });
+        let mut state = match &scope.branch {
+            Some(branch) => reviews.branches.remove(branch).unwrap_or_default(),
+            None => reviews.shared,
+        };
+        state.store = Some(Store {
+            file,
+            branch: scope.branch.clone(),
+        });
+
+        match state.migrate_from(legacy, repo_root) {
+            Ok(0) => {}
+            Ok(migrated) => {
+                tracing::info!("Migrated review marks for {} files", migrated);
+                if let Err(e) = state.save() {
+                    tracing::warn!("Failed to save review state: {}", e);
+                }
+            }
+            Err(e) => tracing::warn!("Failed to migrate review state {:?}: {}", legacy, e),
+        }
+        state
+    }
+
+    /// Move marks for files under `repo_root` out of the old global file at
+    /// `legacy`. Once nothing is left in it, it is renamed to `.bak` rather
+    /// than removed. Returns how many files had marks.
+    fn migrate_from(&mut self, legacy: &Path, repo_root: &Path) -> Result<usize> {
+        if !legacy.exists() {
+            return Ok(0);
+        }
+        let mut old = Self::load_from(legacy)?;
+        let hunk_paths: Vec<PathBuf> = old
+            .reviewed_hunks
+            .keys()
+            .filter(|path| path.starts_with(repo_root))
+            .cloned()
+            .collect();
+        for path in &hunk_paths {
+            let hunks = old.reviewed_hunks.remove(path).unwrap_or_default();
+            let relative = path.strip_prefix(repo_root).unwrap_or(path);
+            self.reviewed_hunks
+                .entry(relative.to_path_buf())
+                .or_default()
+                .extend(hunks);
+        }
+        let file_paths: Vec<PathBuf> = old
+            .reviewed_files
+            .keys()
+            .filter(|path| path.starts_with(repo_root))
+            .cloned()
+            .collect();
+        for path in &file_paths {
+            let Some(entry) = old.reviewed_files.remove(path) else {
+                continue;
+            };
+            let relative = path.strip_prefix(repo_root).unwrap_or(path);
+            self.reviewed_files.insert(relative.to_path_buf(), entry);
         }
-        let content = fs::read_to_string(path)?;
-        let state: Self = serde_json::from_str(&content)?;
-        Ok(state)
+
+        if old.reviewed_hunks.is_empty() && old.reviewed_files.is_empty() {
+            let backup = legacy.with_extension("json.bak");
+            fs::rename(legacy, &backup)?;
+            tracing::info!("Moved the old review state to {:?}", backup);
+        } else if !hunk_paths.is_empty() || !file_paths.is_empty() {
+            old.save_to(legacy)?;
+        }
+        Ok(hunk_paths.len() + file_paths.len())
+    }
+
+    /// Turn the whole-file mark of `path`, if any, into marks for `keys`
+    /// when the file was last `modified` no later than it was marked. The
+    /// file mark is dropped either way. Returns whether anything changed.
+    pub fn adopt_file_mark(
+        &mut self,
+        path: &Path,
+        modified: Option<SystemTime>,
+        keys: &[String],
+    ) -> bool {
+        let Some(entry) = self.reviewed_files.remove(path) else {
+            return false;
+        };
+        if modified.is_some_and(|m| chrono::DateTime::<chrono::Utc>::from(m) <= entry.reviewed_at) {
+            let hunks = self.reviewed_hunks.entry(path.to_path_buf()).or_default();
+            for key in keys {
+                hunks.entry(key.clone()).or_insert_with(|| entry.clone());
+            }
+        }
+        true
+    }
+
+    pub fn load_from(path: &Path) -> Result<Self> {
+        read_json(path)
     }
 
+    /// Write the marks back to the repository's file, leaving the marks of
+    /// other branches in it alone.
     pub fn save(&self) -> Result<()> {
-        self.save_to(&Self::state_path())
+        let Some(store) = &self.store else {
+            return Ok(());
+        };
+        // A file that cannot be read is left alone rather than replaced with
+        // only this branch's marks.
+        let mut reviews = read_json::<RepositoryReviews>(&store.file)
+            .with_context(|| format!("Failed to read review state {:?}", store.file))?;
+        match &store.branch {
+            Some(branch)
+                if self.reviewed_hunks.is_empty()
+                    && self.reviewed_files.is_empty()
+                    && self.notes.is_empty() =>
+            {
+                reviews.branches.remove(branch);
+            }
+            Some(branch) => {
+                reviews
+                    .branches
+                    .insert(branch.clone(), self.without_store());
+            }
+            None => reviews.shared = self.without_store(),
+        }
+        write_json(&store.file, &reviews)
     }
 
     pub fn save_to(&self, path: &Path) -> Result<()> {
-        if let Some(parent) = path.parent() {
-            fs::create_dir_all(parent)?;
+        write_json(path, self)
+    }
+
+    fn without_store(&self) -> Self {
+        Self {
+            reviewed_hunks: self.reviewed_hunks.clone(),
+            reviewed_files: self.reviewed_files.clone(),
+            notes: self.notes.clone(),
+            store: None,
         }
-        let content = serde_json::to_string_pretty(self)?;
-        fs::write(path, content)?;
-        Ok(())
     }
 
-    pub fn is_reviewed(&self, path: &Path) -> bool {
-        self.reviewed_files.contains_key(path)
+    pub fn is_hunk_reviewed(&self, path: &Path, key: &str) -> bool {
+        self.reviewed_hunks
+            .get(path)
+            .is_some_and(|hunks| hunks.contains_key(key))
     }
 
-    pub fn mark_reviewed(&mut self, path: &Path) {
-        self.reviewed_files.insert(
-            path.to_path_buf(),
-            ReviewEntry {
-                reviewed_at: chrono::Utc::now(),
-            },
-        );
+    /// How many of `keys` are marked reviewed for `path`.
+    pub fn reviewed_of(&self, path: &Path, keys: &[String]) -> usize {
+        keys.iter()
+            .filter(|key| self.is_hunk_reviewed(path, key))
+            .count()
     }
 
-    pub fn unmark_reviewed(&mut self, path: &Path) {
-        self.reviewed_files.remove(path);
+    /// Whether every one of `keys`, as from [`review_keys`], is reviewed.
+    pub fn is_reviewed(&self, path: &Path, keys: &[String]) -> bool {
+        !keys.is_empty() && self.reviewed_of(path, keys) == keys.len()
     }
 
-    pub fn toggle_reviewed(&mut self, path: &Path) {
-        if self.is_reviewed(path) {
-            self.unmark_reviewed(path);
+    pub fn mark_reviewed(&mut self, path: &Path, key: &str) {
+        self.reviewed_hunks
+            .entry(path.to_path_buf())
+            .or_default()
+            .insert(
+                key.to_string(),
+                ReviewEntry {
+                    reviewed_at: chrono::Utc::now(),
+                },
+            );
+    }
+
+    pub fn unmark_reviewed(&mut self, path: &Path, key: &str) {
+        if let Some(hunks) = self.reviewed_hunks.get_mut(path) {
+            hunks.remove(key);
+            if hunks.is_empty() {
+                self.reviewed_hunks.remove(path);
+            }
+        }
+    }
+
+    pub fn toggle_hunk_reviewed(&mut self, path: &Path, key: &str) {
+        if self.is_hunk_reviewed(path, key) {
+            self.unmark_reviewed(path, key);
         } else {
-            self.mark_reviewed(path);
+            self.mark_reviewed(path, key);
+        }
+    }
+
+    /// Unmark all of `keys` when they are all reviewed, and otherwise mark
+    /// all of them. Marks on other keys of `path` are left alone.
+    pub fn toggle_reviewed(&mut self, path: &Path, keys: &[String]) {
+        if self.is_reviewed(path, keys) {
+            for key in keys {
+                self.unmark_reviewed(path, key);
+            }
+            return;
         }
+        for key in keys {
+            if !self.is_hunk_reviewed(path, key) {
+                self.mark_reviewed(path, key);
+            }
+        }
+    }
+
+    /// Notes on the hunk of `path` with key `hunk`, its line notes included.
+    pub fn notes_on<'a>(&'a self, path: &'a Path, hunk: &'a str) -> impl Iterator<Item = &'a Note> {
+        self.notes
+            .iter()
+            .filter(move |note| note.path == path && note.hunk == hunk)
+    }
+
+    /// Replace `original` with `note`; either may be missing to only add or
+    /// only remove a note.
+    pub fn set_note(&mut self, original: Option<&Note>, note: Option<Note>) {
+        if let Some(original) = original {
+            self.notes.retain(|n| !n.is_same(original));
+        }
+        self.notes.extend(note);
     }
 
+    /// Drop the marks of `path` whose key is not in `keys`, the hunks the
+    /// file has now, so marks on changes since edited away do not pile up.
+    /// Returns whether any mark was dropped.
+    pub fn retain_marks(&mut self, path: &Path, keys: &[String]) -> bool {
+        let Some(hunks) = self.reviewed_hunks.get_mut(path) else {
+            return false;
+        };
+        let before = hunks.len();
+        hunks.retain(|key, _| keys.contains(key));
+        let dropped = hunks.len() != before;
+        if hunks.is_empty() {
+            self.reviewed_hunks.remove(path);
+        }
+        dropped
+    }
+
+    /// Drop every review mark. Notes are kept.
     pub fn clear_all(&mut self) {
+        self.reviewed_hunks.clear();
         self.reviewed_files.clear();
     }
 
+    /// Number of hunk marks across all files.
     pub fn reviewed_count(&self) -> usize {
-        self.reviewed_files.len()
+        self.reviewed_hunks.values().map(HashMap::len).sum()
+    }
+}
+
+fn read_json<T: serde::de::DeserializeOwned + Default>(path: &Path) -> Result<T> {
+    if !path.exists() {
+        return Ok(T::default());
+    }
+    let content = fs::read_to_string(path)?;
+    Ok(serde_json::from_str(&content)?)
+}
+
+fn write_json<T: Serialize>(path: &Path, value: &T) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
     }
+    let content = serde_json::to_string_pretty(value)?;
+    fs::write(path, content)?;
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::types::DiffLine;
     use tempfile::TempDir;
 
+    fn hunk(lines: &[(DiffKind, &str)]) -> DiffHunk {
+        DiffHunk {
+            old_start: 1,
+            old_count: 1,
+            new_start: 1,
+            new_count: 1,
+            lines: lines
+                .iter()
+                .map(|(kind, content)| DiffLine {
+                    old_line_number: None,
+                    new_line_number: None,
+                    kind: kind.clone(),
+                    content: content.to_string(),
+                    emphasis: Vec::new(),
+                    missing_newline: false,
+                })
+                .collect(),
+        }
+    }
+
     #[test]
     fn test_review_state_new() {
         let state = ReviewState::new();
-        assert!(state.reviewed_files.is_empty());
+        assert!(state.reviewed_hunks.is_empty());
     }
 
     #[test]
@@ -102,9 +434,10 @@ mod tests {
         let mut state = ReviewState::new();
         let path = PathBuf::from("/test/file.rs");
 
-        assert!(!state.is_reviewed(&path));
-        state.mark_reviewed(&path);
-        assert!(state.is_reviewed(&path));
+        assert!(!state.is_hunk_reviewed(&path, "a"));
+        state.mark_reviewed(&path, "a");
+        assert!(state.is_hunk_reviewed(&path, "a"));
+        assert!(!state.is_hunk_reviewed(&path, "b"));
     }
 
     #[test]
@@ -112,18 +445,19 @@ mod tests {
         let mut state = ReviewState::new();
         let path = PathBuf::from("/test/file.rs");
 
-        state.mark_reviewed(&path);
-        assert!(state.is_reviewed(&path));
+        state.mark_reviewed(&path, "a");
+        assert!(state.is_hunk_reviewed(&path, "a"));
 
-        state.unmark_reviewed(&path);
-        assert!(!state.is_reviewed(&path));
+        state.unmark_reviewed(&path, "a");
+        assert!(!state.is_hunk_reviewed(&path, "a"));
+        assert!(state.reviewed_hunks.is_empty());
     }
 
     #[test]
     fn test_clear_all() {
         let mut state = ReviewState::new();
-        state.mark_reviewed(&PathBuf::from("/test/file1.rs"));
-        state.mark_reviewed(&PathBuf::from("/test/file2.rs"));
+        state.mark_reviewed(&PathBuf::from("/test/file1.rs"), "a");
+        state.mark_reviewed(&PathBuf::from("/test/file2.rs"), "a");
 
         assert_eq!(state.reviewed_count(), 2);
         state.clear_all();
@@ -136,10 +470,113 @@ mod tests {
         let state_path = temp_dir.path().join("review_state.json");
 
         let mut state = ReviewState::new();
-        state.mark_reviewed(&PathBuf::from("/test/file.rs"));
+        state.mark_reviewed(&PathBuf::from("/test/file.rs"), "a");
         state.save_to(&state_path).unwrap();
 
         let loaded = ReviewState::load_from(&state_path).unwrap();
-        assert!(loaded.is_reviewed(&PathBuf::from("/test/file.rs")));
+        assert!(loaded.is_hunk_reviewed(&PathBuf::from("/test/file.rs"), "a"));
+    }
+
+    #[test]
+    fn test_hunk_key_follows_changes_only() {
+        let key = hunk_key(&hunk(&[
+            (DiffKind::Context, "fn main() {"),
+            (DiffKind::Deleted, "    old();"),
+            (DiffKind::Added, "    new();"),
+        ]));
+        let more_context = hunk_key(&hunk(&[
+            (DiffKind::Context, "// entry point"),
+            (DiffKind::Context, "fn main() {"),
+            (DiffKind::Deleted, "    old();"),
+            (DiffKind::Added, "    new();"),
+            (DiffKind::Context, "}"),
+        ]));
+        let edited = hunk_key(&hunk(&[
+            (DiffKind::Context, "fn main() {"),
+            (DiffKind::Deleted, "    old();"),
+            (DiffKind::Added, "    newer();"),
+        ]));
+        assert_eq!(key, more_context);
+        assert_ne!(key, edited);
+        assert_eq!(key.len(), 40);
+    }
+
+    #[test]
+    fn test_toggle_file_marks_every_hunk() {
+        let mut state = ReviewState::new();
+        let path = PathBuf::from("/test/file.rs");
+        let keys = vec!["a".to_string(), "b".to_string()];
+
+        state.mark_reviewed(&path, "stale");
+        state.mark_reviewed(&path, "a");
+        assert_eq!(state.reviewed_of(&path, &keys), 1);
+        assert!(!state.is_reviewed(&path, &keys));
+
+        let marked_at = state.reviewed_hunks[&path]["a"].reviewed_at;
+        state.toggle_reviewed(&path, &keys);
+        assert!(state.is_reviewed(&path, &keys));
+        assert_eq!(state.reviewed_hunks[&path]["a"].reviewed_at, marked_at);
+
+        // An edit to one hunk replaces its key, and the file is no longer
+        // fully reviewed.
+        let edited = vec!["a".to_string(), "c".to_string()];
+        assert_eq!(state.reviewed_of(&path, &edited), 1);
+        assert!(!state.is_reviewed(&path, &edited));
+
+        // Unmarking leaves marks outside `keys` alone.
+        state.toggle_reviewed(&path, &keys);
+        assert!(!state.is_hunk_reviewed(&path, "a"));
+        assert!(state.is_hunk_reviewed(&path, "stale"));
+        assert!(!state.is_reviewed(&path, &[]));
+    }
+
+    #[test]
+    fn test_retain_marks_drops_stale_keys() {
+        let mut state = ReviewState::new();
+        let path = PathBuf::from("file.rs");
+        let other = PathBuf::from("other.rs");
+        state.mark_reviewed(&path, "a");
+        state.mark_reviewed(&path, "b");
+        state.mark_reviewed(&other, "a");
+
+        assert!(!state.retain_marks(&path, &["a".to_string(), "b".to_string()]));
+        assert!(state.retain_marks(&path, &["b".to_string(), "c".to_string()]));
+        assert!(!state.is_hunk_reviewed(&path, "a"));
+        assert!(state.is_hunk_reviewed(&path, "b"));
+        assert!(state.is_hunk_reviewed(&other, "a"));
+
+        assert!(state.retain_marks(&path, &[]));
+        assert!(!state.reviewed_hunks.contains_key(&path));
+    }
+
+    #[test]
+    fn test_identical_hunks_share_a_mark() {
+        let first = hunk(&[(DiffKind::Added, "use std::fmt;")]);
+        let mut second = first.clone();
+        second.old_start = 40;
+        second.new_start = 41;
+        let diff = FileDiff {
+            hunks: vec![first.clone(), second.clone()],
+            ..Default::default()
+        };
+        let keys = review_keys(&diff);
+        assert_eq!(keys[0], keys[1]);
+
+        let mut state = ReviewState::new();
+        let path = PathBuf::from("file.rs");
+        state.mark_reviewed(&path, &hunk_key(&first));
+        assert!(state.is_hunk_reviewed(&path, &hunk_key(&second)));
+        assert!(state.is_reviewed(&path, &keys));
+    }
+
+    #[test]
+    fn test_review_keys_without_hunks() {
+        let binary = FileDiff {
+            is_binary: true,
+            ..FileDiff::default()
+        };
+        let keys = review_keys(&binary);
+        assert_eq!(keys.len(), 1);
+        assert_ne!(keys, review_keys(&FileDiff::default()));
     }
 }
@@ -0,0 +1,82 @@
+use anyhow::{Context, Result};
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use crate::headless::write_event;
+use crate::types::DisplayedEvent;
+
+/// First bytes of every gzip stream.
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Writes every event of a watch session to a file, one JSON object per
+/// line, for `gwatch replay`.
+pub struct SessionRecorder {
+    out: Box<dyn Write + Send>,
+}
+
+impl SessionRecorder {
+    /// Start a recording at `path`, replacing any file there. A `.gz`
+    /// extension compresses it.
+    pub fn create(path: &Path) -> Result<Self> {
+        let file = File::create(path)
+            .with_context(|| format!("Cannot create recording {}", path.display()))?;
+        let out: Box<dyn Write + Send> = if path.extension().is_some_and(|ext| ext == "gz") {
+            Box::new(GzEncoder::new(BufWriter::new(file), Compression::default()))
+        } else {
+            Box::new(BufWriter::new(file))
+        };
+        Ok(Self { out })
+    }
+
+    /// Append `event`, flushed so the recording survives a crash.
+    pub fn record(&mut self, event: &DisplayedEvent) -> Result<()> {
+        write_event(&mut self.out, event)
+    }
+}
+
+/// Events of a recording, in the order they happened. Compressed recordings
+/// are recognized by their content. A recording cut short by a crash loads
+/// up to its last complete event.
+pub fn load(path: &Path) -> Result<Vec<DisplayedEvent>> {
+    let mut file =
+        File::open(path).with_context(|| format!("Cannot open recording {}", path.display()))?;
+    let mut magic = [0u8; 2];
+    let is_gzip = file.read_exact(&mut magic).is_ok() && magic == GZIP_MAGIC;
+    let file = File::open(path)?;
+    let reader: Box<dyn BufRead> = if is_gzip {
+        Box::new(BufReader::new(MultiGzDecoder::new(file)))
+    } else {
+        Box::new(BufReader::new(file))
+    };
+
+    let mut events = Vec::new();
+    for (number, line) in reader.lines().enumerate() {
+        let line = match line {
+            Ok(line) => line,
+            Err(e) if !events.is_empty() => {
+                tracing::warn!("Recording {:?} ends early: {}", path, e);
+                break;
+            }
+            Err(e) => return Err(e.into()),
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let event = serde_json::from_str(&line)
+            .with_context(|| format!("{}:{}: not a recorded event", path.display(), number + 1));
+        match event {
+            Ok(event) => events.push(event),
+            // The last line of an interrupted recording may be incomplete.
+            Err(e) if !events.is_empty() => {
+                tracing::warn!("{:#}", e);
+                break;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(events)
+}
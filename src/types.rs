@@ -1,6 +1,8 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::ops::RangeInclusive;
 use std::path::PathBuf;
+use std::sync::Arc;
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct FileDiff {
@@ -12,6 +14,26 @@ pub struct FileDiff {
     pub is_truncated: bool,
     pub omitted_lines: usize,
     pub truncation_reason: Option<String>,
+    /// Unchanged lines kept around each change when the diff was computed.
+    #[serde(default)]
+    pub context_lines: usize,
+    /// Previous path, relative to the repository root, when git detects the
+    /// file as a rename.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub renamed_from: Option<String>,
+}
+
+impl FileDiff {
+    /// Whether there is anything to show; an empty diff means the file
+    /// matches its base.
+    pub fn has_changes(&self) -> bool {
+        self.stats.added_count > 0
+            || self.stats.deleted_count > 0
+            || self.is_new_file
+            || self.is_deleted
+            || self.is_truncated
+            || self.renamed_from.is_some()
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -35,6 +57,12 @@ pub struct DiffLine {
     pub new_line_number: Option<usize>,
     pub kind: DiffKind,
     pub content: String,
+    /// Char ranges `[start, end)` of `content` that changed within the line.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub emphasis: Vec<(usize, usize)>,
+    /// The line ends its file without a trailing newline.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub missing_newline: bool,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -48,6 +76,25 @@ pub enum DiffKind {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_head_label() {
+        let head = HeadInfo {
+            branch: Some("main".to_string()),
+            commit: Some("1a2b3c4".to_string()),
+        };
+        assert_eq!(head.label(), "main @ 1a2b3c4");
+        let detached = HeadInfo {
+            branch: None,
+            ..head
+        };
+        assert_eq!(detached.label(), "detached @ 1a2b3c4");
+        let unborn = HeadInfo {
+            branch: Some("main".to_string()),
+            commit: None,
+        };
+        assert_eq!(unborn.label(), "main (no commits)");
+    }
+
     #[test]
     fn test_diff_mode_default() {
         let mode = DiffMode::default();
@@ -68,6 +115,14 @@ mod tests {
         assert_eq!(DiffMode::Unstaged.label(), "Unstaged");
         assert_eq!(DiffMode::Staged.label(), "Staged");
     }
+
+    #[test]
+    fn test_diff_layout_falls_back_when_narrow() {
+        assert_eq!(DiffLayout::Split.effective(120), DiffLayout::Split);
+        assert_eq!(DiffLayout::Split.effective(60), DiffLayout::Unified);
+        assert_eq!(DiffLayout::Unified.effective(200), DiffLayout::Unified);
+        assert_eq!(DiffLayout::Split.toggle(), DiffLayout::Unified);
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
@@ -96,12 +151,285 @@ impl DiffMode {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffLayout {
+    #[default]
+    Split, // Old on the left, new on the right
+    Unified, // One column, deletions above additions
+}
+
+impl DiffLayout {
+    /// Narrowest terminal that still fits two readable columns.
+    pub const MIN_SPLIT_WIDTH: u16 = 100;
+
+    pub fn toggle(self) -> Self {
+        match self {
+            Self::Split => Self::Unified,
+            Self::Unified => Self::Split,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Split => "Split",
+            Self::Unified => "Unified",
+        }
+    }
+
+    /// Layout actually drawn in a terminal `width` columns wide: split falls
+    /// back to unified when the columns would be too narrow.
+    pub fn effective(self, width: u16) -> Self {
+        if self == Self::Split && width < Self::MIN_SPLIT_WIDTH {
+            Self::Unified
+        } else {
+            self
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DisplayedEvent {
     pub file_path: PathBuf,
     pub relative_path: String,
     pub timestamp: DateTime<Utc>,
     pub diff: FileDiff,
+    /// Base the diff was computed against.
+    #[serde(default)]
+    pub mode: DiffMode,
+    #[serde(skip)]
+    pub snapshot: SaveSnapshot,
+}
+
+/// What an event keeps to compare its save with the one before it.
+#[derive(Debug, Clone, Default)]
+pub struct SaveSnapshot {
+    /// File content at this save, if it is text of a reasonable size.
+    pub content: Option<Arc<str>>,
+    /// Content at the previous save of the same file in this session.
+    pub previous: Option<Arc<str>>,
+    /// Diff from `previous` to `content`.
+    pub diff: Option<FileDiff>,
+}
+
+/// What the diff pane compares the selected save with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Comparison {
+    #[default]
+    Base, // The diff mode's base: HEAD or the index
+    PreviousSave, // The previous save of the same file
+}
+
+impl Comparison {
+    pub fn toggle(self) -> Self {
+        match self {
+            Self::Base => Self::PreviousSave,
+            Self::PreviousSave => Self::Base,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Base => "vs base",
+            Self::PreviousSave => "vs previous save",
+        }
+    }
+}
+
+/// How the history is listed: every save, or the latest diff per file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HistoryView {
+    #[default]
+    Events,
+    Files,
+}
+
+impl HistoryView {
+    pub fn toggle(self) -> Self {
+        match self {
+            Self::Events => Self::Files,
+            Self::Files => Self::Events,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Events => "Events",
+            Self::Files => "Files",
+        }
+    }
+}
+
+/// Order of the file sidebar.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FileSort {
+    #[default]
+    Recency, // Most recently saved first
+    Path, // Alphabetical by relative path
+    Size, // Most changed lines first
+}
+
+impl FileSort {
+    pub fn next(self) -> Self {
+        match self {
+            Self::Recency => Self::Path,
+            Self::Path => Self::Size,
+            Self::Size => Self::Recency,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Recency => "Recent",
+            Self::Path => "Path",
+            Self::Size => "Size",
+        }
+    }
+}
+
+/// A change to the index made from the TUI.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndexAction {
+    Stage,
+    Unstage,
+}
+
+impl IndexAction {
+    pub fn verb(self) -> &'static str {
+        match self {
+            Self::Stage => "stage",
+            Self::Unstage => "unstage",
+        }
+    }
+
+    pub fn past_tense(self) -> &'static str {
+        match self {
+            Self::Stage => "Staged",
+            Self::Unstage => "Unstaged",
+        }
+    }
+
+    /// Whether the action makes sense on a diff computed in `mode`: staging
+    /// acts on working tree changes, unstaging on the index.
+    pub fn applies_in(self, mode: DiffMode) -> bool {
+        match self {
+            Self::Stage => mode != DiffMode::Staged,
+            Self::Unstage => mode != DiffMode::Unstaged,
+        }
+    }
+}
+
+/// Request to recompute the diff of an event already in the history.
+///
+/// The UI cannot reach the git engine, so requests are queued here and
+/// drained by the main loop, which hands results back via
+/// [`App::apply_diff_result`](crate::ui::app::App::apply_diff_result).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffRequest {
+    pub file_path: PathBuf,
+    pub timestamp: DateTime<Utc>,
+    pub mode: DiffMode,
+    pub context_lines: usize,
+    /// Content of an earlier save to also diff against.
+    pub previous: Option<Arc<str>>,
+    /// Content of the save being recomputed; `None` reads the file, as for a
+    /// new event.
+    pub content: Option<Arc<str>>,
+}
+
+impl DiffRequest {
+    /// Request to recompute `event`, including its diff since the previous
+    /// save.
+    pub fn for_event(event: &DisplayedEvent, mode: DiffMode, context_lines: usize) -> Self {
+        Self {
+            file_path: event.file_path.clone(),
+            timestamp: event.timestamp,
+            mode,
+            context_lines,
+            previous: event.snapshot.previous.clone(),
+            content: event.snapshot.content.clone(),
+        }
+    }
+
+    pub(crate) fn targets(&self, event: &DisplayedEvent) -> bool {
+        event.file_path == self.file_path && event.timestamp == self.timestamp
+    }
+}
+
+/// Request to stage or unstage lines of an event, drained by the main loop
+/// like [`DiffRequest`].
+#[derive(Debug, Clone)]
+pub struct IndexRequest {
+    pub action: IndexAction,
+    pub file_path: PathBuf,
+    pub relative_path: String,
+    /// Mode `diff` was computed in.
+    pub mode: DiffMode,
+    pub diff: FileDiff,
+    /// Hunk to act on.
+    pub hunk: usize,
+    /// Lines of the hunk to act on.
+    pub lines: RangeInclusive<usize>,
+}
+
+impl IndexRequest {
+    /// What the request acts on, e.g. "4 lines of hunk 2/3 of src/lib.rs".
+    pub fn target(&self) -> String {
+        let count = changed_lines(&self.diff.hunks[self.hunk], &self.lines);
+        let noun = if count == 1 { "line" } else { "lines" };
+        format!(
+            "{count} {noun} of hunk {}/{} of {}",
+            self.hunk + 1,
+            self.diff.hunks.len(),
+            self.relative_path
+        )
+    }
+}
+
+/// Number of added or deleted lines of `hunk` among `lines`.
+pub(crate) fn changed_lines(hunk: &DiffHunk, lines: &RangeInclusive<usize>) -> usize {
+    hunk.lines
+        .iter()
+        .enumerate()
+        .filter(|(i, line)| lines.contains(i) && line.kind != DiffKind::Context)
+        .count()
+}
+
+/// The checked-out branch and commit, shown in the header.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct HeadInfo {
+    /// Branch name, or `None` when HEAD is detached.
+    pub branch: Option<String>,
+    /// Abbreviated id of the HEAD commit, or `None` before the first commit.
+    pub commit: Option<String>,
+}
+
+impl HeadInfo {
+    pub fn label(&self) -> String {
+        let branch = self.branch.as_deref().unwrap_or("detached");
+        match &self.commit {
+            Some(commit) => format!("{branch} @ {commit}"),
+            None => format!("{branch} (no commits)"),
+        }
+    }
+}
+
+/// A file whose staged content differs from HEAD.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StagedFile {
+    /// `A`, `M`, `D`, `R` or `T`, as in `git status --short`.
+    pub status: char,
+    pub path: String,
+}
+
+/// What the commit editor shows about the commit it is about to write.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommitInfo {
+    pub staged: Vec<StagedFile>,
+    /// `Name <email>` from the git config, or why it could not be read.
+    pub signature: Result<String, String>,
+    /// Message of the HEAD commit, the starting point when amending.
+    pub head_message: Option<String>,
 }
 
 #[derive(Debug, Clone)]
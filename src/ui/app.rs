@@ -1,13 +1,30 @@
-use std::collections::{HashSet, VecDeque};
-use std::path::PathBuf;
+use std::cell::RefCell;
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 
 use crate::config::Config;
-use crate::review_state::ReviewState;
-use crate::types::{DiffMode, DisplayedEvent};
+use crate::notes::Note;
+use crate::review_state::{hunk_key, review_keys, ReviewState};
+use crate::types::{
+    changed_lines, CommitInfo, Comparison, DiffHunk, DiffKind, DiffLayout, DiffLine, DiffMode,
+    DiffRequest, DisplayedEvent, FileDiff, FileSort, HeadInfo, HistoryView, IndexAction,
+    IndexRequest,
+};
 
 use super::diff_view::build_side_by_side_lines;
-use super::theme::Theme;
+use super::keymap::{Action, Keymap};
+use super::syntax::SyntaxHighlighter;
+use super::theme::{Theme, ThemeRegistry};
+
+/// Lines of context added or removed per grow/shrink key press.
+pub const CONTEXT_STEP: usize = 3;
+
+/// How long a status message stays in the footer.
+const STATUS_DURATION: Duration = Duration::from_secs(4);
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum AppState {
@@ -16,6 +33,26 @@ pub enum AppState {
     ThemeSelector,
     HelpPanel,
     SettingsEditor,
+    CommitEditor,
+    NoteEditor,
+}
+
+/// Lines of the focused hunk picked in visual mode, as indices into its
+/// lines. The selection runs from where it started to the cursor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineSelection {
+    pub anchor: usize,
+    pub cursor: usize,
+}
+
+impl LineSelection {
+    pub fn range(&self) -> RangeInclusive<usize> {
+        self.anchor.min(self.cursor)..=self.anchor.max(self.cursor)
+    }
+
+    pub fn contains(&self, index: usize) -> bool {
+        self.range().contains(&index)
+    }
 }
 
 #[derive(Debug, Clone, Default)]
@@ -23,6 +60,8 @@ pub struct HunkViewState {
     pub focused_hunk: usize,
     pub collapsed_hunks: HashSet<usize>,
     pub collapse_context: bool,
+    /// Set while selecting lines of the focused hunk.
+    pub selection: Option<LineSelection>,
 }
 
 impl HunkViewState {
@@ -42,6 +81,7 @@ impl HunkViewState {
         if total_hunks == 0 {
             return;
         }
+        self.selection = None;
         self.focused_hunk = (self.focused_hunk + 1) % total_hunks;
     }
 
@@ -49,6 +89,7 @@ impl HunkViewState {
         if total_hunks == 0 {
             return;
         }
+        self.selection = None;
         if self.focused_hunk == 0 {
             self.focused_hunk = total_hunks - 1;
         } else {
@@ -63,20 +104,52 @@ impl HunkViewState {
     pub fn reset(&mut self) {
         self.focused_hunk = 0;
         self.collapsed_hunks.clear();
+        self.selection = None;
     }
 }
 
+type EventTime = chrono::DateTime<chrono::Utc>;
+
+/// A short-lived message in the footer, such as the outcome of staging.
+#[derive(Debug, Clone)]
+pub struct StatusMessage {
+    pub text: String,
+    pub is_error: bool,
+    until: Instant,
+}
+
 pub struct App {
+    /// Newest first. Call [`App::history_changed`] after changing it directly.
     pub events: VecDeque<DisplayedEvent>,
+    /// Indices into `events` of the files view, grouped on first use and
+    /// dropped whenever the history, a diff or the sort order changes.
+    file_index: RefCell<Option<Vec<usize>>>,
+    /// When each file was last saved back to its base; the files view skips
+    /// a file until an event newer than that.
+    clean_since: HashMap<PathBuf, EventTime>,
+    /// Time and content of the latest save of each file, clean saves
+    /// included, for the next save to be diffed against.
+    saves: HashMap<PathBuf, (EventTime, Option<Arc<str>>)>,
+    /// Events captured while paused, oldest first; applied on resume.
+    pub pending_events: VecDeque<DisplayedEvent>,
     pub state: AppState,
+    /// Index of the selected entry in the list `history_view` shows.
     pub scroll_offset: usize,
     pub diff_scroll_offset: usize,
     pub diff_horizontal_offset: usize,
     pub config: Config,
     pub theme: Theme,
+    pub theme_registry: ThemeRegistry,
+    pub keymap: Keymap,
     pub hunk_state: HunkViewState,
     pub review_state: ReviewState,
     pub diff_mode: DiffMode,
+    pub diff_layout: DiffLayout,
+    pub comparison: Comparison,
+    pub history_view: HistoryView,
+    pub file_sort: FileSort,
+    /// Terminal width at the last draw, used to pick the effective layout.
+    pub terminal_width: Option<u16>,
     pub max_events: usize,
     pub selected_theme_index: usize,
     pub should_quit: bool,
@@ -84,6 +157,25 @@ pub struct App {
     pub repo_root: PathBuf,
     pub settings_editor: SettingsEditorState,
     pub flash_until: Option<Instant>,
+    pub diff_requests: Vec<DiffRequest>,
+    /// Context asked for with grow/shrink whose diff has not arrived yet, so
+    /// requests made meanwhile keep it.
+    requested_context: HashMap<(PathBuf, EventTime), usize>,
+    /// Files whose diff is still being computed in the background.
+    pub computing: HashSet<PathBuf>,
+    pub syntax: SyntaxHighlighter,
+    /// Branch and commit checked out in the repository.
+    pub head: HeadInfo,
+    pub index_requests: Vec<IndexRequest>,
+    pub status: Option<StatusMessage>,
+    /// Recording being replayed, if this is not a live session.
+    pub replay: Option<PathBuf>,
+    pub commit_editor: CommitEditorState,
+    /// Set when the commit editor needs [`CommitInfo`] from the main loop.
+    pub commit_info_requested: bool,
+    /// Commit confirmed in the commit editor, drained by the main loop.
+    pub commit_request: Option<CommitRequest>,
+    pub note_editor: NoteEditorState,
 }
 
 #[derive(Debug, Clone, Default)]
@@ -94,31 +186,107 @@ pub struct SettingsEditorState {
     pub error_message: Option<String>,
 }
 
+/// The commit overlay. The message survives closing the overlay, so a
+/// cancelled commit can be picked up again.
+#[derive(Debug, Clone, Default)]
+pub struct CommitEditorState {
+    pub message: String,
+    pub cursor_line: usize,
+    pub cursor_col: usize,
+    pub amend: bool,
+    /// Staged files and signature, once the main loop has read them.
+    pub info: Option<CommitInfo>,
+    pub error_message: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommitRequest {
+    pub message: String,
+    pub amend: bool,
+}
+
+/// The note overlay, writing `note` in place of `original` when editing an
+/// existing one.
+#[derive(Debug, Clone, Default)]
+pub struct NoteEditorState {
+    pub text: String,
+    pub cursor_line: usize,
+    pub cursor_col: usize,
+    pub note: Option<Note>,
+    pub original: Option<Note>,
+}
+
 impl App {
-    pub fn new(config: Config, repo_root: PathBuf, review_state: ReviewState) -> Self {
-        let theme = Theme::by_name(&config.theme.name);
+    /// `theme_registry` holds the user's theme files; it is read again from
+    /// the same directory when the theme selector opens or the config reloads.
+    pub fn new(
+        config: Config,
+        repo_root: PathBuf,
+        review_state: ReviewState,
+        theme_registry: ThemeRegistry,
+    ) -> Self {
+        let theme = build_theme(&config, &theme_registry);
+        let keymap = build_keymap(&config);
         let max_events = config.watcher.max_events_buffer;
+        let diff_layout = config.display.diff_layout;
 
         Self {
             events: VecDeque::with_capacity(max_events),
+            file_index: RefCell::new(None),
+            clean_since: HashMap::new(),
+            saves: HashMap::new(),
+            pending_events: VecDeque::new(),
             state: AppState::Running,
             scroll_offset: 0,
             diff_scroll_offset: 0,
             diff_horizontal_offset: 0,
             config,
             theme,
+            theme_registry,
+            keymap,
             hunk_state: HunkViewState::default(),
             review_state,
             diff_mode: DiffMode::default(),
+            diff_layout,
+            comparison: Comparison::default(),
+            history_view: HistoryView::default(),
+            file_sort: FileSort::default(),
+            terminal_width: None,
             max_events,
             selected_theme_index: 0,
             should_quit: false,
             repo_root,
             settings_editor: SettingsEditorState::default(),
             flash_until: None,
+            diff_requests: Vec::new(),
+            requested_context: HashMap::new(),
+            computing: HashSet::new(),
+            syntax: SyntaxHighlighter::default(),
+            head: HeadInfo::default(),
+            index_requests: Vec::new(),
+            status: None,
+            replay: None,
+            commit_editor: CommitEditorState::default(),
+            commit_info_requested: false,
+            commit_request: None,
+            note_editor: NoteEditorState::default(),
         }
     }
 
+    /// Show the events of a recorded session instead of live changes,
+    /// starting from the oldest.
+    pub fn start_replay(&mut self, recording: PathBuf, events: Vec<DisplayedEvent>) {
+        self.max_events = self.max_events.max(events.len());
+        self.events = events.into_iter().rev().collect();
+        self.history_changed();
+        self.replay = Some(recording);
+        self.select(self.entry_count().saturating_sub(1));
+    }
+
+    pub fn is_replay(&self) -> bool {
+        self.replay.is_some()
+    }
+
     pub fn is_paused(&self) -> bool {
         self.state == AppState::Paused
     }
@@ -127,21 +295,242 @@ impl App {
         self.state = match self.state {
             AppState::Running => AppState::Paused,
             AppState::Paused => {
-                self.scroll_offset = 0;
+                self.apply_pending_events();
                 AppState::Running
             }
             _ => self.state.clone(),
         };
     }
 
+    /// Move events captured while paused into the history.
+    fn apply_pending_events(&mut self) {
+        let count = self.pending_events.len();
+        let selected = self.selected_key();
+        while let Some(event) = self.pending_events.pop_front() {
+            self.push_event(event);
+        }
+
+        if !self.config.display.jump_to_newest_on_resume {
+            self.restore_selection(selected);
+        } else if count > 0 {
+            self.select(self.newest_index());
+        } else {
+            self.scroll_offset = 0;
+        }
+        if count > 0 {
+            tracing::info!("Resumed with {} pending events", count);
+        }
+    }
+
+    /// Switch to the next diff base and re-diff the history against it.
     pub fn cycle_diff_mode(&mut self) {
         self.diff_mode = self.diff_mode.next();
+        let requests = self.resync_requests();
+        self.diff_requests.extend(requests);
         tracing::info!("Diff mode changed to: {:?}", self.diff_mode);
     }
 
+    pub fn toggle_diff_layout(&mut self) {
+        self.diff_layout = self.diff_layout.toggle();
+        if self.is_selecting() {
+            self.scroll_to_selection();
+        } else if self.hunk_state.focused_hunk > 0 {
+            self.scroll_to_focused_hunk();
+        } else {
+            self.diff_scroll_offset = self
+                .get_current_event()
+                .map(|event| self.find_first_change_offset(event))
+                .unwrap_or(0);
+        }
+        tracing::info!("Diff layout changed to: {:?}", self.diff_layout);
+    }
+
+    /// Switch the diff pane between the diff against the base and the diff
+    /// against the previous save of the same file.
+    pub fn toggle_comparison(&mut self) {
+        self.comparison = self.comparison.toggle();
+        self.select(self.scroll_offset);
+        tracing::info!("Comparison changed to: {:?}", self.comparison);
+    }
+
+    /// The diff the pane shows for `event`. Falls back to the base diff for a
+    /// file's first save, which has nothing earlier to compare with.
+    pub fn shown_diff<'a>(&self, event: &'a DisplayedEvent) -> &'a FileDiff {
+        match (self.comparison, &event.snapshot.diff) {
+            (Comparison::PreviousSave, Some(diff)) => diff,
+            _ => &event.diff,
+        }
+    }
+
+    /// Content of the latest save of `file_path`, for the next save to be
+    /// diffed against.
+    pub fn latest_snapshot(&self, file_path: &Path) -> Option<Arc<str>> {
+        self.saves
+            .get(file_path)
+            .and_then(|(_, content)| content.clone())
+    }
+
+    /// Keep the content of a save of `file_path`, whether or not it left
+    /// changes, unless a newer save is already kept.
+    pub fn note_save(&mut self, file_path: &Path, timestamp: EventTime, content: Option<Arc<str>>) {
+        match self.saves.get_mut(file_path) {
+            Some(save) if save.0 > timestamp => {}
+            Some(save) => *save = (timestamp, content),
+            None => {
+                self.saves
+                    .insert(file_path.to_path_buf(), (timestamp, content));
+            }
+        }
+    }
+
+    /// The newest event for `file_path`, counting events held while paused.
+    fn latest_event(&self, file_path: &Path) -> Option<&DisplayedEvent> {
+        self.pending_events
+            .iter()
+            .rev()
+            .chain(&self.events)
+            .find(|event| event.file_path == file_path)
+    }
+
+    /// Switch between listing every save and listing files, keeping the
+    /// selected file.
+    pub fn toggle_history_view(&mut self) {
+        let selected = self.selected_key();
+        self.history_view = self.history_view.toggle();
+        let index = selected
+            .and_then(|(path, timestamp)| self.position_of(&path, timestamp))
+            .unwrap_or(0);
+        self.select(index);
+        tracing::info!("History view changed to: {:?}", self.history_view);
+    }
+
+    pub fn cycle_file_sort(&mut self) {
+        let selected = self.selected_key();
+        self.file_sort = self.file_sort.next();
+        self.history_changed();
+        self.restore_selection(selected);
+    }
+
+    /// Regroup the files view on its next use, after `events` or one of
+    /// their diffs changed.
+    pub fn history_changed(&self) {
+        self.file_index.take();
+    }
+
+    /// The latest event of every file that still has changes, in
+    /// `file_sort` order.
+    pub fn file_entries(&self) -> Vec<&DisplayedEvent> {
+        self.with_file_index(|index| index.iter().filter_map(|&i| self.events.get(i)).collect())
+    }
+
+    fn with_file_index<R>(&self, f: impl FnOnce(&[usize]) -> R) -> R {
+        let mut cache = self.file_index.borrow_mut();
+        f(cache.get_or_insert_with(|| self.group_files()))
+    }
+
+    fn group_files(&self) -> Vec<usize> {
+        let mut seen = HashSet::new();
+        let mut files: Vec<usize> = self
+            .events
+            .iter()
+            .enumerate()
+            .filter(|(_, event)| seen.insert(&event.file_path))
+            .filter(|(_, event)| event.diff.has_changes())
+            .filter(|(_, event)| {
+                self.clean_since
+                    .get(&event.file_path)
+                    .map_or(true, |clean| *clean < event.timestamp)
+            })
+            .map(|(i, _)| i)
+            .collect();
+        let events = &self.events;
+        match self.file_sort {
+            FileSort::Recency => {}
+            FileSort::Path => {
+                files.sort_by(|&a, &b| events[a].relative_path.cmp(&events[b].relative_path))
+            }
+            FileSort::Size => files.sort_by_key(|&i| {
+                let stats = &events[i].diff.stats;
+                Reverse(stats.added_count + stats.deleted_count)
+            }),
+        }
+        files
+    }
+
+    /// Number of entries in the list `history_view` shows.
+    pub fn entry_count(&self) -> usize {
+        match self.history_view {
+            HistoryView::Events => self.events.len(),
+            HistoryView::Files => self.with_file_index(|index| index.len()),
+        }
+    }
+
+    /// Index of an event in the current list. In the files view any event of
+    /// the same file matches.
+    fn position_of(&self, file_path: &Path, timestamp: EventTime) -> Option<usize> {
+        match self.history_view {
+            HistoryView::Events => self
+                .events
+                .iter()
+                .position(|e| e.file_path == file_path && e.timestamp == timestamp),
+            HistoryView::Files => self
+                .file_entries()
+                .iter()
+                .position(|e| e.file_path == file_path),
+        }
+    }
+
+    fn selected_key(&self) -> Option<(PathBuf, EventTime)> {
+        self.get_current_event()
+            .map(|event| (event.file_path.clone(), event.timestamp))
+    }
+
+    /// Select the entry for the newest event.
+    fn newest_index(&self) -> usize {
+        self.events
+            .front()
+            .and_then(|event| self.position_of(&event.file_path, event.timestamp))
+            .unwrap_or(0)
+    }
+
+    /// Put the selection back on a previously selected event after the list
+    /// changed, or keep the index in bounds if it is gone.
+    fn restore_selection(&mut self, selected: Option<(PathBuf, EventTime)>) {
+        self.scroll_offset = selected
+            .and_then(|(path, timestamp)| self.position_of(&path, timestamp))
+            .unwrap_or_else(|| self.scroll_offset.min(self.entry_count().saturating_sub(1)));
+    }
+
+    /// Select entry `index` and scroll its diff to the first change.
+    fn select(&mut self, index: usize) {
+        self.scroll_offset = index;
+        self.diff_scroll_offset = self
+            .get_current_event()
+            .map(|event| self.find_first_change_offset(event))
+            .unwrap_or(0);
+        self.diff_horizontal_offset = 0;
+        self.hunk_state.reset();
+    }
+
+    /// Layout the diff pane is drawn with, after the narrow-terminal fallback.
+    pub fn active_layout(&self) -> DiffLayout {
+        match self.terminal_width {
+            Some(width) => self.diff_layout.effective(width),
+            None => self.diff_layout,
+        }
+    }
+
+    /// Rows `lines` take up in the diff pane with the active layout.
+    fn display_rows(&self, lines: &[DiffLine]) -> usize {
+        match self.active_layout() {
+            DiffLayout::Split => build_side_by_side_lines(lines).len(),
+            DiffLayout::Unified => lines.len(),
+        }
+    }
+
     pub fn get_current_hunk_count(&self) -> usize {
         self.get_current_event()
-            .map(|e| e.diff.hunks.len())
+            .map(|e| self.shown_diff(e).hunks.len())
             .unwrap_or(0)
     }
 
@@ -164,20 +553,228 @@ impl App {
 
     pub fn toggle_context_collapsed(&mut self) {
         self.hunk_state.toggle_collapse_context();
+        // Keep the selection cursor off hidden context lines.
+        let on_context = self.hunk_state.collapse_context
+            && self.hunk_state.selection.is_some_and(|selection| {
+                self.focused_hunk()
+                    .and_then(|hunk| hunk.lines.get(selection.cursor))
+                    .is_some_and(|line| line.kind == DiffKind::Context)
+            });
+        if on_context && !self.move_selection(true) {
+            self.move_selection(false);
+        }
     }
 
+    /// The hunk under focus in the diff pane.
+    fn focused_hunk(&self) -> Option<&DiffHunk> {
+        let event = self.get_current_event()?;
+        self.shown_diff(event)
+            .hunks
+            .get(self.hunk_state.focused_hunk)
+    }
+
+    pub fn is_selecting(&self) -> bool {
+        self.hunk_state.selection.is_some()
+    }
+
+    /// Enter visual mode on the first change of the focused hunk, or leave
+    /// it.
+    pub fn toggle_line_selection(&mut self) {
+        if self.hunk_state.selection.take().is_some() {
+            return;
+        }
+        let Some(first) = self
+            .focused_hunk()
+            .and_then(|hunk| hunk.lines.iter().position(|l| l.kind != DiffKind::Context))
+        else {
+            return;
+        };
+        let focused = self.hunk_state.focused_hunk;
+        self.hunk_state.collapsed_hunks.remove(&focused);
+        self.hunk_state.selection = Some(LineSelection {
+            anchor: first,
+            cursor: first,
+        });
+        self.scroll_to_selection();
+    }
+
+    pub fn cancel_line_selection(&mut self) {
+        self.hunk_state.selection = None;
+    }
+
+    /// Move the selection cursor to the next shown line of the hunk, down or
+    /// up. Returns whether it moved.
+    pub fn move_selection(&mut self, down: bool) -> bool {
+        let Some(selection) = self.hunk_state.selection else {
+            return false;
+        };
+        let collapse_context = self.hunk_state.collapse_context;
+        let next = self.focused_hunk().and_then(|hunk| {
+            let shown = |i: &usize| !collapse_context || hunk.lines[*i].kind != DiffKind::Context;
+            if down {
+                (selection.cursor + 1..hunk.lines.len()).find(shown)
+            } else {
+                (0..selection.cursor).rev().find(shown)
+            }
+        });
+        let Some(cursor) = next else {
+            return false;
+        };
+        self.hunk_state.selection = Some(LineSelection {
+            cursor,
+            ..selection
+        });
+        self.scroll_to_selection();
+        true
+    }
+
+    /// Scroll so the selection cursor sits a few rows below the top, like
+    /// the first change of a new event.
+    fn scroll_to_selection(&mut self) {
+        if let Some(row) = self.selection_cursor_row() {
+            self.diff_scroll_offset = row.saturating_sub(2);
+        }
+    }
+
+    /// Row of the diff pane the selection cursor is drawn on, counting hunk
+    /// headers.
+    fn selection_cursor_row(&self) -> Option<usize> {
+        let cursor = self.hunk_state.selection?.cursor;
+        let event = self.get_current_event()?;
+        let collapse_context = self.hunk_state.collapse_context;
+        let mut row = 0;
+        for (i, hunk) in self.shown_diff(event).hunks.iter().enumerate() {
+            row += 1;
+            let (indices, lines): (Vec<usize>, Vec<DiffLine>) = hunk
+                .lines
+                .iter()
+                .enumerate()
+                .filter(|(_, l)| !collapse_context || l.kind != DiffKind::Context)
+                .map(|(i, l)| (i, l.clone()))
+                .unzip();
+            if i == self.hunk_state.focused_hunk {
+                let within = match self.active_layout() {
+                    DiffLayout::Unified => indices.iter().position(|&i| i == cursor),
+                    DiffLayout::Split => build_side_by_side_lines(&lines).iter().position(|r| {
+                        [r.left_index, r.right_index]
+                            .into_iter()
+                            .flatten()
+                            .any(|i| indices[i] == cursor)
+                    }),
+                };
+                return within.map(|within| row + within);
+            }
+            row += if self.hunk_state.is_collapsed(i) {
+                1
+            } else {
+                self.display_rows(&lines)
+            };
+        }
+        None
+    }
+
+    /// Mark every hunk of the current event reviewed, or unmark them all
+    /// when they already are.
     pub fn toggle_current_reviewed(&mut self) {
         if let Some(event) = self.get_current_event() {
-            let path = event.file_path.clone();
-            self.review_state.toggle_reviewed(&path);
-            if let Err(e) = self.review_state.save() {
-                tracing::warn!("Failed to save review state: {}", e);
-            }
+            let path = PathBuf::from(&event.relative_path);
+            let keys = review_keys(self.shown_diff(event));
+            self.review_state.toggle_reviewed(&path, &keys);
+            self.save_review_state();
         }
     }
 
+    pub fn toggle_focused_hunk_reviewed(&mut self) {
+        let Some(event) = self.get_current_event() else {
+            return;
+        };
+        let path = PathBuf::from(&event.relative_path);
+        let Some(key) = self
+            .shown_diff(event)
+            .hunks
+            .get(self.hunk_state.focused_hunk)
+            .map(hunk_key)
+        else {
+            return;
+        };
+        self.review_state.toggle_hunk_reviewed(&path, &key);
+        self.save_review_state();
+    }
+
+    /// Reviewed and total hunks of the current event, for diffs with hunks.
+    pub fn review_progress(&self) -> Option<(usize, usize)> {
+        let event = self.get_current_event()?;
+        let diff = self.shown_diff(event);
+        if diff.hunks.is_empty() {
+            return None;
+        }
+        let keys = review_keys(diff);
+        Some((
+            self.review_state
+                .reviewed_of(Path::new(&event.relative_path), &keys),
+            keys.len(),
+        ))
+    }
+
+    /// Whether every hunk of `event`, as currently shown, is reviewed.
+    pub fn is_event_reviewed(&self, event: &DisplayedEvent) -> bool {
+        self.review_state.is_reviewed(
+            Path::new(&event.relative_path),
+            &review_keys(self.shown_diff(event)),
+        )
+    }
+
+    pub fn is_hunk_reviewed(&self, event: &DisplayedEvent, hunk: &DiffHunk) -> bool {
+        self.review_state
+            .is_hunk_reviewed(Path::new(&event.relative_path), &hunk_key(hunk))
+    }
+
     pub fn clear_all_reviewed(&mut self) {
         self.review_state.clear_all();
+        self.save_review_state();
+    }
+
+    /// Turn a whole-file mark left by an older version into marks for the
+    /// hunks of `event`, the first time its file is shown.
+    fn adopt_file_mark(&mut self, event: &DisplayedEvent) {
+        if self.is_replay() || self.review_state.reviewed_files.is_empty() {
+            return;
+        }
+        let modified = std::fs::metadata(&event.file_path)
+            .and_then(|m| m.modified())
+            .ok();
+        if self.review_state.adopt_file_mark(
+            Path::new(&event.relative_path),
+            modified,
+            &review_keys(&event.diff),
+        ) {
+            self.save_review_state();
+        }
+    }
+
+    /// Drop the review marks of `event`'s file that match none of its hunks,
+    /// against the base or the previous save, when it is the file's newest.
+    fn prune_review_marks(&mut self, event: &DisplayedEvent) {
+        let newer = self
+            .events
+            .iter()
+            .any(|e| e.file_path == event.file_path && e.timestamp > event.timestamp);
+        if self.is_replay() || newer {
+            return;
+        }
+        let mut keys = review_keys(&event.diff);
+        if let Some(diff) = &event.snapshot.diff {
+            keys.extend(review_keys(diff));
+        }
+        if self
+            .review_state
+            .retain_marks(Path::new(&event.relative_path), &keys)
+        {
+            self.save_review_state();
+        }
+    }
+
+    fn save_review_state(&self) {
         if let Err(e) = self.review_state.save() {
             tracing::warn!("Failed to save review state: {}", e);
         }
@@ -186,13 +783,13 @@ impl App {
     fn scroll_to_focused_hunk(&mut self) {
         if let Some(event) = self.get_current_event() {
             let mut line_offset = 0;
-            for (i, hunk) in event.diff.hunks.iter().enumerate() {
+            for (i, hunk) in self.shown_diff(event).hunks.iter().enumerate() {
                 if i == self.hunk_state.focused_hunk {
                     self.diff_scroll_offset = line_offset;
                     return;
                 }
                 if !self.hunk_state.is_collapsed(i) {
-                    line_offset += hunk.lines.len();
+                    line_offset += self.display_rows(&hunk.lines);
                 } else {
                     line_offset += 1; // Collapsed shows 1 summary line
                 }
@@ -201,29 +798,304 @@ impl App {
     }
 
     pub fn add_event(&mut self, event: DisplayedEvent) {
-        if self.events.len() >= self.max_events {
-            self.events.pop_back();
+        if self.is_paused() {
+            if self.pending_events.len() >= self.max_events {
+                self.pending_events.pop_front();
+            }
+            self.pending_events.push_back(event);
+            tracing::info!("New event (paused): {} pending", self.pending_events.len());
+            return;
         }
 
         // Calculate scroll offset to focus on first actual change (skip context lines)
         let first_change_offset = self.find_first_change_offset(&event);
 
-        self.events.push_front(event);
+        self.push_event(event);
 
         // Always set flash effect for visual feedback
         self.flash_until = Some(Instant::now() + Duration::from_millis(1500));
 
-        if !self.is_paused() {
-            self.scroll_offset = 0;
-            self.diff_scroll_offset = first_change_offset;
-            self.diff_horizontal_offset = 0;
-            self.hunk_state.reset();
-            tracing::info!(
-                "New event: scroll to line {}, flash enabled",
-                first_change_offset
+        self.scroll_offset = self.newest_index();
+        self.diff_scroll_offset = first_change_offset;
+        self.diff_horizontal_offset = 0;
+        self.hunk_state.reset();
+        tracing::info!(
+            "New event: scroll to line {}, flash enabled",
+            first_change_offset
+        );
+    }
+
+    /// Add an event found by the startup scan. The history stays ordered by
+    /// time, and nothing flashes or moves the selection.
+    pub fn seed_event(&mut self, event: DisplayedEvent) {
+        let index = self
+            .events
+            .iter()
+            .position(|e| e.timestamp < event.timestamp)
+            .unwrap_or(self.events.len());
+        if index >= self.max_events {
+            return;
+        }
+
+        self.adopt_file_mark(&event);
+        self.prune_review_marks(&event);
+        let was_empty = self.events.is_empty();
+        let selected = self.selected_key();
+        self.events.insert(index, event);
+        self.events.truncate(self.max_events);
+        self.history_changed();
+        if was_empty {
+            self.select(self.newest_index());
+        } else {
+            self.restore_selection(selected);
+        }
+    }
+
+    fn push_event(&mut self, event: DisplayedEvent) {
+        self.adopt_file_mark(&event);
+        self.prune_review_marks(&event);
+        if self.events.len() >= self.max_events {
+            self.events.pop_back();
+        }
+        self.events.push_front(event);
+        self.history_changed();
+    }
+
+    pub fn grow_context(&mut self) {
+        self.request_context_change(true);
+    }
+
+    pub fn shrink_context(&mut self) {
+        self.request_context_change(false);
+    }
+
+    fn request_context_change(&mut self, grow: bool) {
+        let Some(event) = self.get_current_event() else {
+            return;
+        };
+        let current = event.diff.context_lines;
+        let context_lines = if grow {
+            current + CONTEXT_STEP
+        } else {
+            current.saturating_sub(CONTEXT_STEP)
+        };
+        if context_lines == current {
+            return;
+        }
+        let request = DiffRequest::for_event(event, event.mode, context_lines);
+        self.requested_context.insert(
+            (request.file_path.clone(), request.timestamp),
+            context_lines,
+        );
+        self.diff_requests.push(request);
+    }
+
+    /// Context lines `event` is shown with, or will be once a pending
+    /// grow/shrink has been computed. A resync replaces that refresh in the
+    /// worker, so it has to carry the new context along.
+    fn context_lines(&self, event: &DisplayedEvent) -> usize {
+        self.requested_context
+            .get(&(event.file_path.clone(), event.timestamp))
+            .copied()
+            .unwrap_or(event.diff.context_lines)
+    }
+
+    /// Stage or unstage the lines selected in visual mode.
+    pub fn request_index_action(&mut self, action: IndexAction) {
+        let Some(event) = self.get_current_event() else {
+            return;
+        };
+        let diff = &event.diff;
+        let problem = if !action.applies_in(event.mode) {
+            Some(format!(
+                "Cannot {} in {} mode",
+                action.verb(),
+                event.mode.label()
+            ))
+        } else if self.comparison == Comparison::PreviousSave && event.snapshot.diff.is_some() {
+            Some(format!(
+                "Switch to the base comparison to {}",
+                action.verb()
+            ))
+        } else if self
+            .latest_event(&event.file_path)
+            .is_some_and(|latest| latest.timestamp != event.timestamp)
+        {
+            Some("File changed since it was diffed; select its newest save".to_string())
+        } else if diff.is_new_file || diff.is_deleted || diff.is_binary {
+            Some(format!(
+                "Use git to {} new, deleted or binary files",
+                action.verb()
+            ))
+        } else if diff.is_truncated || diff.renamed_from.is_some() {
+            Some(format!(
+                "Use git to {} truncated or renamed diffs",
+                action.verb()
+            ))
+        } else {
+            None
+        };
+        if let Some(problem) = problem {
+            self.set_status(problem, true);
+            return;
+        }
+
+        let Some(lines) = self.hunk_state.selection.map(|selection| selection.range()) else {
+            self.set_status(
+                format!(
+                    "Select lines with {} to {} them",
+                    self.keymap.short_label(Action::SelectLines),
+                    action.verb()
+                ),
+                true,
             );
+            return;
+        };
+        if diff.hunks.is_empty() {
+            return;
+        }
+        let hunk = self.hunk_state.focused_hunk.min(diff.hunks.len() - 1);
+        if changed_lines(&diff.hunks[hunk], &lines) == 0 {
+            self.set_status("No changed lines selected".to_string(), true);
+            return;
+        }
+
+        let request = IndexRequest {
+            action,
+            file_path: event.file_path.clone(),
+            relative_path: event.relative_path.clone(),
+            mode: event.mode,
+            diff: diff.clone(),
+            hunk,
+            lines,
+        };
+        self.hunk_state.selection = None;
+        self.index_requests.push(request);
+    }
+
+    pub fn take_index_requests(&mut self) -> Vec<IndexRequest> {
+        std::mem::take(&mut self.index_requests)
+    }
+
+    pub fn set_status(&mut self, text: String, is_error: bool) {
+        self.status = Some(StatusMessage {
+            text,
+            is_error,
+            until: Instant::now() + STATUS_DURATION,
+        });
+    }
+
+    /// The status message, unless it has expired.
+    pub fn status_message(&self) -> Option<&StatusMessage> {
+        self.status
+            .as_ref()
+            .filter(|status| Instant::now() < status.until)
+    }
+
+    pub fn take_diff_requests(&mut self) -> Vec<DiffRequest> {
+        std::mem::take(&mut self.diff_requests)
+    }
+
+    /// Requests recomputing every event in the history against the current
+    /// diff mode, after the mode or the base it diffs against has changed.
+    pub fn resync_requests(&self) -> Vec<DiffRequest> {
+        self.events
+            .iter()
+            .chain(&self.pending_events)
+            .map(|event| DiffRequest::for_event(event, self.diff_mode, self.context_lines(event)))
+            .collect()
+    }
+
+    /// Drop the event a [`DiffRequest`] was made for, keeping the selection on
+    /// the same event where possible.
+    pub fn remove_event(&mut self, request: &DiffRequest) {
+        self.requested_context
+            .remove(&(request.file_path.clone(), request.timestamp));
+        if let Some(index) = self.pending_events.iter().position(|e| request.targets(e)) {
+            self.pending_events.remove(index);
+            return;
+        }
+        let Some(index) = self.events.iter().position(|e| request.targets(e)) else {
+            return;
+        };
+        let was_selected = self.get_current_event().is_some_and(|e| request.targets(e));
+        let selected = self.selected_key();
+        self.events.remove(index);
+        self.history_changed();
+
+        if was_selected {
+            self.select(self.scroll_offset.min(self.entry_count().saturating_sub(1)));
         } else {
-            tracing::info!("New event (paused): flash only, no scroll");
+            self.restore_selection(selected);
+        }
+    }
+
+    /// Note that a save at `timestamp` left `file_path` without changes, so
+    /// the files view stops listing it until it changes again, and its review
+    /// marks no longer match any hunk.
+    pub fn mark_clean(&mut self, file_path: PathBuf, timestamp: EventTime) {
+        let newer = self
+            .events
+            .iter()
+            .any(|e| e.file_path == file_path && e.timestamp > timestamp);
+        if !self.is_replay() && !newer {
+            if let Ok(relative) = file_path.strip_prefix(&self.repo_root) {
+                if self.review_state.retain_marks(relative, &[]) {
+                    self.save_review_state();
+                }
+            }
+        }
+        let selected = self.selected_key();
+        self.clean_since.insert(file_path, timestamp);
+        self.history_changed();
+        self.restore_selection(selected);
+    }
+
+    /// Replace the diff since the previous save of the event a
+    /// [`DiffRequest`] was made for.
+    pub fn apply_since_previous(&mut self, request: &DiffRequest, diff: FileDiff) {
+        if let Some(event) = self
+            .events
+            .iter_mut()
+            .chain(self.pending_events.iter_mut())
+            .find(|e| request.targets(e))
+        {
+            event.snapshot.diff = Some(diff);
+        }
+    }
+
+    /// Replace the diff of the event a [`DiffRequest`] was made for. Does
+    /// nothing if the event has since left the history.
+    pub fn apply_diff_result(&mut self, request: &DiffRequest, diff: FileDiff) {
+        let key = (request.file_path.clone(), request.timestamp);
+        if self.requested_context.get(&key) == Some(&request.context_lines) {
+            self.requested_context.remove(&key);
+        }
+        if let Some(event) = self.pending_events.iter_mut().find(|e| request.targets(e)) {
+            event.diff = diff;
+            event.mode = request.mode;
+            return;
+        }
+        let Some(index) = self.events.iter().position(|e| request.targets(e)) else {
+            return;
+        };
+        let was_selected = self.get_current_event().is_some_and(|e| request.targets(e));
+        let selected = self.selected_key();
+        let hunk_count = diff.hunks.len();
+        self.events[index].diff = diff;
+        self.events[index].mode = request.mode;
+        self.history_changed();
+        // The files view may reorder or drop the file with its new stats.
+        self.restore_selection(selected);
+
+        if was_selected {
+            // Selected lines may have moved within the new diff.
+            self.hunk_state.selection = None;
+            if self.hunk_state.focused_hunk >= hunk_count {
+                self.hunk_state.reset();
+            }
+            let max = self.get_current_diff_line_count();
+            self.diff_scroll_offset = self.diff_scroll_offset.min(max.saturating_sub(1));
         }
     }
 
@@ -234,10 +1106,8 @@ impl App {
     }
 
     fn find_first_change_offset(&self, event: &DisplayedEvent) -> usize {
-        use crate::types::DiffKind;
-
-        let all_lines: Vec<_> = event
-            .diff
+        let all_lines: Vec<_> = self
+            .shown_diff(event)
             .hunks
             .iter()
             .flat_map(|h| h.lines.clone())
@@ -245,18 +1115,25 @@ impl App {
 
         tracing::debug!("find_first_change_offset: {} raw lines", all_lines.len());
 
-        let side_by_side = build_side_by_side_lines(&all_lines);
+        let is_change: Vec<bool> = match self.active_layout() {
+            DiffLayout::Split => build_side_by_side_lines(&all_lines)
+                .iter()
+                .map(|line| {
+                    matches!(line.left_kind, Some(DiffKind::Deleted))
+                        || matches!(line.right_kind, Some(DiffKind::Added))
+                })
+                .collect(),
+            DiffLayout::Unified => all_lines
+                .iter()
+                .map(|line| line.kind != DiffKind::Context)
+                .collect(),
+        };
 
-        tracing::debug!(
-            "find_first_change_offset: {} side-by-side lines",
-            side_by_side.len()
-        );
+        tracing::debug!("find_first_change_offset: {} display rows", is_change.len());
 
         // Find first non-context line (actual change)
-        for (idx, line) in side_by_side.iter().enumerate() {
-            let is_change = matches!(line.left_kind, Some(DiffKind::Deleted))
-                || matches!(line.right_kind, Some(DiffKind::Added));
-            if is_change {
+        for (idx, changed) in is_change.into_iter().enumerate() {
+            if changed {
                 tracing::info!(
                     "First change at line {}, scrolling to {}",
                     idx,
@@ -272,7 +1149,7 @@ impl App {
     }
 
     pub fn scroll_up(&mut self) {
-        if self.scroll_offset < self.events.len().saturating_sub(1) {
+        if self.scroll_offset < self.entry_count().saturating_sub(1) {
             self.scroll_offset += 1;
             self.diff_scroll_offset = 0;
             self.diff_horizontal_offset = 0;
@@ -311,11 +1188,20 @@ impl App {
 
     pub fn clear_history(&mut self) {
         self.events.clear();
+        self.clean_since.clear();
+        self.requested_context.clear();
+        self.history_changed();
         self.scroll_offset = 0;
     }
 
+    /// Themes offered by the theme selector, built-ins first.
+    pub fn theme_names(&self) -> Vec<String> {
+        Theme::names(&self.config.theme, &self.theme_registry)
+    }
+
     pub fn open_theme_selector(&mut self) {
-        let themes = Theme::available_themes();
+        self.theme_registry = self.theme_registry.reload();
+        let themes = self.theme_names();
         self.selected_theme_index = themes
             .iter()
             .position(|t| t.to_lowercase() == self.config.theme.name.to_lowercase())
@@ -349,7 +1235,23 @@ impl App {
     pub fn save_settings(&mut self) -> bool {
         match serde_json::from_str::<Config>(&self.settings_editor.content) {
             Ok(new_config) => {
-                self.theme = Theme::by_name(&new_config.theme.name);
+                let keymap = Keymap::from_config(&new_config.keybindings);
+                if !keymap.issues.is_empty() {
+                    self.settings_editor.error_message =
+                        Some(format!("Keybindings: {}", keymap.issues.join("; ")));
+                    return false;
+                }
+                let (theme, theme_errors) =
+                    Theme::from_config(&new_config.theme, &self.theme_registry);
+                if !theme_errors.is_empty() {
+                    self.settings_editor.error_message = Some(theme_errors.join("; "));
+                    return false;
+                }
+                self.theme = theme;
+                self.keymap = keymap;
+                if new_config.display.diff_layout != self.config.display.diff_layout {
+                    self.diff_layout = new_config.display.diff_layout;
+                }
                 self.max_events = new_config.watcher.max_events_buffer;
                 self.config = new_config;
                 if let Err(e) = self.config.save() {
@@ -366,17 +1268,191 @@ impl App {
         }
     }
 
+    pub fn open_commit_editor(&mut self) {
+        self.commit_editor.info = None;
+        self.commit_editor.error_message = None;
+        self.commit_info_requested = true;
+        self.state = AppState::CommitEditor;
+    }
+
+    pub fn take_commit_info_request(&mut self) -> bool {
+        std::mem::take(&mut self.commit_info_requested)
+    }
+
+    pub fn set_commit_info(&mut self, info: CommitInfo) {
+        self.commit_editor.info = Some(info);
+    }
+
+    /// Switch between a new commit and amending HEAD. An empty message is
+    /// filled with the message of the commit being amended.
+    pub fn toggle_amend(&mut self) {
+        let editor = &mut self.commit_editor;
+        let head_message = editor
+            .info
+            .as_ref()
+            .and_then(|info| info.head_message.as_deref())
+            .map(str::trim_end);
+        if editor.amend {
+            editor.amend = false;
+            if head_message == Some(editor.message.as_str()) {
+                editor.message.clear();
+                editor.cursor_line = 0;
+                editor.cursor_col = 0;
+            }
+            return;
+        }
+        let Some(head_message) = head_message else {
+            editor.error_message = Some("There is no commit to amend".to_string());
+            return;
+        };
+        editor.amend = true;
+        editor.error_message = None;
+        if editor.message.trim().is_empty() {
+            editor.message = head_message.to_string();
+            editor.cursor_line = 0;
+            editor.cursor_col = 0;
+        }
+    }
+
+    /// Queue the commit for the main loop, unless there is nothing to commit.
+    pub fn submit_commit(&mut self) {
+        let editor = &mut self.commit_editor;
+        let problem = match &editor.info {
+            None => Some("Still reading the staged changes"),
+            Some(info) if info.signature.is_err() => {
+                Some("Set user.name and user.email in the git config to commit")
+            }
+            Some(info) if info.staged.is_empty() && !editor.amend => {
+                Some("Nothing staged to commit")
+            }
+            Some(_) if editor.message.trim().is_empty() => Some("The commit message is empty"),
+            Some(_) => None,
+        };
+        if let Some(problem) = problem {
+            editor.error_message = Some(problem.to_string());
+            return;
+        }
+        self.commit_request = Some(CommitRequest {
+            message: editor.message.clone(),
+            amend: editor.amend,
+        });
+    }
+
+    pub fn take_commit_request(&mut self) -> Option<CommitRequest> {
+        self.commit_request.take()
+    }
+
+    /// Close the commit editor after a successful commit with id `short_id`,
+    /// or keep it open with `error`.
+    pub fn finish_commit(&mut self, result: Result<String, String>) {
+        match result {
+            Ok(short_id) => {
+                let verb = if self.commit_editor.amend {
+                    "Amended"
+                } else {
+                    "Committed"
+                };
+                self.commit_editor = CommitEditorState::default();
+                self.close_overlay();
+                self.set_status(format!("{verb} {short_id}"), false);
+            }
+            Err(error) => self.commit_editor.error_message = Some(error),
+        }
+    }
+
+    /// Open the note editor on the cursor line in visual mode, or else on
+    /// the focused hunk, with the note already there if any.
+    pub fn open_note_editor(&mut self) {
+        let Some(event) = self.get_current_event() else {
+            return;
+        };
+        let Some(hunk) = self.focused_hunk() else {
+            self.set_status("No hunk to write a note on".to_string(), true);
+            return;
+        };
+        let path = Path::new(&event.relative_path);
+        let cursor = self
+            .hunk_state
+            .selection
+            .map(|selection| selection.cursor)
+            .filter(|&cursor| cursor < hunk.lines.len());
+        let note = Note::new(path, hunk, cursor.map(|cursor| &hunk.lines[cursor]));
+        let original = self
+            .review_state
+            .notes_on(path, &note.hunk)
+            .find(|n| match cursor {
+                Some(cursor) => n.line_index(hunk) == Some(cursor),
+                None => n.line.is_none(),
+            })
+            .cloned();
+
+        let text = original
+            .as_ref()
+            .map(|n| n.text.clone())
+            .unwrap_or_default();
+        self.note_editor = NoteEditorState {
+            cursor_line: text.split('\n').count() - 1,
+            cursor_col: text
+                .split('\n')
+                .next_back()
+                .map_or(0, |l| l.chars().count()),
+            text,
+            note: Some(note),
+            original,
+        };
+        self.state = AppState::NoteEditor;
+    }
+
+    /// Store the note being edited; an empty note removes it.
+    pub fn save_note(&mut self) {
+        let editor = std::mem::take(&mut self.note_editor);
+        let Some(mut note) = editor.note else {
+            return;
+        };
+        let text = editor.text.trim();
+        let saved = (!text.is_empty()).then(|| {
+            note.text = text.to_string();
+            if let Some(original) = &editor.original {
+                note.created_at = original.created_at;
+            }
+            note
+        });
+        let status = match (&saved, &editor.original) {
+            (Some(_), _) => "Note saved",
+            (None, Some(_)) => "Note deleted",
+            (None, None) => "Empty note discarded",
+        };
+        self.review_state.set_note(editor.original.as_ref(), saved);
+        self.save_review_state();
+        self.hunk_state.selection = None;
+        self.close_overlay();
+        self.set_status(status.to_string(), false);
+    }
+
+    /// Notes on `hunk` of `event`, hunk notes first.
+    pub fn hunk_notes<'a>(&'a self, event: &'a DisplayedEvent, hunk: &DiffHunk) -> Vec<&'a Note> {
+        let key = hunk_key(hunk);
+        let mut notes: Vec<&Note> = self
+            .review_state
+            .notes
+            .iter()
+            .filter(|n| n.path == Path::new(&event.relative_path) && n.hunk == key)
+            .collect();
+        notes.sort_by_key(|n| (n.line.is_some(), n.line_index(hunk)));
+        notes
+    }
+
     pub fn select_theme(&mut self, index: usize) {
-        let themes = Theme::available_themes();
-        if index < themes.len() {
-            self.config.theme.name = themes[index].to_string();
-            self.theme = Theme::by_name(themes[index]);
+        let themes = self.theme_names();
+        if let Some(name) = themes.get(index) {
+            self.config.theme.name = name.clone();
+            self.theme = build_theme(&self.config, &self.theme_registry);
             let _ = self.config.save();
         }
     }
 
     pub fn theme_selector_up(&mut self) {
-        let count = Theme::available_themes().len();
+        let count = self.theme_names().len();
         if self.selected_theme_index > 0 {
             self.selected_theme_index -= 1;
         } else {
@@ -385,17 +1461,22 @@ impl App {
     }
 
     pub fn theme_selector_down(&mut self) {
-        let count = Theme::available_themes().len();
+        let count = self.theme_names().len();
         self.selected_theme_index = (self.selected_theme_index + 1) % count;
     }
 
     pub fn get_current_event(&self) -> Option<&DisplayedEvent> {
-        self.events.get(self.scroll_offset)
+        match self.history_view {
+            HistoryView::Events => self.events.get(self.scroll_offset),
+            HistoryView::Files => self
+                .with_file_index(|index| index.get(self.scroll_offset).copied())
+                .and_then(|i| self.events.get(i)),
+        }
     }
 
     pub fn get_first_changed_line(&self) -> Option<usize> {
         self.get_current_event().and_then(|event| {
-            event.diff.hunks.first().and_then(|hunk| {
+            self.shown_diff(event).hunks.first().and_then(|hunk| {
                 hunk.lines
                     .first()
                     .and_then(|line| line.new_line_number.or(line.old_line_number))
@@ -406,13 +1487,13 @@ impl App {
     pub fn get_current_diff_line_count(&self) -> usize {
         self.get_current_event()
             .map(|event| {
-                let all_lines: Vec<_> = event
-                    .diff
+                let all_lines: Vec<_> = self
+                    .shown_diff(event)
                     .hunks
                     .iter()
                     .flat_map(|h| h.lines.clone())
                     .collect();
-                build_side_by_side_lines(&all_lines).len()
+                self.display_rows(&all_lines)
             })
             .unwrap_or(0)
     }
@@ -421,7 +1502,12 @@ impl App {
         match Config::load() {
             Ok(new_config) => {
                 tracing::info!("Config reloaded: theme={}", new_config.theme.name);
-                self.theme = Theme::by_name(&new_config.theme.name);
+                self.theme_registry = self.theme_registry.reload();
+                self.theme = build_theme(&new_config, &self.theme_registry);
+                self.keymap = build_keymap(&new_config);
+                if new_config.display.diff_layout != self.config.display.diff_layout {
+                    self.diff_layout = new_config.display.diff_layout;
+                }
                 self.max_events = new_config.watcher.max_events_buffer;
                 self.config = new_config;
             }
@@ -432,11 +1518,27 @@ impl App {
     }
 }
 
+fn build_theme(config: &Config, registry: &ThemeRegistry) -> Theme {
+    let (theme, errors) = Theme::from_config(&config.theme, registry);
+    for error in &errors {
+        tracing::warn!("Theme issue: {}", error);
+    }
+    theme
+}
+
+fn build_keymap(config: &Config) -> Keymap {
+    let keymap = Keymap::from_config(&config.keybindings);
+    for issue in &keymap.issues {
+        tracing::warn!("Keybinding issue: {}", issue);
+    }
+    keymap
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::config::Config;
-    use crate::types::{DiffHunk, FileDiff};
+    use crate::types::{DiffHunk, FileDiff, SaveSnapshot};
     use std::path::PathBuf;
 
     fn test_app() -> App {
@@ -444,6 +1546,7 @@ mod tests {
             Config::default(),
             PathBuf::from("/tmp/test"),
             ReviewState::default(),
+            ThemeRegistry::default(),
         )
     }
 
@@ -529,6 +1632,8 @@ mod tests {
             relative_path: "file.rs".to_string(),
             timestamp: chrono::Utc::now(),
             diff,
+            mode: DiffMode::All,
+            snapshot: SaveSnapshot::default(),
         };
         app.events.push_front(event);
 
@@ -548,6 +1653,36 @@ mod tests {
         assert_eq!(app.review_state.reviewed_count(), 0);
     }
 
+    #[test]
+    fn test_seeded_event_takes_over_a_file_mark() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let file_path = dir.path().join("file.rs");
+        std::fs::write(&file_path, "new\n").unwrap();
+        let mut app = test_app();
+        app.review_state.reviewed_files.insert(
+            PathBuf::from("file.rs"),
+            crate::review_state::ReviewEntry {
+                reviewed_at: chrono::Utc::now() + chrono::Duration::hours(1),
+            },
+        );
+
+        let diff = FileDiff {
+            hunks: vec![DiffHunk::default()],
+            ..FileDiff::default()
+        };
+        let keys = review_keys(&diff);
+        app.seed_event(DisplayedEvent {
+            file_path,
+            relative_path: "file.rs".to_string(),
+            timestamp: chrono::Utc::now(),
+            diff,
+            mode: DiffMode::All,
+            snapshot: SaveSnapshot::default(),
+        });
+        assert!(app.review_state.reviewed_files.is_empty());
+        assert!(app.review_state.is_reviewed(Path::new("file.rs"), &keys));
+    }
+
     #[test]
     fn test_toggle_current_reviewed() {
         let mut app = test_app();
@@ -557,13 +1692,409 @@ mod tests {
             relative_path: "file.rs".to_string(),
             timestamp: chrono::Utc::now(),
             diff: FileDiff::default(),
+            mode: DiffMode::All,
+            snapshot: SaveSnapshot::default(),
         };
-        let path = event.file_path.clone();
+        let path = PathBuf::from(&event.relative_path);
         app.events.push_front(event);
 
-        assert!(!app.review_state.is_reviewed(&path));
+        let keys = review_keys(&FileDiff::default());
+        assert!(!app.review_state.is_reviewed(&path, &keys));
         app.toggle_current_reviewed();
-        assert!(app.review_state.is_reviewed(&path));
+        assert!(app.review_state.is_reviewed(&path, &keys));
+    }
+
+    #[test]
+    fn test_grow_and_shrink_context_queue_requests() {
+        let mut app = test_app();
+        app.events.push_front(DisplayedEvent {
+            file_path: PathBuf::from("/test/file.rs"),
+            relative_path: "file.rs".to_string(),
+            timestamp: chrono::Utc::now(),
+            diff: FileDiff {
+                context_lines: 3,
+                ..Default::default()
+            },
+            mode: DiffMode::All,
+            snapshot: SaveSnapshot::default(),
+        });
+
+        app.grow_context();
+        app.shrink_context();
+        let requests = app.take_diff_requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].context_lines, 6);
+        assert_eq!(requests[1].context_lines, 0);
+        assert!(app.diff_requests.is_empty());
+    }
+
+    #[test]
+    fn test_shrink_context_at_zero_is_noop() {
+        let mut app = test_app();
+        app.events.push_front(DisplayedEvent {
+            file_path: PathBuf::from("/test/file.rs"),
+            relative_path: "file.rs".to_string(),
+            timestamp: chrono::Utc::now(),
+            diff: FileDiff::default(),
+            mode: DiffMode::All,
+            snapshot: SaveSnapshot::default(),
+        });
+
+        app.shrink_context();
+        assert!(app.diff_requests.is_empty());
+    }
+
+    #[test]
+    fn test_apply_diff_result_replaces_matching_event() {
+        let mut app = test_app();
+        let timestamp = chrono::Utc::now();
+        app.events.push_front(DisplayedEvent {
+            file_path: PathBuf::from("/test/file.rs"),
+            relative_path: "file.rs".to_string(),
+            timestamp,
+            diff: FileDiff::default(),
+            mode: DiffMode::All,
+            snapshot: SaveSnapshot::default(),
+        });
+        app.grow_context();
+        let request = app.take_diff_requests().remove(0);
+
+        app.apply_diff_result(
+            &request,
+            FileDiff {
+                context_lines: request.context_lines,
+                ..Default::default()
+            },
+        );
+        assert_eq!(app.events[0].diff.context_lines, CONTEXT_STEP);
+    }
+
+    #[test]
+    fn test_resync_keeps_pending_context_change() {
+        let mut app = test_app();
+        app.events.push_front(DisplayedEvent {
+            file_path: PathBuf::from("/test/file.rs"),
+            relative_path: "file.rs".to_string(),
+            timestamp: chrono::Utc::now(),
+            diff: FileDiff::default(),
+            mode: DiffMode::All,
+            snapshot: SaveSnapshot::default(),
+        });
+        app.grow_context();
+        assert_eq!(app.take_diff_requests()[0].context_lines, CONTEXT_STEP);
+
+        // HEAD moved before the refresh came back.
+        let resync = app.resync_requests().remove(0);
+        assert_eq!(resync.context_lines, CONTEXT_STEP);
+
+        app.apply_diff_result(
+            &resync,
+            FileDiff {
+                context_lines: resync.context_lines,
+                ..Default::default()
+            },
+        );
+        assert!(app.requested_context.is_empty());
+    }
+
+    #[test]
+    fn test_remove_event_keeps_selection() {
+        let mut app = test_app();
+        let base = chrono::Utc::now();
+        for i in 0..3 {
+            app.events.push_front(DisplayedEvent {
+                file_path: PathBuf::from(format!("/test/file{i}.rs")),
+                relative_path: format!("file{i}.rs"),
+                timestamp: base + chrono::Duration::seconds(i),
+                diff: FileDiff::default(),
+                mode: DiffMode::All,
+                snapshot: SaveSnapshot::default(),
+            });
+        }
+        app.scroll_offset = 1;
+
+        let requests = app.resync_requests();
+        assert_eq!(requests.len(), 3);
+        app.remove_event(&requests[0]);
+        assert_eq!(app.events.len(), 2);
+        assert_eq!(app.scroll_offset, 0);
+        assert_eq!(app.events[0].relative_path, "file1.rs");
+
+        app.remove_event(&requests[2]);
+        app.remove_event(&requests[1]);
+        assert!(app.events.is_empty());
+        assert_eq!(app.scroll_offset, 0);
+    }
+
+    #[test]
+    fn test_line_count_follows_layout() {
+        use crate::types::{DiffKind, DiffLine};
+
+        let line = |kind: DiffKind, old: Option<usize>, new: Option<usize>| DiffLine {
+            old_line_number: old,
+            new_line_number: new,
+            kind,
+            content: "x".to_string(),
+            emphasis: Vec::new(),
+            missing_newline: false,
+        };
+        let mut app = test_app();
+        app.events.push_front(DisplayedEvent {
+            file_path: PathBuf::from("/test/file.rs"),
+            relative_path: "file.rs".to_string(),
+            timestamp: chrono::Utc::now(),
+            diff: FileDiff {
+                hunks: vec![DiffHunk {
+                    lines: vec![
+                        line(DiffKind::Deleted, Some(1), None),
+                        line(DiffKind::Added, None, Some(1)),
+                    ],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+            mode: DiffMode::All,
+            snapshot: SaveSnapshot::default(),
+        });
+
+        assert_eq!(app.get_current_diff_line_count(), 1);
+        app.toggle_diff_layout();
+        assert_eq!(app.diff_layout, DiffLayout::Unified);
+        assert_eq!(app.get_current_diff_line_count(), 2);
+
+        app.toggle_diff_layout();
+        app.terminal_width = Some(DiffLayout::MIN_SPLIT_WIDTH - 1);
+        assert_eq!(app.active_layout(), DiffLayout::Unified);
+        assert_eq!(app.get_current_diff_line_count(), 2);
+    }
+
+    #[test]
+    fn test_cycle_diff_mode_rediffs_history() {
+        let mut app = test_app();
+        let timestamp = chrono::Utc::now();
+        app.events.push_front(DisplayedEvent {
+            file_path: PathBuf::from("/test/file.rs"),
+            relative_path: "file.rs".to_string(),
+            timestamp,
+            diff: FileDiff {
+                context_lines: 5,
+                ..Default::default()
+            },
+            mode: DiffMode::All,
+            snapshot: SaveSnapshot::default(),
+        });
+
+        app.cycle_diff_mode();
+        let requests = app.take_diff_requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].mode, DiffMode::Unstaged);
+        assert_eq!(requests[0].context_lines, 5);
+
+        app.apply_diff_result(&requests[0], FileDiff::default());
+        assert_eq!(app.events[0].mode, DiffMode::Unstaged);
+    }
+
+    fn saved(name: &str, seconds: i64, added: usize) -> DisplayedEvent {
+        DisplayedEvent {
+            file_path: PathBuf::from(format!("/test/{name}")),
+            relative_path: name.to_string(),
+            timestamp: chrono::DateTime::from_timestamp(seconds, 0).unwrap(),
+            diff: FileDiff {
+                stats: crate::types::DiffStats {
+                    added_count: added,
+                    deleted_count: 0,
+                },
+                ..Default::default()
+            },
+            mode: DiffMode::All,
+            snapshot: SaveSnapshot::default(),
+        }
+    }
+
+    #[test]
+    fn test_file_entries_group_by_file() {
+        let mut app = test_app();
+        app.add_event(saved("b.rs", 1, 5));
+        app.add_event(saved("a.rs", 2, 1));
+        app.add_event(saved("b.rs", 3, 2));
+        app.add_event(saved("clean.rs", 4, 0));
+
+        let paths = |app: &App| -> Vec<String> {
+            app.file_entries()
+                .iter()
+                .map(|e| e.relative_path.clone())
+                .collect()
+        };
+        assert_eq!(paths(&app), ["b.rs", "a.rs"]);
+        assert_eq!(app.file_entries()[0].diff.stats.added_count, 2);
+
+        app.cycle_file_sort();
+        assert_eq!(app.file_sort, FileSort::Path);
+        assert_eq!(paths(&app), ["a.rs", "b.rs"]);
+
+        app.cycle_file_sort();
+        app.add_event(saved("a.rs", 5, 9));
+        assert_eq!(paths(&app), ["a.rs", "b.rs"]);
+    }
+
+    #[test]
+    fn test_file_entries_follow_diff_updates() {
+        let mut app = test_app();
+        app.add_event(saved("a.rs", 1, 1));
+        app.add_event(saved("b.rs", 2, 3));
+        app.toggle_history_view();
+        assert_eq!(app.entry_count(), 2);
+
+        // b.rs was committed: its file drops out of the view.
+        let request = DiffRequest::for_event(&app.events[0], DiffMode::All, 3);
+        app.apply_diff_result(&request, FileDiff::default());
+        assert_eq!(app.entry_count(), 1);
+        assert_eq!(app.get_current_event().unwrap().relative_path, "a.rs");
+
+        app.clear_history();
+        assert!(app.file_entries().is_empty());
+    }
+
+    #[test]
+    fn test_clean_save_drops_file_from_files_view() {
+        let mut app = test_app();
+        app.add_event(saved("a.rs", 1, 2));
+        app.toggle_history_view();
+        assert_eq!(app.file_entries().len(), 1);
+
+        // A save that undoes every change leaves no event behind.
+        let clean = saved("a.rs", 2, 0);
+        app.mark_clean(clean.file_path, clean.timestamp);
+        assert!(app.file_entries().is_empty());
+        assert_eq!(app.entry_count(), 0);
+        assert_eq!(app.events.len(), 1);
+
+        app.add_event(saved("a.rs", 3, 1));
+        assert_eq!(app.file_entries()[0].diff.stats.added_count, 1);
+    }
+
+    #[test]
+    fn test_saves_drop_marks_of_edited_hunks() {
+        let mut app = test_app();
+        let file_path = app.repo_root.join("a.rs");
+        let with_hunks = |seconds, changes: &[&str]| {
+            let mut event = saved("a.rs", seconds, changes.len());
+            event.file_path = file_path.clone();
+            event.diff.hunks = changes
+                .iter()
+                .map(|content| DiffHunk {
+                    old_start: 1,
+                    old_count: 0,
+                    new_start: 1,
+                    new_count: 1,
+                    lines: vec![DiffLine {
+                        old_line_number: None,
+                        new_line_number: Some(1),
+                        kind: DiffKind::Added,
+                        content: content.to_string(),
+                        emphasis: Vec::new(),
+                        missing_newline: false,
+                    }],
+                })
+                .collect();
+            event
+        };
+        let path = Path::new("a.rs");
+        let first = with_hunks(1, &["one", "two"]);
+        let keys = review_keys(&first.diff);
+        app.add_event(first);
+        app.toggle_current_reviewed();
+        assert_eq!(app.review_state.reviewed_count(), 2);
+
+        // "one" was edited away; the mark on "two" still matches.
+        app.add_event(with_hunks(2, &["two", "three"]));
+        assert!(!app.review_state.is_hunk_reviewed(path, &keys[0]));
+        assert!(app.review_state.is_hunk_reviewed(path, &keys[1]));
+
+        // An older save seeded late leaves the newer save's marks alone.
+        app.seed_event(with_hunks(0, &["zero"]));
+        assert!(app.review_state.is_hunk_reviewed(path, &keys[1]));
+
+        let clean = with_hunks(3, &[]);
+        app.mark_clean(clean.file_path, clean.timestamp);
+        assert_eq!(app.review_state.reviewed_count(), 0);
+    }
+
+    #[test]
+    fn test_latest_snapshot_follows_clean_saves() {
+        let mut app = test_app();
+        let path = Path::new("/test/a.rs");
+        let at = |seconds| saved("a.rs", seconds, 0).timestamp;
+
+        app.note_save(path, at(1), Some("dirty".into()));
+        assert_eq!(app.latest_snapshot(path).as_deref(), Some("dirty"));
+        app.note_save(path, at(2), Some("base".into()));
+        assert_eq!(app.latest_snapshot(path).as_deref(), Some("base"));
+        app.note_save(path, at(3), Some("dirty again".into()));
+        assert_eq!(app.latest_snapshot(path).as_deref(), Some("dirty again"));
+
+        // A save seeded late by the startup scan does not replace a newer one.
+        app.note_save(path, at(0), Some("seeded".into()));
+        assert_eq!(app.latest_snapshot(path).as_deref(), Some("dirty again"));
+    }
+
+    #[test]
+    fn test_toggle_history_view_keeps_file() {
+        let mut app = test_app();
+        app.add_event(saved("a.rs", 1, 1));
+        app.add_event(saved("b.rs", 2, 1));
+        app.add_event(saved("b.rs", 3, 1));
+        app.scroll_offset = 2;
+
+        app.toggle_history_view();
+        assert_eq!(app.history_view, HistoryView::Files);
+        assert_eq!(app.entry_count(), 2);
+        assert_eq!(app.get_current_event().unwrap().relative_path, "a.rs");
+
+        app.scroll_down();
+        app.toggle_history_view();
+        let event = app.get_current_event().unwrap();
+        assert_eq!(event.relative_path, "b.rs");
+        assert_eq!(event.timestamp.timestamp(), 3);
+    }
+
+    #[test]
+    fn test_comparison_shows_diff_since_previous_save() {
+        let mut app = test_app();
+        let mut first = saved("a.rs", 1, 3);
+        first.snapshot.content = Some("v1".into());
+        app.add_event(first);
+
+        let mut second = saved("a.rs", 2, 4);
+        second.snapshot = SaveSnapshot {
+            content: Some("v2".into()),
+            previous: Some("v1".into()),
+            diff: Some(FileDiff {
+                stats: crate::types::DiffStats {
+                    added_count: 1,
+                    deleted_count: 1,
+                },
+                ..Default::default()
+            }),
+        };
+        app.add_event(second);
+
+        let current = |app: &App| {
+            app.shown_diff(app.get_current_event().unwrap())
+                .stats
+                .added_count
+        };
+        assert_eq!(current(&app), 4);
+        app.toggle_comparison();
+        assert_eq!(current(&app), 1);
+
+        // The first save has nothing earlier and falls back to the base diff.
+        app.scroll_up();
+        assert_eq!(current(&app), 3);
+
+        let request = DiffRequest::for_event(app.get_current_event().unwrap(), DiffMode::All, 3);
+        assert_eq!(request.content.as_deref(), Some("v1"));
+        assert!(request.previous.is_none());
     }
 
     #[test]
@@ -2,12 +2,17 @@ use crate::types::{DiffKind, DiffLine};
 
 #[derive(Debug, Clone)]
 pub struct SideBySideLine {
+    /// Position in the input of the line shown on the left.
+    pub left_index: Option<usize>,
     pub left_num: Option<usize>,
     pub left_content: String,
     pub left_kind: Option<DiffKind>,
+    pub left_emphasis: Vec<(usize, usize)>,
+    pub right_index: Option<usize>,
     pub right_num: Option<usize>,
     pub right_content: String,
     pub right_kind: Option<DiffKind>,
+    pub right_emphasis: Vec<(usize, usize)>,
 }
 
 pub fn build_side_by_side_lines(diff_lines: &[DiffLine]) -> Vec<SideBySideLine> {
@@ -20,51 +25,63 @@ pub fn build_side_by_side_lines(diff_lines: &[DiffLine]) -> Vec<SideBySideLine>
         match line.kind {
             DiffKind::Context => {
                 result.push(SideBySideLine {
+                    left_index: Some(i),
                     left_num: line.old_line_number,
                     left_content: line.content.clone(),
                     left_kind: Some(DiffKind::Context),
+                    left_emphasis: Vec::new(),
+                    right_index: Some(i),
                     right_num: line.new_line_number,
                     right_content: line.content.clone(),
                     right_kind: Some(DiffKind::Context),
+                    right_emphasis: Vec::new(),
                 });
                 i += 1;
             }
             DiffKind::Deleted => {
                 let mut deletions = Vec::new();
                 while i < diff_lines.len() && diff_lines[i].kind == DiffKind::Deleted {
-                    deletions.push(&diff_lines[i]);
+                    deletions.push((i, &diff_lines[i]));
                     i += 1;
                 }
 
                 let mut additions = Vec::new();
                 while i < diff_lines.len() && diff_lines[i].kind == DiffKind::Added {
-                    additions.push(&diff_lines[i]);
+                    additions.push((i, &diff_lines[i]));
                     i += 1;
                 }
 
                 let max_len = deletions.len().max(additions.len());
                 for j in 0..max_len {
-                    let del = deletions.get(j);
-                    let add = additions.get(j);
+                    let (left_index, del) = deletions.get(j).copied().unzip();
+                    let (right_index, add) = additions.get(j).copied().unzip();
 
                     result.push(SideBySideLine {
+                        left_index,
                         left_num: del.and_then(|d| d.old_line_number),
                         left_content: del.map(|d| d.content.clone()).unwrap_or_default(),
                         left_kind: del.map(|_| DiffKind::Deleted),
+                        left_emphasis: del.map(|d| d.emphasis.clone()).unwrap_or_default(),
+                        right_index,
                         right_num: add.and_then(|a| a.new_line_number),
                         right_content: add.map(|a| a.content.clone()).unwrap_or_default(),
                         right_kind: add.map(|_| DiffKind::Added),
+                        right_emphasis: add.map(|a| a.emphasis.clone()).unwrap_or_default(),
                     });
                 }
             }
             DiffKind::Added => {
                 result.push(SideBySideLine {
+                    left_index: None,
                     left_num: None,
                     left_content: String::new(),
                     left_kind: None,
+                    left_emphasis: Vec::new(),
+                    right_index: Some(i),
                     right_num: line.new_line_number,
                     right_content: line.content.clone(),
                     right_kind: Some(DiffKind::Added),
+                    right_emphasis: line.emphasis.clone(),
                 });
                 i += 1;
             }
@@ -83,6 +100,33 @@ pub fn truncate_with_offset(s: &str, offset: usize, max_len: usize) -> String {
     chars[offset..end].iter().collect()
 }
 
+/// Like `truncate_with_offset`, but splits the visible window into runs
+/// flagged by whether they fall inside one of the `emphasis` char ranges.
+pub fn split_emphasis(
+    s: &str,
+    emphasis: &[(usize, usize)],
+    offset: usize,
+    max_len: usize,
+) -> Vec<(String, bool)> {
+    let chars: Vec<char> = s.chars().collect();
+    if offset >= chars.len() {
+        return Vec::new();
+    }
+    let end = (offset + max_len).min(chars.len());
+
+    let mut segments: Vec<(String, bool)> = Vec::new();
+    for (idx, ch) in chars.iter().enumerate().take(end).skip(offset) {
+        let emphasized = emphasis
+            .iter()
+            .any(|&(start, stop)| idx >= start && idx < stop);
+        match segments.last_mut() {
+            Some((text, flag)) if *flag == emphasized => text.push(*ch),
+            _ => segments.push((ch.to_string(), emphasized)),
+        }
+    }
+    segments
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -93,6 +137,8 @@ mod tests {
             new_line_number: Some(new),
             kind: DiffKind::Context,
             content: content.to_string(),
+            emphasis: Vec::new(),
+            missing_newline: false,
         }
     }
 
@@ -102,6 +148,8 @@ mod tests {
             new_line_number: Some(new),
             kind: DiffKind::Added,
             content: content.to_string(),
+            emphasis: Vec::new(),
+            missing_newline: false,
         }
     }
 
@@ -111,6 +159,8 @@ mod tests {
             new_line_number: None,
             kind: DiffKind::Deleted,
             content: content.to_string(),
+            emphasis: Vec::new(),
+            missing_newline: false,
         }
     }
 
@@ -184,6 +234,27 @@ mod tests {
         assert_eq!(result[1].right_content, "");
     }
 
+    #[test]
+    fn test_rows_keep_input_positions() {
+        let lines = vec![
+            make_context_line(1, 1, "same"),
+            make_deleted_line(2, "old1"),
+            make_deleted_line(3, "old2"),
+            make_added_line(2, "new1"),
+        ];
+
+        let result = build_side_by_side_lines(&lines);
+
+        let indices: Vec<_> = result
+            .iter()
+            .map(|row| (row.left_index, row.right_index))
+            .collect();
+        assert_eq!(
+            indices,
+            [(Some(0), Some(0)), (Some(1), Some(3)), (Some(2), None)]
+        );
+    }
+
     #[test]
     fn test_truncate_with_offset_basic() {
         let s = "Hello, World!";
@@ -202,4 +273,27 @@ mod tests {
         let s = "Hello";
         assert_eq!(truncate_with_offset(s, 3, 10), "lo");
     }
+
+    #[test]
+    fn test_split_emphasis_marks_ranges() {
+        let segments = split_emphasis("let x = 1;", &[(8, 9)], 0, 20);
+        assert_eq!(
+            segments,
+            vec![
+                ("let x = ".to_string(), false),
+                ("1".to_string(), true),
+                (";".to_string(), false),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_split_emphasis_respects_offset() {
+        let segments = split_emphasis("let x = 1;", &[(8, 9)], 6, 3);
+        assert_eq!(
+            segments,
+            vec![("= ".to_string(), false), ("1".to_string(), true)]
+        );
+        assert!(split_emphasis("short", &[], 10, 5).is_empty());
+    }
 }
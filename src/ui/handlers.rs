@@ -1,117 +1,190 @@
 use anyhow::Result;
-use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use crossterm::event::{KeyCode, KeyEvent};
 use std::process::Command;
 
 use crate::config::DiffViewerType;
 use crate::diff_viewer::resolve_viewer;
 
+use crate::types::IndexAction;
+
 use super::app::{App, AppState};
+use super::keymap::{Action, KeyResolution};
 
 pub fn handle_key_event(app: &mut App, key: KeyEvent) -> Result<()> {
     match app.state {
         AppState::ThemeSelector => handle_theme_selector_keys(app, key),
         AppState::HelpPanel => handle_help_panel_keys(app, key),
         AppState::SettingsEditor => handle_settings_editor_keys(app, key),
+        AppState::CommitEditor => handle_commit_editor_keys(app, key),
+        AppState::NoteEditor => handle_note_editor_keys(app, key),
         _ => handle_main_keys(app, key),
     }
 }
 
 fn handle_main_keys(app: &mut App, key: KeyEvent) -> Result<()> {
-    match key.code {
-        KeyCode::Char('q') | KeyCode::Esc => {
+    // Esc leaves visual mode before it can quit.
+    if key.code == KeyCode::Esc && app.is_selecting() {
+        app.cancel_line_selection();
+        return Ok(());
+    }
+    match app.keymap.resolve(&key) {
+        KeyResolution::Action(action) => run_action(app, action),
+        KeyResolution::Pending | KeyResolution::Unbound => Ok(()),
+    }
+}
+
+fn run_action(app: &mut App, action: Action) -> Result<()> {
+    if app.is_replay() && needs_repository(action) {
+        app.set_status("Not available while replaying a session".to_string(), true);
+        return Ok(());
+    }
+    match action {
+        Action::Quit => {
             app.should_quit = true;
         }
-        KeyCode::Char(' ') => {
+        Action::TogglePause => {
             app.toggle_pause();
         }
-        KeyCode::Up | KeyCode::Char('k') => {
-            let max = app.get_current_diff_line_count();
+        Action::ScrollUp if app.is_selecting() => {
+            app.move_selection(false);
+        }
+        Action::ScrollDown if app.is_selecting() => {
+            app.move_selection(true);
+        }
+        Action::ScrollUp => {
             app.diff_scroll_up(1);
-            let _ = max;
         }
-        KeyCode::Down | KeyCode::Char('j') => {
+        Action::ScrollDown => {
             let max = app.get_current_diff_line_count();
             app.diff_scroll_down(1, max);
         }
-        KeyCode::PageUp => {
+        Action::PageUp => {
             app.diff_scroll_up(10);
         }
-        KeyCode::PageDown => {
+        Action::PageDown => {
             let max = app.get_current_diff_line_count();
             app.diff_scroll_down(10, max);
         }
-        KeyCode::Left | KeyCode::Char('h') => {
+        Action::ScrollLeft => {
             app.diff_scroll_left();
         }
-        KeyCode::Right | KeyCode::Char('l') => {
+        Action::ScrollRight => {
             app.diff_scroll_right();
         }
-        KeyCode::Char('p') => {
+        Action::PrevEvent => {
             app.scroll_up();
         }
-        KeyCode::Char('n') => {
+        Action::NextEvent => {
             app.scroll_down();
         }
-        KeyCode::Char(']') => {
+        Action::ToggleFilesView => {
+            app.toggle_history_view();
+        }
+        Action::CycleFileSort => {
+            app.cycle_file_sort();
+        }
+        Action::NextHunk => {
             app.next_hunk();
         }
-        KeyCode::Char('[') => {
+        Action::PrevHunk => {
             app.prev_hunk();
         }
-        KeyCode::Char('z') => {
+        Action::ToggleHunk => {
             app.toggle_current_hunk_collapsed();
         }
-        KeyCode::Char('Z') => {
+        Action::ToggleContext => {
             app.toggle_context_collapsed();
         }
-        KeyCode::Char('c') => {
+        Action::GrowContext => {
+            app.grow_context();
+        }
+        Action::ShrinkContext => {
+            app.shrink_context();
+        }
+        Action::ClearHistory => {
             app.clear_history();
         }
-        KeyCode::Char('t') => {
+        Action::ThemeSelector => {
             app.open_theme_selector();
         }
-        KeyCode::Char('m') => {
+        Action::CycleDiffMode => {
             app.cycle_diff_mode();
         }
-        KeyCode::Char('r') => {
+        Action::ToggleLayout => {
+            app.toggle_diff_layout();
+        }
+        Action::ToggleComparison => {
+            app.toggle_comparison();
+        }
+        Action::SelectLines => {
+            app.toggle_line_selection();
+        }
+        Action::StageLines => {
+            app.request_index_action(IndexAction::Stage);
+        }
+        Action::UnstageLines => {
+            app.request_index_action(IndexAction::Unstage);
+        }
+        Action::Commit => {
+            app.open_commit_editor();
+        }
+        Action::ToggleReviewed => {
             app.toggle_current_reviewed();
         }
-        KeyCode::Char('R') => {
+        Action::ToggleHunkReviewed => {
+            app.toggle_focused_hunk_reviewed();
+        }
+        Action::AddNote => {
+            app.open_note_editor();
+        }
+        Action::ClearReviewed => {
             app.clear_all_reviewed();
         }
-        KeyCode::Char('d') => {
+        Action::OpenDiffViewer => {
             open_in_diff_viewer(app)?;
         }
-        KeyCode::Char('?') => {
+        Action::Help => {
             app.open_help();
         }
-        KeyCode::Char('s') => {
+        Action::Settings => {
             app.open_settings_editor();
         }
-        KeyCode::Enter => {
+        Action::OpenEditor => {
             open_in_editor(app)?;
         }
-        _ => {}
+        Action::SaveSettings | Action::SubmitCommit | Action::ToggleAmend | Action::SaveNote => {}
     }
     Ok(())
 }
 
+/// Actions that recompute diffs or change the repository, which a replayed
+/// session has no live repository for.
+fn needs_repository(action: Action) -> bool {
+    matches!(
+        action,
+        Action::CycleDiffMode
+            | Action::GrowContext
+            | Action::ShrinkContext
+            | Action::StageLines
+            | Action::UnstageLines
+            | Action::Commit
+    )
+}
+
 fn handle_theme_selector_keys(app: &mut App, key: KeyEvent) -> Result<()> {
-    match key.code {
-        KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('t') => {
-            app.close_overlay();
-        }
-        KeyCode::Up | KeyCode::Char('k') => {
-            app.theme_selector_up();
-        }
-        KeyCode::Down | KeyCode::Char('j') => {
-            app.theme_selector_down();
-        }
-        KeyCode::Enter => {
-            app.select_theme(app.selected_theme_index);
-            app.close_overlay();
-        }
-        _ => {}
+    let keymap = &app.keymap;
+    if key.code == KeyCode::Esc
+        || keymap.matches(Action::Quit, &key)
+        || keymap.matches(Action::ThemeSelector, &key)
+    {
+        app.close_overlay();
+    } else if key.code == KeyCode::Up || keymap.matches(Action::ScrollUp, &key) {
+        app.theme_selector_up();
+    } else if key.code == KeyCode::Down || keymap.matches(Action::ScrollDown, &key) {
+        app.theme_selector_down();
+    } else if key.code == KeyCode::Enter {
+        app.select_theme(app.selected_theme_index);
+        app.close_overlay();
     }
     Ok(())
 }
@@ -122,120 +195,183 @@ fn handle_help_panel_keys(app: &mut App, _key: KeyEvent) -> Result<()> {
 }
 
 fn handle_settings_editor_keys(app: &mut App, key: KeyEvent) -> Result<()> {
-    let state = &mut app.settings_editor;
-    let lines: Vec<&str> = state.content.lines().collect();
-    let line_count = lines.len().max(1);
-
-    match key.code {
-        KeyCode::Esc => {
+    if app.keymap.matches(Action::SaveSettings, &key) {
+        if app.save_settings() {
             app.close_overlay();
         }
-        KeyCode::Char('s') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-            if app.save_settings() {
-                app.close_overlay();
-            }
-        }
-        KeyCode::Up => {
-            if state.cursor_line > 0 {
-                state.cursor_line -= 1;
-                let new_line_len = lines.get(state.cursor_line).map(|l| l.len()).unwrap_or(0);
-                state.cursor_col = state.cursor_col.min(new_line_len);
-            }
+        return Ok(());
+    }
+    if key.code == KeyCode::Esc {
+        app.close_overlay();
+        return Ok(());
+    }
+
+    let state = &mut app.settings_editor;
+    if edit_text(
+        &mut state.content,
+        &mut state.cursor_line,
+        &mut state.cursor_col,
+        &key,
+    ) {
+        state.error_message = None;
+    }
+    Ok(())
+}
+
+fn handle_note_editor_keys(app: &mut App, key: KeyEvent) -> Result<()> {
+    if app.keymap.matches(Action::SaveNote, &key) {
+        app.save_note();
+        return Ok(());
+    }
+    if key.code == KeyCode::Esc {
+        app.close_overlay();
+        return Ok(());
+    }
+
+    let state = &mut app.note_editor;
+    edit_text(
+        &mut state.text,
+        &mut state.cursor_line,
+        &mut state.cursor_col,
+        &key,
+    );
+    Ok(())
+}
+
+fn handle_commit_editor_keys(app: &mut App, key: KeyEvent) -> Result<()> {
+    if app.keymap.matches(Action::SubmitCommit, &key) {
+        app.submit_commit();
+        return Ok(());
+    }
+    if app.keymap.matches(Action::ToggleAmend, &key) {
+        app.toggle_amend();
+        return Ok(());
+    }
+    if key.code == KeyCode::Esc {
+        app.close_overlay();
+        return Ok(());
+    }
+
+    let state = &mut app.commit_editor;
+    if edit_text(
+        &mut state.message,
+        &mut state.cursor_line,
+        &mut state.cursor_col,
+        &key,
+    ) {
+        state.error_message = None;
+    }
+    Ok(())
+}
+
+/// Applies a cursor or editing key to `content`, with the cursor at `line`
+/// and `col`, counted in chars. Returns whether the key edits the text.
+fn edit_text(content: &mut String, line: &mut usize, col: &mut usize, key: &KeyEvent) -> bool {
+    let lines: Vec<&str> = content.split('\n').collect();
+    let line_count = lines.len();
+    let line_len = |i: usize| lines.get(i).map(|l| l.chars().count()).unwrap_or(0);
+
+    let edits = match key.code {
+        KeyCode::Up if *line > 0 => {
+            *line -= 1;
+            *col = (*col).min(line_len(*line));
+            false
         }
-        KeyCode::Down => {
-            if state.cursor_line < line_count.saturating_sub(1) {
-                state.cursor_line += 1;
-                let new_line_len = lines.get(state.cursor_line).map(|l| l.len()).unwrap_or(0);
-                state.cursor_col = state.cursor_col.min(new_line_len);
-            }
+        KeyCode::Down if *line + 1 < line_count => {
+            *line += 1;
+            *col = (*col).min(line_len(*line));
+            false
         }
         KeyCode::Left => {
-            if state.cursor_col > 0 {
-                state.cursor_col -= 1;
-            } else if state.cursor_line > 0 {
-                state.cursor_line -= 1;
-                state.cursor_col = lines.get(state.cursor_line).map(|l| l.len()).unwrap_or(0);
+            if *col > 0 {
+                *col -= 1;
+            } else if *line > 0 {
+                *line -= 1;
+                *col = line_len(*line);
             }
+            false
         }
         KeyCode::Right => {
-            let current_line_len = lines.get(state.cursor_line).map(|l| l.len()).unwrap_or(0);
-            if state.cursor_col < current_line_len {
-                state.cursor_col += 1;
-            } else if state.cursor_line < line_count.saturating_sub(1) {
-                state.cursor_line += 1;
-                state.cursor_col = 0;
+            if *col < line_len(*line) {
+                *col += 1;
+            } else if *line + 1 < line_count {
+                *line += 1;
+                *col = 0;
             }
+            false
         }
         KeyCode::Home => {
-            state.cursor_col = 0;
+            *col = 0;
+            false
         }
         KeyCode::End => {
-            state.cursor_col = lines.get(state.cursor_line).map(|l| l.len()).unwrap_or(0);
+            *col = line_len(*line);
+            false
         }
         KeyCode::Enter => {
-            let pos = get_cursor_position(&state.content, state.cursor_line, state.cursor_col);
-            state.content.insert(pos, '\n');
-            state.cursor_line += 1;
-            state.cursor_col = 0;
-            state.error_message = None;
+            let pos = get_cursor_position(content, *line, *col);
+            content.insert(pos, '\n');
+            *line += 1;
+            *col = 0;
+            true
         }
         KeyCode::Backspace => {
-            if state.cursor_col > 0 {
-                let pos = get_cursor_position(&state.content, state.cursor_line, state.cursor_col);
-                if pos > 0 {
-                    state.content.remove(pos - 1);
-                    state.cursor_col -= 1;
+            if *col > 0 {
+                let pos = get_cursor_position(content, *line, *col);
+                if let Some(c) = content[..pos].chars().next_back() {
+                    content.remove(pos - c.len_utf8());
+                    *col -= 1;
                 }
-            } else if state.cursor_line > 0 {
-                let prev_line_len = lines
-                    .get(state.cursor_line - 1)
-                    .map(|l| l.len())
-                    .unwrap_or(0);
-                let pos = get_cursor_position(&state.content, state.cursor_line, 0);
+            } else if *line > 0 {
+                let prev_line_len = line_len(*line - 1);
+                let pos = get_cursor_position(content, *line, 0);
                 if pos > 0 {
-                    state.content.remove(pos - 1);
-                    state.cursor_line -= 1;
-                    state.cursor_col = prev_line_len;
+                    content.remove(pos - 1);
+                    *line -= 1;
+                    *col = prev_line_len;
                 }
             }
-            state.error_message = None;
+            true
         }
         KeyCode::Delete => {
-            let pos = get_cursor_position(&state.content, state.cursor_line, state.cursor_col);
-            if pos < state.content.len() {
-                state.content.remove(pos);
+            let pos = get_cursor_position(content, *line, *col);
+            if pos < content.len() {
+                content.remove(pos);
             }
-            state.error_message = None;
+            true
         }
         KeyCode::Char(c) => {
-            let pos = get_cursor_position(&state.content, state.cursor_line, state.cursor_col);
-            state.content.insert(pos, c);
-            state.cursor_col += 1;
-            state.error_message = None;
+            let pos = get_cursor_position(content, *line, *col);
+            content.insert(pos, c);
+            *col += 1;
+            true
         }
         KeyCode::Tab => {
-            let pos = get_cursor_position(&state.content, state.cursor_line, state.cursor_col);
-            state.content.insert_str(pos, "  ");
-            state.cursor_col += 2;
-            state.error_message = None;
+            let pos = get_cursor_position(content, *line, *col);
+            content.insert_str(pos, "  ");
+            *col += 2;
+            true
         }
-        _ => {}
-    }
+        _ => false,
+    };
 
-    let new_lines: Vec<&str> = app.settings_editor.content.lines().collect();
-    let new_line_count = new_lines.len();
-    if app.settings_editor.cursor_line >= new_line_count && new_line_count > 0 {
-        app.settings_editor.cursor_line = new_line_count - 1;
+    let line_count = content.split('\n').count();
+    if *line >= line_count {
+        *line = line_count - 1;
     }
-
-    Ok(())
+    edits
 }
 
+/// Byte offset in `content` of char `col` on line `line`, clamped to the
+/// end of the line or of the content.
 pub fn get_cursor_position(content: &str, line: usize, col: usize) -> usize {
     let mut pos = 0;
-    for (i, l) in content.lines().enumerate() {
+    for (i, l) in content.split('\n').enumerate() {
         if i == line {
-            return pos + col.min(l.len());
+            return pos
+                + l.char_indices()
+                    .nth(col)
+                    .map_or(l.len(), |(offset, _)| offset);
         }
         pos += l.len() + 1;
     }
@@ -0,0 +1,839 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use thiserror::Error;
+
+use crate::config::KeybindingConfig;
+
+/// Everything a key can be bound to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    Quit,
+    TogglePause,
+    ScrollUp,
+    ScrollDown,
+    PageUp,
+    PageDown,
+    ScrollLeft,
+    ScrollRight,
+    PrevEvent,
+    NextEvent,
+    ToggleFilesView,
+    CycleFileSort,
+    NextHunk,
+    PrevHunk,
+    ToggleHunk,
+    ToggleContext,
+    GrowContext,
+    ShrinkContext,
+    ToggleReviewed,
+    ToggleHunkReviewed,
+    ClearReviewed,
+    AddNote,
+    SaveNote,
+    CycleDiffMode,
+    ToggleLayout,
+    ToggleComparison,
+    SelectLines,
+    StageLines,
+    UnstageLines,
+    Commit,
+    SubmitCommit,
+    ToggleAmend,
+    OpenDiffViewer,
+    OpenEditor,
+    ThemeSelector,
+    Settings,
+    SaveSettings,
+    ClearHistory,
+    Help,
+}
+
+/// Where a binding is active. Bindings only conflict within the same scope.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scope {
+    Main,
+    Editor,
+    Commit,
+    Note,
+}
+
+/// Section an action is listed under in the help panel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ActionGroup {
+    Navigation,
+    Hunks,
+    Staging,
+    Actions,
+}
+
+impl ActionGroup {
+    pub const ALL: [ActionGroup; 4] = [Self::Navigation, Self::Hunks, Self::Staging, Self::Actions];
+
+    pub fn title(self) -> &'static str {
+        match self {
+            Self::Navigation => "Navigation",
+            Self::Hunks => "Hunk Navigation",
+            Self::Staging => "Staging",
+            Self::Actions => "Actions",
+        }
+    }
+}
+
+impl Action {
+    pub const ALL: [Action; 39] = [
+        Self::ScrollUp,
+        Self::ScrollDown,
+        Self::PageUp,
+        Self::PageDown,
+        Self::ScrollLeft,
+        Self::ScrollRight,
+        Self::PrevEvent,
+        Self::NextEvent,
+        Self::ToggleFilesView,
+        Self::CycleFileSort,
+        Self::NextHunk,
+        Self::PrevHunk,
+        Self::ToggleHunk,
+        Self::ToggleContext,
+        Self::GrowContext,
+        Self::ShrinkContext,
+        Self::TogglePause,
+        Self::OpenEditor,
+        Self::ClearHistory,
+        Self::OpenDiffViewer,
+        Self::ThemeSelector,
+        Self::CycleDiffMode,
+        Self::ToggleLayout,
+        Self::ToggleComparison,
+        Self::ToggleReviewed,
+        Self::ClearReviewed,
+        Self::Settings,
+        Self::SaveSettings,
+        Self::Help,
+        Self::Quit,
+        // Earlier actions win conflicts, so configs that already bind these
+        // keys to something else keep working.
+        Self::SelectLines,
+        Self::StageLines,
+        Self::UnstageLines,
+        Self::Commit,
+        Self::SubmitCommit,
+        Self::ToggleAmend,
+        Self::ToggleHunkReviewed,
+        Self::AddNote,
+        Self::SaveNote,
+    ];
+
+    /// Name of the `keybindings` field in the config file.
+    pub fn config_key(self) -> &'static str {
+        match self {
+            Self::Quit => "quit",
+            Self::TogglePause => "pause_resume",
+            Self::ScrollUp => "scroll_up",
+            Self::ScrollDown => "scroll_down",
+            Self::PageUp => "page_up",
+            Self::PageDown => "page_down",
+            Self::ScrollLeft => "scroll_left",
+            Self::ScrollRight => "scroll_right",
+            Self::PrevEvent => "prev_event",
+            Self::NextEvent => "next_event",
+            Self::ToggleFilesView => "toggle_files_view",
+            Self::CycleFileSort => "cycle_file_sort",
+            Self::NextHunk => "next_hunk",
+            Self::PrevHunk => "prev_hunk",
+            Self::ToggleHunk => "toggle_hunk",
+            Self::ToggleContext => "toggle_context",
+            Self::GrowContext => "grow_context",
+            Self::ShrinkContext => "shrink_context",
+            Self::ToggleReviewed => "toggle_reviewed",
+            Self::ToggleHunkReviewed => "toggle_hunk_reviewed",
+            Self::AddNote => "add_note",
+            Self::SaveNote => "save_note",
+            Self::ClearReviewed => "clear_reviewed",
+            Self::CycleDiffMode => "cycle_diff_mode",
+            Self::ToggleLayout => "toggle_layout",
+            Self::ToggleComparison => "toggle_comparison",
+            Self::SelectLines => "select_lines",
+            Self::StageLines => "stage_lines",
+            Self::UnstageLines => "unstage_lines",
+            Self::Commit => "commit",
+            Self::SubmitCommit => "submit_commit",
+            Self::ToggleAmend => "toggle_amend",
+            Self::OpenDiffViewer => "open_diff_viewer",
+            Self::OpenEditor => "open_editor",
+            Self::ThemeSelector => "theme_selector",
+            Self::Settings => "settings",
+            Self::SaveSettings => "save_settings",
+            Self::ClearHistory => "clear_history",
+            Self::Help => "help",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Self::Quit => "Quit gwatch",
+            Self::TogglePause => "Pause/Resume live streaming",
+            Self::ScrollUp => "Scroll up within current diff",
+            Self::ScrollDown => "Scroll down within current diff",
+            Self::PageUp => "Fast scroll up (10 lines)",
+            Self::PageDown => "Fast scroll down (10 lines)",
+            Self::ScrollLeft => "Pan diff left",
+            Self::ScrollRight => "Pan diff right",
+            Self::PrevEvent => "Previous (older) event",
+            Self::NextEvent => "Next (newer) event",
+            Self::ToggleFilesView => "Toggle file view (latest diff per file)",
+            Self::CycleFileSort => "Cycle file order (recent/path/size)",
+            Self::NextHunk => "Jump to next hunk",
+            Self::PrevHunk => "Jump to previous hunk",
+            Self::ToggleHunk => "Toggle hunk collapsed",
+            Self::ToggleContext => "Toggle hide context lines",
+            Self::GrowContext => "Show more context around changes",
+            Self::ShrinkContext => "Show less context around changes",
+            Self::ToggleReviewed => "Toggle reviewed status for current file",
+            Self::ToggleHunkReviewed => "Toggle reviewed status for the focused hunk",
+            Self::AddNote => "Write a note on the focused hunk, or the cursor line in visual mode",
+            Self::SaveNote => "Save in the note editor",
+            Self::ClearReviewed => "Clear all reviewed markers",
+            Self::CycleDiffMode => "Cycle diff mode (All/Unstaged/Staged)",
+            Self::ToggleLayout => "Toggle split/unified diff layout",
+            Self::ToggleComparison => "Compare with the base or the previous save",
+            Self::SelectLines => "Select lines of the hunk to stage (visual mode)",
+            Self::StageLines => "Stage the selected lines",
+            Self::UnstageLines => "Unstage the selected lines",
+            Self::Commit => "Commit the staged changes",
+            Self::SubmitCommit => "Commit in the commit editor",
+            Self::ToggleAmend => "Toggle amending the last commit in the commit editor",
+            Self::OpenDiffViewer => "Open diff in external viewer (delta/difftastic/pager)",
+            Self::OpenEditor => "Open current file in $EDITOR",
+            Self::ThemeSelector => "Open theme selector",
+            Self::Settings => "Open settings editor",
+            Self::SaveSettings => "Save in settings editor",
+            Self::ClearHistory => "Clear event history",
+            Self::Help => "Show this help panel",
+        }
+    }
+
+    pub fn group(self) -> ActionGroup {
+        match self {
+            Self::ScrollUp
+            | Self::ScrollDown
+            | Self::PageUp
+            | Self::PageDown
+            | Self::ScrollLeft
+            | Self::ScrollRight
+            | Self::PrevEvent
+            | Self::NextEvent
+            | Self::ToggleFilesView
+            | Self::CycleFileSort => ActionGroup::Navigation,
+            Self::NextHunk
+            | Self::PrevHunk
+            | Self::ToggleHunk
+            | Self::ToggleContext
+            | Self::GrowContext
+            | Self::ShrinkContext
+            | Self::ToggleHunkReviewed
+            | Self::AddNote
+            | Self::SaveNote => ActionGroup::Hunks,
+            Self::SelectLines
+            | Self::StageLines
+            | Self::UnstageLines
+            | Self::Commit
+            | Self::SubmitCommit
+            | Self::ToggleAmend => ActionGroup::Staging,
+            _ => ActionGroup::Actions,
+        }
+    }
+
+    pub fn scope(self) -> Scope {
+        match self {
+            Self::SaveSettings => Scope::Editor,
+            Self::SubmitCommit | Self::ToggleAmend => Scope::Commit,
+            Self::SaveNote => Scope::Note,
+            _ => Scope::Main,
+        }
+    }
+
+    fn binding(self, config: &KeybindingConfig) -> &str {
+        match self {
+            Self::Quit => &config.quit,
+            Self::TogglePause => &config.pause_resume,
+            Self::ScrollUp => &config.scroll_up,
+            Self::ScrollDown => &config.scroll_down,
+            Self::PageUp => &config.page_up,
+            Self::PageDown => &config.page_down,
+            Self::ScrollLeft => &config.scroll_left,
+            Self::ScrollRight => &config.scroll_right,
+            Self::PrevEvent => &config.prev_event,
+            Self::NextEvent => &config.next_event,
+            Self::ToggleFilesView => &config.toggle_files_view,
+            Self::CycleFileSort => &config.cycle_file_sort,
+            Self::NextHunk => &config.next_hunk,
+            Self::PrevHunk => &config.prev_hunk,
+            Self::ToggleHunk => &config.toggle_hunk,
+            Self::ToggleContext => &config.toggle_context,
+            Self::GrowContext => &config.grow_context,
+            Self::ShrinkContext => &config.shrink_context,
+            Self::ToggleReviewed => &config.toggle_reviewed,
+            Self::ToggleHunkReviewed => &config.toggle_hunk_reviewed,
+            Self::AddNote => &config.add_note,
+            Self::SaveNote => &config.save_note,
+            Self::ClearReviewed => &config.clear_reviewed,
+            Self::CycleDiffMode => &config.cycle_diff_mode,
+            Self::ToggleLayout => &config.toggle_layout,
+            Self::ToggleComparison => &config.toggle_comparison,
+            Self::SelectLines => &config.select_lines,
+            Self::StageLines => &config.stage_lines,
+            Self::UnstageLines => &config.unstage_lines,
+            Self::Commit => &config.commit,
+            Self::SubmitCommit => &config.submit_commit,
+            Self::ToggleAmend => &config.toggle_amend,
+            Self::OpenDiffViewer => &config.open_diff_viewer,
+            Self::OpenEditor => &config.open_editor,
+            Self::ThemeSelector => &config.theme_selector,
+            Self::Settings => &config.settings,
+            Self::SaveSettings => &config.save_settings,
+            Self::ClearHistory => &config.clear_history,
+            Self::Help => &config.help,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum KeymapError {
+    #[error("empty key binding")]
+    Empty,
+    #[error("unknown key '{0}'")]
+    UnknownKey(String),
+    #[error("unknown modifier '{0}'")]
+    UnknownModifier(String),
+    #[error("'{0}' would be typed into the editor instead")]
+    TypesText(String),
+    #[error("'{0}' is a key sequence; only single keys work in overlays")]
+    Sequence(String),
+}
+
+/// A single key press with its modifiers, normalized so that shifted
+/// characters compare equal regardless of how the terminal reports them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KeyChord {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl KeyChord {
+    pub fn new(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        let mut code = code;
+        let mut modifiers = modifiers;
+        match code {
+            KeyCode::Char(c) => {
+                if modifiers.contains(KeyModifiers::SHIFT) {
+                    code = KeyCode::Char(c.to_ascii_uppercase());
+                }
+                modifiers.remove(KeyModifiers::SHIFT);
+            }
+            KeyCode::BackTab => modifiers.remove(KeyModifiers::SHIFT),
+            _ => {}
+        }
+        Self { code, modifiers }
+    }
+
+    pub fn from_event(key: &KeyEvent) -> Self {
+        Self::new(key.code, key.modifiers)
+    }
+
+    /// Parse a chord such as `"q"`, `"ctrl+s"`, `"shift+r"` or `"pagedown"`.
+    pub fn parse(s: &str) -> Result<Self, KeymapError> {
+        let mut rest = s.trim();
+        if rest.is_empty() {
+            return Err(KeymapError::Empty);
+        }
+
+        let mut modifiers = KeyModifiers::NONE;
+        while rest.chars().count() > 1 {
+            let Some((prefix, tail)) = rest.split_once('+') else {
+                break;
+            };
+            if tail.is_empty() {
+                break;
+            }
+            modifiers |= match prefix.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => KeyModifiers::CONTROL,
+                "alt" | "meta" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                _ => return Err(KeymapError::UnknownModifier(prefix.to_string())),
+            };
+            rest = tail;
+        }
+
+        let code = parse_key_code(rest)?;
+        Ok(Self::new(code, modifiers))
+    }
+
+    pub fn label(&self) -> String {
+        let mut label = String::new();
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            label.push_str("Ctrl+");
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            label.push_str("Alt+");
+        }
+        if self.modifiers.contains(KeyModifiers::SHIFT) {
+            label.push_str("Shift+");
+        }
+        let key = match self.code {
+            KeyCode::Up => "↑".to_string(),
+            KeyCode::Down => "↓".to_string(),
+            KeyCode::Left => "←".to_string(),
+            KeyCode::Right => "→".to_string(),
+            KeyCode::PageUp => "PgUp".to_string(),
+            KeyCode::PageDown => "PgDn".to_string(),
+            KeyCode::Home => "Home".to_string(),
+            KeyCode::End => "End".to_string(),
+            KeyCode::Enter => "Enter".to_string(),
+            KeyCode::Esc => "Esc".to_string(),
+            KeyCode::Tab => "Tab".to_string(),
+            KeyCode::BackTab => "Shift+Tab".to_string(),
+            KeyCode::Backspace => "Backspace".to_string(),
+            KeyCode::Delete => "Del".to_string(),
+            KeyCode::Insert => "Ins".to_string(),
+            KeyCode::F(n) => format!("F{n}"),
+            KeyCode::Char(' ') => "Space".to_string(),
+            KeyCode::Char(c) if self.modifiers.is_empty() => c.to_string(),
+            KeyCode::Char(c) => c.to_ascii_uppercase().to_string(),
+            _ => "?".to_string(),
+        };
+        label.push_str(&key);
+        label
+    }
+}
+
+fn parse_key_code(name: &str) -> Result<KeyCode, KeymapError> {
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return Ok(KeyCode::Char(c));
+    }
+
+    let code = match name.to_ascii_lowercase().as_str() {
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "pageup" | "pgup" => KeyCode::PageUp,
+        "pagedown" | "pgdn" | "pgdown" => KeyCode::PageDown,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "enter" | "return" => KeyCode::Enter,
+        "esc" | "escape" => KeyCode::Esc,
+        "tab" => KeyCode::Tab,
+        "backtab" => KeyCode::BackTab,
+        "backspace" => KeyCode::Backspace,
+        "delete" | "del" => KeyCode::Delete,
+        "insert" | "ins" => KeyCode::Insert,
+        "space" => KeyCode::Char(' '),
+        "comma" => KeyCode::Char(','),
+        "plus" => KeyCode::Char('+'),
+        "minus" => KeyCode::Char('-'),
+        other => match other.strip_prefix('f').and_then(|n| n.parse::<u8>().ok()) {
+            Some(n) if (1..=24).contains(&n) => KeyCode::F(n),
+            _ => return Err(KeymapError::UnknownKey(name.to_string())),
+        },
+    };
+    Ok(code)
+}
+
+/// A sequence of chords that must be pressed in order, e.g. `g g`.
+pub type KeySequence = Vec<KeyChord>;
+
+/// Parse a binding string into its alternatives.
+pub fn parse_binding(s: &str) -> Result<Vec<KeySequence>, KeymapError> {
+    let mut alternatives = Vec::new();
+    for alternative in s.split(',') {
+        let alternative = alternative.trim();
+        if alternative.is_empty() {
+            continue;
+        }
+        let sequence = alternative
+            .split_whitespace()
+            .map(KeyChord::parse)
+            .collect::<Result<KeySequence, _>>()?;
+        alternatives.push(sequence);
+    }
+    if alternatives.is_empty() {
+        return Err(KeymapError::Empty);
+    }
+    Ok(alternatives)
+}
+
+/// Overlays with a text editor insert plain characters, so their actions
+/// need a key that cannot be typed. They also match single key presses
+/// only (see [`Keymap::matches`]), so a sequence would never fire.
+fn check_scope(
+    action: Action,
+    alternatives: Vec<KeySequence>,
+) -> Result<Vec<KeySequence>, KeymapError> {
+    if action.scope() == Scope::Main {
+        return Ok(alternatives);
+    }
+    for sequence in &alternatives {
+        if sequence.len() > 1 {
+            return Err(KeymapError::Sequence(sequence_label(sequence)));
+        }
+        if let Some(chord) = sequence.first() {
+            if matches!(chord.code, KeyCode::Char(_)) && chord.modifiers.is_empty() {
+                return Err(KeymapError::TypesText(chord.label()));
+            }
+        }
+    }
+    Ok(alternatives)
+}
+
+pub fn sequence_label(sequence: &[KeyChord]) -> String {
+    sequence
+        .iter()
+        .map(KeyChord::label)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Result of feeding a key press to the keymap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyResolution {
+    Action(Action),
+    /// The key is a prefix of a longer binding; waiting for more input.
+    Pending,
+    Unbound,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Keymap {
+    bindings: Vec<(KeySequence, Action)>,
+    pending: Vec<KeyChord>,
+    /// Parse errors and conflicts found while building the keymap.
+    pub issues: Vec<String>,
+}
+
+impl Keymap {
+    pub fn from_config(config: &KeybindingConfig) -> Self {
+        let mut keymap = Self::default();
+
+        for action in Action::ALL {
+            let binding = action.binding(config);
+            let alternatives = match parse_binding(binding)
+                .and_then(|alternatives| check_scope(action, alternatives))
+            {
+                Ok(alternatives) => alternatives,
+                Err(e) => {
+                    keymap.issues.push(format!(
+                        "{}: {e} in \"{binding}\", using default",
+                        action.config_key()
+                    ));
+                    parse_binding(action.binding(&KeybindingConfig::default())).unwrap_or_default()
+                }
+            };
+
+            for sequence in alternatives {
+                if let Some(conflict) = keymap.find_conflict(&sequence, action) {
+                    keymap.issues.push(format!(
+                        "{}: \"{}\" conflicts with {}",
+                        action.config_key(),
+                        sequence_label(&sequence),
+                        conflict.config_key()
+                    ));
+                    continue;
+                }
+                keymap.bindings.push((sequence, action));
+            }
+        }
+
+        keymap
+    }
+
+    fn find_conflict(&self, sequence: &[KeyChord], action: Action) -> Option<Action> {
+        self.bindings
+            .iter()
+            .filter(|(_, existing)| existing.scope() == action.scope())
+            .find(|(existing, _)| {
+                let len = existing.len().min(sequence.len());
+                existing[..len] == sequence[..len]
+            })
+            .map(|(_, existing)| *existing)
+    }
+
+    /// Feed a key press, tracking multi-key sequences across calls.
+    pub fn resolve(&mut self, key: &KeyEvent) -> KeyResolution {
+        let chord = KeyChord::from_event(key);
+
+        if !self.pending.is_empty() {
+            let mut candidate = self.pending.clone();
+            candidate.push(chord);
+            self.pending.clear();
+            match self.lookup(&candidate) {
+                KeyResolution::Pending => {
+                    self.pending = candidate;
+                    return KeyResolution::Pending;
+                }
+                KeyResolution::Action(action) => return KeyResolution::Action(action),
+                KeyResolution::Unbound => {}
+            }
+        }
+
+        let resolution = self.lookup(&[chord]);
+        if resolution == KeyResolution::Pending {
+            self.pending = vec![chord];
+        }
+        resolution
+    }
+
+    fn lookup(&self, input: &[KeyChord]) -> KeyResolution {
+        let mut pending = false;
+        for (sequence, action) in &self.bindings {
+            if action.scope() != Scope::Main || !sequence.starts_with(input) {
+                continue;
+            }
+            if sequence.len() == input.len() {
+                return KeyResolution::Action(*action);
+            }
+            pending = true;
+        }
+        if pending {
+            KeyResolution::Pending
+        } else {
+            KeyResolution::Unbound
+        }
+    }
+
+    /// Whether a single key press triggers `action`, ignoring multi-key
+    /// sequences. Used by overlays that interpret keys themselves.
+    pub fn matches(&self, action: Action, key: &KeyEvent) -> bool {
+        let chord = KeyChord::from_event(key);
+        self.bindings
+            .iter()
+            .any(|(sequence, a)| *a == action && sequence.as_slice() == [chord])
+    }
+
+    pub fn pending_label(&self) -> Option<String> {
+        if self.pending.is_empty() {
+            None
+        } else {
+            Some(sequence_label(&self.pending))
+        }
+    }
+
+    fn sequences(&self, action: Action) -> impl Iterator<Item = &KeySequence> {
+        self.bindings
+            .iter()
+            .filter(move |(_, a)| *a == action)
+            .map(|(sequence, _)| sequence)
+    }
+
+    /// All bindings for `action`, e.g. `"↑/k"`.
+    pub fn label(&self, action: Action) -> String {
+        let labels: Vec<String> = self.sequences(action).map(|s| sequence_label(s)).collect();
+        if labels.is_empty() {
+            "unbound".to_string()
+        } else {
+            labels.join("/")
+        }
+    }
+
+    /// The first binding for `action`, for compact hints like the footer.
+    pub fn short_label(&self, action: Action) -> String {
+        self.sequences(action)
+            .next()
+            .map(|s| sequence_label(s))
+            .unwrap_or_else(|| "-".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossterm::event::{KeyEventKind, KeyEventState};
+
+    fn key(code: KeyCode, modifiers: KeyModifiers) -> KeyEvent {
+        KeyEvent {
+            code,
+            modifiers,
+            kind: KeyEventKind::Press,
+            state: KeyEventState::NONE,
+        }
+    }
+
+    #[test]
+    fn test_parse_chord_plain() {
+        let chord = KeyChord::parse("q").unwrap();
+        assert_eq!(chord.code, KeyCode::Char('q'));
+        assert_eq!(chord.modifiers, KeyModifiers::NONE);
+    }
+
+    #[test]
+    fn test_parse_chord_modifiers() {
+        let chord = KeyChord::parse("ctrl+s").unwrap();
+        assert_eq!(chord.code, KeyCode::Char('s'));
+        assert_eq!(chord.modifiers, KeyModifiers::CONTROL);
+
+        let chord = KeyChord::parse("Ctrl+Alt+PageDown").unwrap();
+        assert_eq!(chord.code, KeyCode::PageDown);
+        assert_eq!(chord.modifiers, KeyModifiers::CONTROL | KeyModifiers::ALT);
+    }
+
+    #[test]
+    fn test_parse_chord_shift_letter_is_uppercase() {
+        assert_eq!(KeyChord::parse("shift+r"), KeyChord::parse("R"));
+    }
+
+    #[test]
+    fn test_parse_chord_plus_key() {
+        let chord = KeyChord::parse("ctrl++").unwrap();
+        assert_eq!(chord.code, KeyCode::Char('+'));
+        assert_eq!(KeyChord::parse("+").unwrap().code, KeyCode::Char('+'));
+    }
+
+    #[test]
+    fn test_parse_chord_errors() {
+        assert_eq!(
+            KeyChord::parse("hyper+x"),
+            Err(KeymapError::UnknownModifier("hyper".to_string()))
+        );
+        assert_eq!(
+            KeyChord::parse("bogus"),
+            Err(KeymapError::UnknownKey("bogus".to_string()))
+        );
+        assert_eq!(KeyChord::parse(" "), Err(KeymapError::Empty));
+    }
+
+    #[test]
+    fn test_parse_binding_alternatives_and_sequences() {
+        let binding = parse_binding("up, k, g g").unwrap();
+        assert_eq!(binding.len(), 3);
+        assert_eq!(binding[2].len(), 2);
+        assert_eq!(binding[2][0].code, KeyCode::Char('g'));
+    }
+
+    #[test]
+    fn test_event_with_shift_matches_uppercase_binding() {
+        let keymap = Keymap::from_config(&KeybindingConfig::default());
+        let event = key(KeyCode::Char('R'), KeyModifiers::SHIFT);
+        assert!(keymap.matches(Action::ClearReviewed, &event));
+    }
+
+    #[test]
+    fn test_default_config_has_no_issues() {
+        let keymap = Keymap::from_config(&KeybindingConfig::default());
+        assert!(keymap.issues.is_empty(), "{:?}", keymap.issues);
+    }
+
+    #[test]
+    fn test_conflict_reported() {
+        let config = KeybindingConfig {
+            help: "q".to_string(),
+            ..Default::default()
+        };
+        let keymap = Keymap::from_config(&config);
+        assert_eq!(keymap.issues.len(), 1);
+        assert!(keymap.issues[0].contains("help"));
+        assert!(keymap.issues[0].contains("quit"));
+    }
+
+    #[test]
+    fn test_prefix_conflict_reported() {
+        let config = KeybindingConfig {
+            next_hunk: "g".to_string(),
+            prev_hunk: "g g".to_string(),
+            ..Default::default()
+        };
+        let keymap = Keymap::from_config(&config);
+        assert_eq!(keymap.issues.len(), 1);
+    }
+
+    #[test]
+    fn test_editor_scope_does_not_conflict_with_main() {
+        let config = KeybindingConfig {
+            // Also the default of save_settings, save_note and submit_commit.
+            quit: "ctrl+s".to_string(),
+            ..Default::default()
+        };
+        let keymap = Keymap::from_config(&config);
+        assert!(keymap.issues.is_empty());
+    }
+
+    #[test]
+    fn test_editor_scope_rejects_printable_keys() {
+        let config = KeybindingConfig {
+            save_note: "ctrl+s, S".to_string(),
+            submit_commit: "alt+s".to_string(),
+            ..Default::default()
+        };
+        let keymap = Keymap::from_config(&config);
+        assert_eq!(keymap.issues.len(), 1);
+        assert!(keymap.issues[0].starts_with("save_note: 'S'"));
+        assert_eq!(keymap.label(Action::SaveNote), "Ctrl+S");
+        assert_eq!(keymap.label(Action::SubmitCommit), "Alt+S");
+    }
+
+    #[test]
+    fn test_editor_scope_rejects_sequences() {
+        let config = KeybindingConfig {
+            save_settings: "ctrl+x ctrl+s".to_string(),
+            ..Default::default()
+        };
+        let keymap = Keymap::from_config(&config);
+        assert_eq!(keymap.issues.len(), 1);
+        assert!(keymap.issues[0].starts_with("save_settings: 'Ctrl+X Ctrl+S'"));
+        assert_eq!(keymap.label(Action::SaveSettings), "Ctrl+S");
+    }
+
+    #[test]
+    fn test_function_keys() {
+        assert_eq!(KeyChord::parse("f24").unwrap().code, KeyCode::F(24));
+        assert!(KeyChord::parse("f25").is_err());
+    }
+
+    #[test]
+    fn test_invalid_binding_falls_back_to_default() {
+        let config = KeybindingConfig {
+            help: "nonsense-key".to_string(),
+            ..Default::default()
+        };
+        let keymap = Keymap::from_config(&config);
+        assert_eq!(keymap.issues.len(), 1);
+        assert_eq!(keymap.label(Action::Help), "?");
+    }
+
+    #[test]
+    fn test_resolve_sequence() {
+        let config = KeybindingConfig {
+            next_hunk: "g g".to_string(),
+            ..Default::default()
+        };
+        let mut keymap = Keymap::from_config(&config);
+        let g = key(KeyCode::Char('g'), KeyModifiers::NONE);
+
+        assert_eq!(keymap.resolve(&g), KeyResolution::Pending);
+        assert_eq!(keymap.pending_label(), Some("g".to_string()));
+        assert_eq!(keymap.resolve(&g), KeyResolution::Action(Action::NextHunk));
+        assert_eq!(keymap.pending_label(), None);
+    }
+
+    #[test]
+    fn test_resolve_broken_sequence_retries_key() {
+        let config = KeybindingConfig {
+            next_hunk: "g g".to_string(),
+            ..Default::default()
+        };
+        let mut keymap = Keymap::from_config(&config);
+
+        keymap.resolve(&key(KeyCode::Char('g'), KeyModifiers::NONE));
+        assert_eq!(
+            keymap.resolve(&key(KeyCode::Char('q'), KeyModifiers::NONE)),
+            KeyResolution::Action(Action::Quit)
+        );
+    }
+
+    #[test]
+    fn test_labels() {
+        let keymap = Keymap::from_config(&KeybindingConfig::default());
+        assert_eq!(keymap.label(Action::ScrollUp), "↑/k");
+        assert_eq!(keymap.short_label(Action::TogglePause), "Space");
+        assert_eq!(keymap.label(Action::SaveSettings), "Ctrl+S");
+    }
+}
@@ -2,21 +2,30 @@ use ratatui::{
     layout::Rect,
     style::{Modifier, Style},
     text::{Line, Span},
-    widgets::Paragraph,
+    widgets::{Block, Borders, Paragraph},
     Frame,
 };
 
 use crate::config::DiffViewerType;
 use crate::diff_viewer::{get_viewer_display_name, resolve_viewer};
-use crate::types::DisplayedEvent;
+use crate::types::{Comparison, DisplayedEvent, HistoryView};
 
 use super::app::App;
+use super::keymap::Action;
+use super::layout_helpers::truncate_path_start;
 use super::theme::Theme;
 
 pub fn draw_header(f: &mut Frame, app: &App, area: Rect) {
     let theme = &app.theme;
 
-    let status = if app.is_paused() {
+    let status = if app.is_replay() {
+        Span::styled(
+            " [REPLAY] ",
+            Style::default()
+                .fg(theme.status_paused)
+                .add_modifier(Modifier::BOLD),
+        )
+    } else if app.is_paused() {
         Span::styled(
             " [PAUSED] ",
             Style::default()
@@ -40,12 +49,28 @@ pub fn draw_header(f: &mut Frame, app: &App, area: Rect) {
         )
     };
 
+    let pending = if app.pending_events.is_empty() {
+        Span::raw("")
+    } else {
+        Span::styled(
+            format!("{} pending ", app.pending_events.len()),
+            Style::default().fg(theme.status_paused),
+        )
+    };
+
+    let source = match &app.replay {
+        Some(recording) => recording.display().to_string(),
+        None => app.head.label(),
+    };
+
     let title = Line::from(vec![
         Span::styled(
             " gwatch",
             Style::default().fg(theme.text).add_modifier(Modifier::BOLD),
         ),
         status,
+        pending,
+        Span::styled(format!("{source} "), Style::default().fg(theme.text)),
         Span::styled(
             format!("─ {} ", app.theme.name),
             Style::default().fg(theme.text_dim),
@@ -59,56 +84,79 @@ pub fn draw_header(f: &mut Frame, app: &App, area: Rect) {
 pub fn draw_footer(f: &mut Frame, app: &App, area: Rect) {
     let theme = &app.theme;
 
-    let mut spans = vec![
-        Span::styled(
-            " [↑↓]",
-            Style::default().fg(theme.text).add_modifier(Modifier::BOLD),
+    let keymap = &app.keymap;
+    let key_style = Style::default().fg(theme.text).add_modifier(Modifier::BOLD);
+    let label_style = Style::default().fg(theme.text_dim);
+    let hints = [
+        (
+            format!(
+                "[{}{}]",
+                keymap.short_label(Action::ScrollUp),
+                keymap.short_label(Action::ScrollDown)
+            ),
+            " Diff  ",
         ),
-        Span::styled(" Diff  ", Style::default().fg(theme.text_dim)),
-        Span::styled(
-            "[N/P]",
-            Style::default().fg(theme.text).add_modifier(Modifier::BOLD),
+        (
+            format!(
+                "[{}/{}]",
+                keymap.short_label(Action::NextEvent),
+                keymap.short_label(Action::PrevEvent)
+            ),
+            " Event  ",
         ),
-        Span::styled(" Event  ", Style::default().fg(theme.text_dim)),
-        Span::styled(
-            "[←→]",
-            Style::default().fg(theme.text).add_modifier(Modifier::BOLD),
+        (
+            format!(
+                "[{}{}]",
+                keymap.short_label(Action::ScrollLeft),
+                keymap.short_label(Action::ScrollRight)
+            ),
+            " Pan  ",
         ),
-        Span::styled(" Pan  ", Style::default().fg(theme.text_dim)),
-        Span::styled(
-            "[Space]",
-            Style::default().fg(theme.text).add_modifier(Modifier::BOLD),
+        (
+            format!("[{}]", keymap.short_label(Action::TogglePause)),
+            " Pause  ",
         ),
-        Span::styled(" Pause  ", Style::default().fg(theme.text_dim)),
-        Span::styled(
-            "[Enter]",
-            Style::default().fg(theme.text).add_modifier(Modifier::BOLD),
+        (
+            format!("[{}]", keymap.short_label(Action::OpenEditor)),
+            " Edit  ",
         ),
-        Span::styled(" Edit  ", Style::default().fg(theme.text_dim)),
+        (format!("[{}]", keymap.short_label(Action::Help)), " Help  "),
+        (format!("[{}]", keymap.short_label(Action::Quit)), " Quit"),
+    ];
+
+    let mut spans = vec![Span::raw(" ")];
+    for (keys, label) in hints {
+        spans.push(Span::styled(keys, key_style));
+        spans.push(Span::styled(label, label_style));
+    }
+    spans.extend([
+        Span::styled(" │ ", Style::default().fg(theme.border)),
         Span::styled(
-            "[?]",
-            Style::default().fg(theme.text).add_modifier(Modifier::BOLD),
+            format!("Mode: {}", app.diff_mode.label()),
+            Style::default().fg(theme.text_dim),
         ),
-        Span::styled(" Help  ", Style::default().fg(theme.text_dim)),
         Span::styled(
-            "[q]",
-            Style::default().fg(theme.text).add_modifier(Modifier::BOLD),
+            format!(" [{}]", keymap.short_label(Action::CycleDiffMode)),
+            Style::default().fg(theme.context),
         ),
-        Span::styled(" Quit", Style::default().fg(theme.text_dim)),
         Span::styled(" │ ", Style::default().fg(theme.border)),
+        Span::styled(layout_label(app), Style::default().fg(theme.text_dim)),
         Span::styled(
-            format!("Mode: {}", app.diff_mode.label()),
-            Style::default().fg(theme.text_dim),
+            format!(" [{}]", keymap.short_label(Action::ToggleLayout)),
+            Style::default().fg(theme.context),
         ),
-        Span::styled(" [m]", Style::default().fg(theme.context)),
-    ];
+    ]);
 
-    let reviewed_count = app.review_state.reviewed_count();
-    if reviewed_count > 0 {
+    if let Some((reviewed, total)) = app.review_progress() {
+        let color = if reviewed == total {
+            theme.added
+        } else {
+            theme.text_dim
+        };
         spans.push(Span::styled(" │ ", Style::default().fg(theme.border)));
         spans.push(Span::styled(
-            format!("Reviewed: {reviewed_count}"),
-            Style::default().fg(theme.added),
+            format!("{reviewed}/{total} hunks reviewed"),
+            Style::default().fg(color),
         ));
     }
 
@@ -119,7 +167,38 @@ pub fn draw_footer(f: &mut Frame, app: &App, area: Rect) {
             format!("Hunk {}/{}", app.hunk_state.focused_hunk + 1, hunk_count),
             Style::default().fg(theme.text_dim),
         ));
-        spans.push(Span::styled(" []/z", Style::default().fg(theme.context)));
+        spans.push(Span::styled(
+            format!(
+                " {}{}/{}",
+                keymap.short_label(Action::NextHunk),
+                keymap.short_label(Action::PrevHunk),
+                keymap.short_label(Action::ToggleHunk)
+            ),
+            Style::default().fg(theme.context),
+        ));
+    }
+
+    if let Some(selection) = app.hunk_state.selection {
+        let count = selection.range().count();
+        spans.push(Span::styled(" │ ", Style::default().fg(theme.border)));
+        spans.push(Span::styled(
+            format!(
+                "VISUAL {count} {}",
+                if count == 1 { "line" } else { "lines" }
+            ),
+            Style::default()
+                .fg(theme.status_paused)
+                .add_modifier(Modifier::BOLD),
+        ));
+        spans.push(Span::styled(
+            format!(
+                " [{}] stage [{}] unstage [{}] note [Esc] cancel",
+                keymap.short_label(Action::StageLines),
+                keymap.short_label(Action::UnstageLines),
+                keymap.short_label(Action::AddNote)
+            ),
+            Style::default().fg(theme.context),
+        ));
     }
 
     let viewer = resolve_viewer(&app.config.diff_viewer);
@@ -129,13 +208,50 @@ pub fn draw_footer(f: &mut Frame, app: &App, area: Rect) {
             format!("Viewer: {}", get_viewer_display_name(&viewer)),
             Style::default().fg(theme.text_dim),
         ));
-        spans.push(Span::styled(" [d]", Style::default().fg(theme.context)));
+        spans.push(Span::styled(
+            format!(" [{}]", keymap.short_label(Action::OpenDiffViewer)),
+            Style::default().fg(theme.context),
+        ));
+    }
+
+    if let Some(status) = app.status_message() {
+        let color = if status.is_error {
+            theme.deleted
+        } else {
+            theme.added
+        };
+        spans.push(Span::styled(" │ ", Style::default().fg(theme.border)));
+        spans.push(Span::styled(
+            status.text.clone(),
+            Style::default().fg(color),
+        ));
+    }
+
+    if let Some(pending) = keymap.pending_label() {
+        spans.push(Span::styled(" │ ", Style::default().fg(theme.border)));
+        spans.push(Span::styled(
+            format!("{pending} …"),
+            Style::default()
+                .fg(theme.status_paused)
+                .add_modifier(Modifier::BOLD),
+        ));
     }
 
     let footer = Paragraph::new(Line::from(spans)).style(Style::default().bg(theme.footer_bg));
     f.render_widget(footer, area);
 }
 
+/// Footer label for the diff layout, noting when split view had to fall
+/// back to unified for lack of width.
+fn layout_label(app: &App) -> String {
+    let active = app.active_layout();
+    if active == app.diff_layout {
+        format!("Layout: {}", active.label())
+    } else {
+        format!("Layout: {} (narrow)", active.label())
+    }
+}
+
 pub fn draw_event_header(
     f: &mut Frame,
     event: &DisplayedEvent,
@@ -144,9 +260,10 @@ pub fn draw_event_header(
     app: &App,
 ) {
     let time_str = event.timestamp.format("%H:%M:%S").to_string();
+    let shown = app.shown_diff(event);
     let stats = format!(
         "+{} / -{} lines",
-        event.diff.stats.added_count, event.diff.stats.deleted_count
+        shown.stats.added_count, shown.stats.deleted_count
     );
 
     let file_indicator = if event.diff.is_new_file {
@@ -171,17 +288,34 @@ pub fn draw_event_header(
         Style::default().fg(theme.text_dim)
     };
 
-    let event_index_info = format!(" [{}/{}]", app.scroll_offset + 1, app.events.len());
+    let event_index_info = if app.history_view == HistoryView::Files {
+        let saves = app
+            .events
+            .iter()
+            .filter(|e| e.file_path == event.file_path)
+            .count();
+        format!(
+            " [{}/{} files · {saves} saves]",
+            app.scroll_offset + 1,
+            app.entry_count()
+        )
+    } else {
+        format!(" [{}/{}]", app.scroll_offset + 1, app.entry_count())
+    };
 
-    let mut spans = vec![
-        Span::styled(" ", Style::default()),
-        Span::styled(
-            event.relative_path.clone(),
-            Style::default().fg(theme.text).add_modifier(Modifier::BOLD),
-        ),
-    ];
+    let mut spans = vec![Span::styled(" ", Style::default())];
+    if let Some(ref from) = event.diff.renamed_from {
+        spans.push(Span::styled(
+            format!("{from} → "),
+            Style::default().fg(theme.text_dim),
+        ));
+    }
+    spans.push(Span::styled(
+        event.relative_path.clone(),
+        Style::default().fg(theme.text).add_modifier(Modifier::BOLD),
+    ));
 
-    if app.review_state.is_reviewed(&event.file_path) {
+    if app.is_event_reviewed(event) {
         spans.push(Span::styled(
             " ✓ Reviewed",
             Style::default()
@@ -197,12 +331,97 @@ pub fn draw_event_header(
             Style::default().fg(theme.text_dim),
         ),
         Span::styled(stats, Style::default().fg(theme.context)),
+        Span::styled(
+            format!(" ±{} ctx", event.diff.context_lines),
+            Style::default().fg(theme.text_dim),
+        ),
+        Span::styled(
+            format!(" · {}", event.mode.label()),
+            Style::default().fg(theme.text_dim),
+        ),
+        Span::styled(
+            format!(" · {}", comparison_label(app, event)),
+            Style::default().fg(theme.text_dim),
+        ),
         Span::styled(event_index_info, Style::default().fg(theme.text_dim)),
     ]);
 
+    if app.computing.contains(&event.file_path) {
+        spans.push(Span::styled(
+            " computing…",
+            Style::default()
+                .fg(theme.status_paused)
+                .add_modifier(Modifier::ITALIC),
+        ));
+    }
+
     let header = Line::from(spans);
 
     let p =
         Paragraph::new(vec![header, Line::from("")]).style(Style::default().bg(theme.background));
     f.render_widget(p, area);
 }
+
+/// Sidebar of the files view: every file with changes and its line counts.
+pub fn draw_file_sidebar(f: &mut Frame, app: &App, files: &[&DisplayedEvent], area: Rect) {
+    let theme = &app.theme;
+    let block = Block::default()
+        .title(Span::styled(
+            format!(
+                " Files · {} [{}] ",
+                app.file_sort.label(),
+                app.keymap.short_label(Action::CycleFileSort)
+            ),
+            Style::default().fg(theme.text_dim),
+        ))
+        .borders(Borders::RIGHT)
+        .border_style(Style::default().fg(theme.border))
+        .style(Style::default().bg(theme.background));
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    // Keep the selected file in view.
+    let height = inner.height as usize;
+    let start = app.scroll_offset.saturating_sub(height.saturating_sub(1));
+
+    let lines: Vec<Line> = files
+        .iter()
+        .enumerate()
+        .skip(start)
+        .take(height)
+        .map(|(i, event)| {
+            let added = format!(" +{}", event.diff.stats.added_count);
+            let deleted = format!(" -{}", event.diff.stats.deleted_count);
+            let path_width = (inner.width as usize)
+                .saturating_sub(added.chars().count() + deleted.chars().count() + 1);
+            let path = truncate_path_start(&event.relative_path, path_width);
+
+            let selected = i == app.scroll_offset;
+            let row = if selected {
+                Style::default().bg(theme.border_focused)
+            } else {
+                Style::default()
+            };
+            let path_style = if selected {
+                row.fg(theme.text).add_modifier(Modifier::BOLD)
+            } else {
+                row.fg(theme.text_dim)
+            };
+            Line::from(vec![
+                Span::styled(format!(" {path:<path_width$}"), path_style),
+                Span::styled(added, row.fg(theme.added)),
+                Span::styled(deleted, row.fg(theme.deleted)),
+            ])
+        })
+        .collect();
+
+    f.render_widget(Paragraph::new(lines), inner);
+}
+
+fn comparison_label(app: &App, event: &DisplayedEvent) -> String {
+    if app.comparison == Comparison::PreviousSave && event.snapshot.diff.is_none() {
+        format!("{} (no earlier save)", Comparison::Base.label())
+    } else {
+        app.comparison.label().to_string()
+    }
+}
@@ -38,10 +38,31 @@ pub fn format_hunk_info(current: usize, total: usize) -> String {
     format!("Hunk {}/{}", current + 1, total)
 }
 
+/// Shorten `path` to at most `width` chars by dropping its start, so the file
+/// name stays visible.
+pub fn truncate_path_start(path: &str, width: usize) -> String {
+    let len = path.chars().count();
+    if len <= width {
+        return path.to_string();
+    }
+    if width == 0 {
+        return String::new();
+    }
+    let tail: String = path.chars().skip(len - (width - 1)).collect();
+    format!("…{tail}")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_truncate_path_start() {
+        assert_eq!(truncate_path_start("src/main.rs", 20), "src/main.rs");
+        assert_eq!(truncate_path_start("src/ui/render.rs", 10), "…render.rs");
+        assert_eq!(truncate_path_start("src/main.rs", 0), "");
+    }
+
     #[test]
     fn test_format_event_index() {
         assert_eq!(format_event_index(0, 5), " [1/5]");
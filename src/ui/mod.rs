@@ -1,11 +1,13 @@
 pub mod app;
 pub mod diff_view;
 pub mod handlers;
+pub mod keymap;
 pub mod layout;
 pub mod layout_helpers;
 pub mod overlays;
 pub mod render;
 pub mod render_helpers;
+pub mod syntax;
 pub mod theme;
 
 pub use app::App;
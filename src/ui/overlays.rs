@@ -7,6 +7,7 @@ use ratatui::{
 };
 
 use super::app::App;
+use super::keymap::{Action, ActionGroup};
 use super::theme::Theme;
 
 pub fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
@@ -35,8 +36,8 @@ pub fn draw_theme_selector(f: &mut Frame, app: &App) {
 
     f.render_widget(Clear, area);
 
-    let themes = Theme::available_themes();
-    let items: Vec<ListItem> = themes
+    let themes = app.theme_names();
+    let mut items: Vec<ListItem> = themes
         .iter()
         .enumerate()
         .map(|(i, name)| {
@@ -52,6 +53,15 @@ pub fn draw_theme_selector(f: &mut Frame, app: &App) {
         })
         .collect();
 
+    if !app.theme_registry.errors.is_empty() {
+        items.push(ListItem::new(""));
+        for error in &app.theme_registry.errors {
+            items.push(
+                ListItem::new(format!("  ! {error}")).style(Style::default().fg(theme.deleted)),
+            );
+        }
+    }
+
     let list = List::new(items).block(
         Block::default()
             .title(" Select Theme ")
@@ -66,190 +76,54 @@ pub fn draw_theme_selector(f: &mut Frame, app: &App) {
 
 pub fn draw_help_panel(f: &mut Frame, app: &App) {
     let theme = &app.theme;
-    let area = centered_rect(60, 70, f.area());
+    let area = centered_rect(60, 85, f.area());
 
     f.render_widget(Clear, area);
 
-    let help_text = vec![
-        Line::from(""),
-        Line::from(Span::styled(
-            "  Navigation",
-            Style::default()
-                .fg(theme.text)
-                .add_modifier(Modifier::BOLD | Modifier::UNDERLINED),
-        )),
-        Line::from(vec![
-            Span::styled(
-                "  ↑/↓ or j/k   ",
-                Style::default().fg(theme.text).add_modifier(Modifier::BOLD),
-            ),
-            Span::styled(
-                "Scroll within current diff",
-                Style::default().fg(theme.text_dim),
-            ),
-        ]),
-        Line::from(vec![
-            Span::styled(
-                "  p / n        ",
-                Style::default().fg(theme.text).add_modifier(Modifier::BOLD),
-            ),
-            Span::styled(
-                "Navigate between events (prev/next)",
-                Style::default().fg(theme.text_dim),
-            ),
-        ]),
-        Line::from(vec![
-            Span::styled(
-                "  ←/→ or h/l   ",
-                Style::default().fg(theme.text).add_modifier(Modifier::BOLD),
-            ),
-            Span::styled("Pan diff horizontally", Style::default().fg(theme.text_dim)),
-        ]),
-        Line::from(vec![
-            Span::styled(
-                "  PgUp/PgDn    ",
-                Style::default().fg(theme.text).add_modifier(Modifier::BOLD),
-            ),
-            Span::styled(
-                "Fast scroll within diff (10 lines)",
-                Style::default().fg(theme.text_dim),
-            ),
-        ]),
-        Line::from(""),
-        Line::from(Span::styled(
-            "  Hunk Navigation",
-            Style::default()
-                .fg(theme.text)
-                .add_modifier(Modifier::BOLD | Modifier::UNDERLINED),
-        )),
-        Line::from(vec![
-            Span::styled(
-                "  ] / [        ",
-                Style::default().fg(theme.text).add_modifier(Modifier::BOLD),
-            ),
-            Span::styled(
-                "Jump to next/prev hunk",
-                Style::default().fg(theme.text_dim),
-            ),
-        ]),
-        Line::from(vec![
-            Span::styled(
-                "  z            ",
-                Style::default().fg(theme.text).add_modifier(Modifier::BOLD),
-            ),
-            Span::styled("Toggle hunk collapsed", Style::default().fg(theme.text_dim)),
-        ]),
-        Line::from(vec![
-            Span::styled(
-                "  Z            ",
-                Style::default().fg(theme.text).add_modifier(Modifier::BOLD),
-            ),
-            Span::styled(
-                "Toggle hide context lines",
-                Style::default().fg(theme.text_dim),
-            ),
-        ]),
-        Line::from(""),
-        Line::from(Span::styled(
-            "  Actions",
+    let keymap = &app.keymap;
+    let heading_style = Style::default()
+        .fg(theme.text)
+        .add_modifier(Modifier::BOLD | Modifier::UNDERLINED);
+    let key_style = Style::default().fg(theme.text).add_modifier(Modifier::BOLD);
+    let desc_style = Style::default().fg(theme.text_dim);
+
+    let mut help_text = Vec::new();
+
+    if !keymap.issues.is_empty() {
+        help_text.push(Line::from(""));
+        help_text.push(Line::from(Span::styled(
+            "  Keybinding issues",
             Style::default()
-                .fg(theme.text)
+                .fg(theme.deleted)
                 .add_modifier(Modifier::BOLD | Modifier::UNDERLINED),
-        )),
-        Line::from(vec![
-            Span::styled(
-                "  Space        ",
-                Style::default().fg(theme.text).add_modifier(Modifier::BOLD),
-            ),
-            Span::styled(
-                "Pause/Resume live streaming",
-                Style::default().fg(theme.text_dim),
-            ),
-        ]),
-        Line::from(vec![
-            Span::styled(
-                "  Enter        ",
-                Style::default().fg(theme.text).add_modifier(Modifier::BOLD),
-            ),
-            Span::styled(
-                "Open current file in $EDITOR",
-                Style::default().fg(theme.text_dim),
-            ),
-        ]),
-        Line::from(vec![
-            Span::styled(
-                "  c            ",
-                Style::default().fg(theme.text).add_modifier(Modifier::BOLD),
-            ),
-            Span::styled("Clear event history", Style::default().fg(theme.text_dim)),
-        ]),
-        Line::from(vec![
-            Span::styled(
-                "  d            ",
-                Style::default().fg(theme.text).add_modifier(Modifier::BOLD),
-            ),
-            Span::styled(
-                "Open diff in external viewer (delta/difftastic/pager)",
-                Style::default().fg(theme.text_dim),
-            ),
-        ]),
-        Line::from(vec![
-            Span::styled(
-                "  t            ",
-                Style::default().fg(theme.text).add_modifier(Modifier::BOLD),
-            ),
-            Span::styled("Open theme selector", Style::default().fg(theme.text_dim)),
-        ]),
-        Line::from(vec![
-            Span::styled(
-                "  m            ",
-                Style::default().fg(theme.text).add_modifier(Modifier::BOLD),
-            ),
-            Span::styled(
-                "Cycle diff mode (All/Unstaged/Staged)",
-                Style::default().fg(theme.text_dim),
-            ),
-        ]),
-        Line::from(vec![
-            Span::styled(
-                "  r            ",
-                Style::default().fg(theme.text).add_modifier(Modifier::BOLD),
-            ),
-            Span::styled(
-                "Toggle reviewed status for current file",
-                Style::default().fg(theme.text_dim),
-            ),
-        ]),
-        Line::from(vec![
-            Span::styled(
-                "  R            ",
-                Style::default().fg(theme.text).add_modifier(Modifier::BOLD),
-            ),
-            Span::styled(
-                "Clear all reviewed markers",
-                Style::default().fg(theme.text_dim),
-            ),
-        ]),
-        Line::from(vec![
-            Span::styled(
-                "  s            ",
-                Style::default().fg(theme.text).add_modifier(Modifier::BOLD),
-            ),
-            Span::styled("Open settings editor", Style::default().fg(theme.text_dim)),
-        ]),
-        Line::from(vec![
-            Span::styled(
-                "  q / Esc      ",
-                Style::default().fg(theme.text).add_modifier(Modifier::BOLD),
-            ),
-            Span::styled("Quit gwatch", Style::default().fg(theme.text_dim)),
-        ]),
-        Line::from(""),
-        Line::from(Span::styled(
-            "  Press any key to close this panel",
-            Style::default().fg(theme.context),
-        )),
-    ];
+        )));
+        for issue in &keymap.issues {
+            help_text.push(Line::from(Span::styled(
+                format!("  {issue}"),
+                Style::default().fg(theme.deleted),
+            )));
+        }
+    }
+
+    for group in ActionGroup::ALL {
+        help_text.push(Line::from(""));
+        help_text.push(Line::from(Span::styled(
+            format!("  {}", group.title()),
+            heading_style,
+        )));
+        for action in Action::ALL.iter().filter(|a| a.group() == group) {
+            help_text.push(Line::from(vec![
+                Span::styled(format!("  {:<13}", keymap.label(*action)), key_style),
+                Span::styled(action.description(), desc_style),
+            ]));
+        }
+    }
+
+    help_text.push(Line::from(""));
+    help_text.push(Line::from(Span::styled(
+        "  Press any key to close this panel",
+        Style::default().fg(theme.context),
+    )));
 
     let help = Paragraph::new(help_text)
         .block(
@@ -272,85 +146,15 @@ pub fn draw_settings_editor(f: &mut Frame, app: &App) {
     f.render_widget(Clear, area);
 
     let state = &app.settings_editor;
-    let lines: Vec<&str> = state.content.lines().collect();
-    let line_count = lines.len();
-
+    let line_count = state.content.split('\n').count();
     let inner_height = area.height.saturating_sub(4) as usize;
-    let visible_start = if state.cursor_line >= inner_height {
-        state.cursor_line - inner_height + 1
-    } else {
-        0
-    };
-
-    let mut text_lines: Vec<Line> = Vec::new();
-    for (i, line) in lines
-        .iter()
-        .enumerate()
-        .skip(visible_start)
-        .take(inner_height)
-    {
-        let line_num = format!("{:>3} ", i + 1);
-        let is_cursor_line = i == state.cursor_line;
-
-        let mut spans = vec![Span::styled(
-            line_num,
-            Style::default().fg(theme.line_number),
-        )];
-
-        if is_cursor_line {
-            let before = if state.cursor_col > 0 {
-                &line[..state.cursor_col.min(line.len())]
-            } else {
-                ""
-            };
-            let cursor_char = if state.cursor_col < line.len() {
-                &line[state.cursor_col..state.cursor_col + 1]
-            } else {
-                " "
-            };
-            let after = if state.cursor_col + 1 < line.len() {
-                &line[state.cursor_col + 1..]
-            } else {
-                ""
-            };
-
-            spans.push(Span::styled(
-                before.to_string(),
-                Style::default().fg(theme.text),
-            ));
-            spans.push(Span::styled(
-                cursor_char.to_string(),
-                Style::default()
-                    .fg(theme.background)
-                    .bg(theme.text)
-                    .add_modifier(Modifier::BOLD),
-            ));
-            spans.push(Span::styled(
-                after.to_string(),
-                Style::default().fg(theme.text),
-            ));
-        } else {
-            spans.push(Span::styled(
-                line.to_string(),
-                Style::default().fg(theme.text),
-            ));
-        }
-
-        text_lines.push(Line::from(spans));
-    }
-
-    if line_count == 0 {
-        text_lines.push(Line::from(vec![
-            Span::styled("  1 ", Style::default().fg(theme.line_number)),
-            Span::styled(
-                " ",
-                Style::default()
-                    .fg(theme.background)
-                    .bg(theme.text)
-                    .add_modifier(Modifier::BOLD),
-            ),
-        ]));
-    }
+    let mut text_lines = editor_lines(
+        &state.content,
+        (state.cursor_line, state.cursor_col),
+        inner_height,
+        theme,
+        |_| None,
+    );
 
     let footer_text = if let Some(ref err) = state.error_message {
         Line::from(vec![Span::styled(
@@ -362,7 +166,7 @@ pub fn draw_settings_editor(f: &mut Frame, app: &App) {
     } else {
         Line::from(vec![
             Span::styled(
-                " [Ctrl+S]",
+                format!(" [{}]", app.keymap.short_label(Action::SaveSettings)),
                 Style::default().fg(theme.text).add_modifier(Modifier::BOLD),
             ),
             Span::styled(" Save  ", Style::default().fg(theme.text_dim)),
@@ -372,7 +176,7 @@ pub fn draw_settings_editor(f: &mut Frame, app: &App) {
             ),
             Span::styled(" Cancel  ", Style::default().fg(theme.text_dim)),
             Span::styled(
-                format!("Line {}/{}", state.cursor_line + 1, line_count.max(1)),
+                format!("Line {}/{}", state.cursor_line + 1, line_count),
                 Style::default().fg(theme.context),
             ),
         ])
@@ -393,6 +197,271 @@ pub fn draw_settings_editor(f: &mut Frame, app: &App) {
     f.render_widget(editor, area);
 }
 
+/// Column past which a commit subject is too long to read in `git log --oneline`.
+const SUBJECT_WIDTH: usize = 50;
+/// Column past which commit body lines should wrap.
+const BODY_WIDTH: usize = 72;
+/// Staged files listed in the commit editor before the rest are summarised.
+const STAGED_PREVIEW: usize = 5;
+
+pub fn draw_commit_editor(f: &mut Frame, app: &App) {
+    let theme = &app.theme;
+    let area = centered_rect(80, 85, f.area());
+
+    f.render_widget(Clear, area);
+
+    let state = &app.commit_editor;
+    let dim = Style::default().fg(theme.text_dim);
+    let key_style = Style::default().fg(theme.text).add_modifier(Modifier::BOLD);
+    let error_style = Style::default()
+        .fg(theme.deleted)
+        .add_modifier(Modifier::BOLD);
+
+    let mut text_lines: Vec<Line> = Vec::new();
+    match &state.info {
+        None => text_lines.push(Line::from(Span::styled(" Reading the index…", dim))),
+        Some(info) => {
+            text_lines.push(match &info.signature {
+                Ok(signature) => Line::from(vec![
+                    Span::styled(" Author  ", dim),
+                    Span::styled(signature.clone(), Style::default().fg(theme.text)),
+                ]),
+                Err(error) => Line::from(Span::styled(format!(" {error}"), error_style)),
+            });
+            let staged = match info.staged.len() {
+                0 if state.amend => " Staged  nothing new; only the message changes".to_string(),
+                0 => " Staged  nothing".to_string(),
+                1 => " Staged  1 file".to_string(),
+                n => format!(" Staged  {n} files"),
+            };
+            text_lines.push(Line::from(Span::styled(staged, dim)));
+            for file in info.staged.iter().take(STAGED_PREVIEW) {
+                let color = match file.status {
+                    'A' => theme.added,
+                    'D' => theme.deleted,
+                    _ => theme.context,
+                };
+                text_lines.push(Line::from(vec![
+                    Span::styled(format!("   {} ", file.status), Style::default().fg(color)),
+                    Span::styled(file.path.clone(), Style::default().fg(theme.text)),
+                ]));
+            }
+            if info.staged.len() > STAGED_PREVIEW {
+                text_lines.push(Line::from(Span::styled(
+                    format!("   … and {} more", info.staged.len() - STAGED_PREVIEW),
+                    dim,
+                )));
+            }
+        }
+    }
+    text_lines.push(Line::from(""));
+
+    let inner_width = area.width.saturating_sub(2) as usize;
+    text_lines.push(ruler(inner_width.saturating_sub(4), theme));
+
+    // Two rows below the message: a blank one and the footer.
+    let inner_height = area.height.saturating_sub(2) as usize;
+    let message_height = inner_height.saturating_sub(text_lines.len() + 2).max(1);
+    text_lines.extend(editor_lines(
+        &state.message,
+        (state.cursor_line, state.cursor_col),
+        message_height,
+        theme,
+        |line| Some(if line == 0 { SUBJECT_WIDTH } else { BODY_WIDTH }),
+    ));
+
+    let footer_text = if let Some(ref err) = state.error_message {
+        Line::from(Span::styled(format!(" Error: {err} "), error_style))
+    } else {
+        Line::from(vec![
+            Span::styled(
+                format!(" [{}]", app.keymap.short_label(Action::SubmitCommit)),
+                key_style,
+            ),
+            Span::styled(if state.amend { " Amend  " } else { " Commit  " }, dim),
+            Span::styled(
+                format!("[{}]", app.keymap.short_label(Action::ToggleAmend)),
+                key_style,
+            ),
+            Span::styled(
+                if state.amend {
+                    " Amend: on  "
+                } else {
+                    " Amend: off  "
+                },
+                dim,
+            ),
+            Span::styled("[Esc]", key_style),
+            Span::styled(" Cancel  ", dim),
+            Span::styled(
+                format!("Col {}", state.cursor_col + 1),
+                Style::default().fg(theme.context),
+            ),
+        ])
+    };
+
+    text_lines.push(Line::from(""));
+    text_lines.push(footer_text);
+
+    let editor = Paragraph::new(text_lines).block(
+        Block::default()
+            .title(if state.amend {
+                " Amend Last Commit "
+            } else {
+                " Commit "
+            })
+            .title_style(Style::default().fg(theme.text).add_modifier(Modifier::BOLD))
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(theme.border_focused))
+            .style(Style::default().bg(theme.background)),
+    );
+
+    f.render_widget(editor, area);
+}
+
+pub fn draw_note_editor(f: &mut Frame, app: &App) {
+    let Some(note) = &app.note_editor.note else {
+        return;
+    };
+    let theme = &app.theme;
+    let area = centered_rect(60, 40, f.area());
+
+    f.render_widget(Clear, area);
+
+    let state = &app.note_editor;
+    let dim = Style::default().fg(theme.text_dim);
+    let key_style = Style::default().fg(theme.text).add_modifier(Modifier::BOLD);
+
+    let inner_height = area.height.saturating_sub(2) as usize;
+    let mut text_lines = editor_lines(
+        &state.text,
+        (state.cursor_line, state.cursor_col),
+        inner_height.saturating_sub(2).max(1),
+        theme,
+        |_| None,
+    );
+    text_lines.push(Line::from(""));
+    text_lines.push(Line::from(vec![
+        Span::styled(
+            format!(" [{}]", app.keymap.short_label(Action::SaveNote)),
+            key_style,
+        ),
+        Span::styled(" Save  ", dim),
+        Span::styled("[Esc]", key_style),
+        Span::styled(" Cancel  ", dim),
+        Span::styled(
+            if state.original.is_some() {
+                "Save it empty to delete the note"
+            } else {
+                ""
+            },
+            Style::default().fg(theme.context),
+        ),
+    ]));
+
+    let editor = Paragraph::new(text_lines).block(
+        Block::default()
+            .title(format!(
+                " Note on {} of {} ",
+                note.location(),
+                note.path.display()
+            ))
+            .title_style(Style::default().fg(theme.text).add_modifier(Modifier::BOLD))
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(theme.border_focused))
+            .style(Style::default().bg(theme.background)),
+    );
+
+    f.render_widget(editor, area);
+}
+
+/// A column ruler lined up with [`editor_lines`], marking the subject and
+/// body width limits.
+fn ruler(width: usize, theme: &Theme) -> Line<'static> {
+    let mut spans = vec![Span::raw("    ")];
+    for column in 1..=width.min(BODY_WIDTH + 8) {
+        let span = if column == SUBJECT_WIDTH || column == BODY_WIDTH {
+            Span::styled(
+                "┃",
+                Style::default()
+                    .fg(theme.status_paused)
+                    .add_modifier(Modifier::BOLD),
+            )
+        } else if column % 10 == 0 {
+            Span::styled(
+                (column / 10 % 10).to_string(),
+                Style::default().fg(theme.line_number),
+            )
+        } else {
+            Span::styled(
+                if column % 5 == 0 { "+" } else { "·" },
+                Style::default().fg(theme.border),
+            )
+        };
+        spans.push(span);
+    }
+    Line::from(spans)
+}
+
+/// Numbered lines of a text being edited, scrolled so the cursor line is one
+/// of the `height` shown, with the character under the cursor highlighted.
+/// Characters from column `overflow(line)` on are shown as too long.
+fn editor_lines(
+    content: &str,
+    (cursor_line, cursor_col): (usize, usize),
+    height: usize,
+    theme: &Theme,
+    overflow: impl Fn(usize) -> Option<usize>,
+) -> Vec<Line<'static>> {
+    let visible_start = (cursor_line + 1).saturating_sub(height);
+    let text_style = Style::default().fg(theme.text);
+    let overflow_style = Style::default().fg(theme.deleted);
+    let cursor_style = Style::default()
+        .fg(theme.background)
+        .bg(theme.text)
+        .add_modifier(Modifier::BOLD);
+
+    let mut lines = Vec::new();
+    for (i, line) in content
+        .split('\n')
+        .enumerate()
+        .skip(visible_start)
+        .take(height)
+    {
+        let limit = overflow(i).unwrap_or(usize::MAX);
+        let cursor = (i == cursor_line).then_some(cursor_col);
+        let mut spans = vec![Span::styled(
+            format!("{:>3} ", i + 1),
+            Style::default().fg(theme.line_number),
+        )];
+        let mut run = String::new();
+        let mut run_style = text_style;
+        let len = line.chars().count();
+        for (col, c) in line.chars().chain(std::iter::once(' ')).enumerate() {
+            if col == len && cursor != Some(col) {
+                break;
+            }
+            let style = if cursor == Some(col) {
+                cursor_style
+            } else if col >= limit {
+                overflow_style
+            } else {
+                text_style
+            };
+            if style != run_style && !run.is_empty() {
+                spans.push(Span::styled(std::mem::take(&mut run), run_style));
+            }
+            run_style = style;
+            run.push(c);
+        }
+        if !run.is_empty() {
+            spans.push(Span::styled(run, run_style));
+        }
+        lines.push(Line::from(spans));
+    }
+    lines
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1,3 +1,5 @@
+use std::rc::Rc;
+
 use ratatui::{
     layout::{Constraint, Direction, Layout, Rect},
     style::Style,
@@ -6,14 +8,28 @@ use ratatui::{
     Frame,
 };
 
-use crate::types::DiffKind;
+use crate::types::{Comparison, DiffKind, DiffLayout, HistoryView};
 
-use super::app::{App, AppState};
-use super::diff_view::{build_side_by_side_lines, truncate_with_offset};
-use super::layout::{draw_event_header, draw_footer, draw_header};
-use super::overlays::{draw_help_panel, draw_settings_editor, draw_theme_selector};
+use super::app::{App, AppState, LineSelection};
+use super::diff_view::{build_side_by_side_lines, split_emphasis};
+use super::layout::{draw_event_header, draw_file_sidebar, draw_footer, draw_header};
+use super::overlays::{
+    draw_commit_editor, draw_help_panel, draw_note_editor, draw_settings_editor,
+    draw_theme_selector,
+};
+use super::syntax::{split_colors, HunkColors, LineColors};
 use super::theme::Theme;
 
+const FILE_SIDEBAR_MAX_WIDTH: u16 = 48;
+const NOTE_MARKER: char = '✎';
+
+/// What the hunk line builders mark on top of the diff, by index into the
+/// hunk's lines.
+struct LineMarks<'m> {
+    selection: Option<LineSelection>,
+    noted: &'m [usize],
+}
+
 pub fn draw_ui(f: &mut Frame, app: &App) {
     let chunks = Layout::default()
         .direction(Direction::Vertical)
@@ -32,6 +48,8 @@ pub fn draw_ui(f: &mut Frame, app: &App) {
         AppState::ThemeSelector => draw_theme_selector(f, app),
         AppState::HelpPanel => draw_help_panel(f, app),
         AppState::SettingsEditor => draw_settings_editor(f, app),
+        AppState::CommitEditor => draw_commit_editor(f, app),
+        AppState::NoteEditor => draw_note_editor(f, app),
         _ => {}
     }
 }
@@ -52,7 +70,28 @@ fn draw_main_content(f: &mut Frame, app: &App, area: Rect) {
         return;
     }
 
-    let event = match app.events.get(app.scroll_offset) {
+    let inner = if app.history_view == HistoryView::Files {
+        let files = app.file_entries();
+        let width = (inner.width / 3).clamp(20, FILE_SIDEBAR_MAX_WIDTH);
+        let [sidebar, rest] = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Length(width), Constraint::Min(0)])
+            .areas(inner);
+        draw_file_sidebar(f, app, &files, sidebar);
+        if files.is_empty() {
+            let msg = Paragraph::new(Line::from(Span::styled(
+                "  No files with changes",
+                Style::default().fg(theme.text_dim),
+            )));
+            f.render_widget(msg, rest);
+            return;
+        }
+        rest
+    } else {
+        inner
+    };
+
+    let event = match app.get_current_event() {
         Some(e) => e,
         None => return,
     };
@@ -109,9 +148,19 @@ fn draw_diff_content(
         return;
     }
 
-    if event.diff.hunks.is_empty() {
+    let diff = app.shown_diff(event);
+    if diff.hunks.is_empty() {
+        let since_previous =
+            app.comparison == Comparison::PreviousSave && event.snapshot.diff.is_some();
+        let text = if since_previous {
+            "  No changes since the previous save"
+        } else if diff.renamed_from.is_some() {
+            "  Renamed without content changes"
+        } else {
+            "  No changes detected"
+        };
         let msg = Paragraph::new(Line::from(Span::styled(
-            "  No changes detected",
+            text,
             Style::default().fg(theme.text_dim),
         )))
         .style(Style::default().bg(theme.background));
@@ -121,8 +170,9 @@ fn draw_diff_content(
 
     let mut display_lines: Vec<Line> = Vec::new();
     let is_flashing = app.is_flashing();
+    let layout = app.diff_layout.effective(f.area().width);
 
-    for (hunk_idx, hunk) in event.diff.hunks.iter().enumerate() {
+    for (hunk_idx, hunk) in diff.hunks.iter().enumerate() {
         let is_focused = hunk_idx == app.hunk_state.focused_hunk;
         let is_collapsed = app.hunk_state.is_collapsed(hunk_idx);
 
@@ -141,14 +191,39 @@ fn draw_diff_content(
             " {} Hunk {}/{}: @@ -{},{} +{},{} @@ ",
             collapse_indicator,
             hunk_idx + 1,
-            event.diff.hunks.len(),
+            diff.hunks.len(),
             hunk.old_start,
             hunk.old_count,
             hunk.new_start,
             hunk.new_count,
         );
 
-        display_lines.push(Line::from(Span::styled(hunk_header, header_style)));
+        let mut header = vec![Span::styled(hunk_header, header_style)];
+        if app.is_hunk_reviewed(event, hunk) {
+            header.push(Span::styled(
+                " ✓ Reviewed",
+                Style::default()
+                    .fg(theme.added)
+                    .add_modifier(ratatui::style::Modifier::BOLD),
+            ));
+        }
+        let notes = app.hunk_notes(event, hunk);
+        for note in &notes {
+            let text = note.text.lines().next().unwrap_or_default();
+            let label = match note.line_index(hunk).map(|i| &hunk.lines[i]) {
+                Some(line) => {
+                    let number = line.new_line_number.or(line.old_line_number);
+                    format!("  {NOTE_MARKER} L{}: {text}", number.unwrap_or_default())
+                }
+                None => format!("  {NOTE_MARKER} {text}"),
+            };
+            header.push(Span::styled(
+                label,
+                Style::default().fg(theme.status_paused),
+            ));
+        }
+        let noted: Vec<usize> = notes.iter().filter_map(|n| n.line_index(hunk)).collect();
+        display_lines.push(Line::from(header));
 
         if is_collapsed {
             let added = hunk
@@ -167,7 +242,23 @@ fn draw_diff_content(
                 Style::default().fg(theme.text_dim),
             )));
         } else {
-            let hunk_lines = build_hunk_lines(hunk, app, theme, is_flashing, area.width);
+            let build = match layout {
+                DiffLayout::Split => build_hunk_lines,
+                DiffLayout::Unified => build_unified_lines,
+            };
+            let selection = app.hunk_state.selection.filter(|_| is_focused);
+            let hunk_lines = build(
+                hunk,
+                &event.relative_path,
+                app,
+                theme,
+                is_flashing,
+                area.width,
+                LineMarks {
+                    selection,
+                    noted: &noted,
+                },
+            );
             display_lines.extend(hunk_lines);
         }
     }
@@ -188,18 +279,22 @@ fn draw_diff_content(
 
 fn build_hunk_lines<'a>(
     hunk: &crate::types::DiffHunk,
+    relative_path: &str,
     app: &App,
     theme: &'a Theme,
     is_flashing: bool,
     width: u16,
+    marks: LineMarks,
 ) -> Vec<Line<'a>> {
+    let LineMarks { selection, noted } = marks;
     let collapse_context = app.hunk_state.collapse_context;
-    let filtered_lines: Vec<_> = hunk
+    let (indices, filtered_lines): (Vec<usize>, Vec<_>) = hunk
         .lines
         .iter()
-        .filter(|l| !collapse_context || l.kind != DiffKind::Context)
-        .cloned()
-        .collect();
+        .enumerate()
+        .filter(|(_, l)| !collapse_context || l.kind != DiffKind::Context)
+        .map(|(i, l)| (i, l.clone()))
+        .unzip();
 
     let side_by_side = build_side_by_side_lines(&filtered_lines);
 
@@ -207,37 +302,27 @@ fn build_hunk_lines<'a>(
     let available_for_content = width.saturating_sub(total_fixed);
     let content_width = (available_for_content / 2) as usize;
     let h_offset = app.diff_horizontal_offset;
+    let hunk_colors = hunk_syntax_colors(app, relative_path, hunk);
 
     let mut lines = Vec::new();
     for sbs in side_by_side {
-        let left_num_str = sbs
-            .left_num
-            .map(|n| format!("{n:>4}"))
-            .unwrap_or_else(|| "  · ".to_string());
+        let left_num_str = line_number_label(sbs.left_num);
 
-        let left_content = truncate_with_offset(&sbs.left_content, h_offset, content_width);
         let is_left_change = matches!(sbs.left_kind, Some(DiffKind::Deleted));
         let flash_left = is_flashing && is_left_change;
 
         let (left_num_style, left_content_style, left_prefix) =
             get_line_styles(sbs.left_kind.as_ref(), flash_left, theme);
 
-        let right_num_str = sbs
-            .right_num
-            .map(|n| format!("{n:>4}"))
-            .unwrap_or_else(|| "  · ".to_string());
+        let right_num_str = line_number_label(sbs.right_num);
 
-        let right_content = truncate_with_offset(&sbs.right_content, h_offset, content_width);
         let is_right_change = matches!(sbs.right_kind, Some(DiffKind::Added));
         let flash_right = is_flashing && is_right_change;
 
         let (right_num_style, right_content_style, right_prefix) =
             get_line_styles(sbs.right_kind.as_ref(), flash_right, theme);
 
-        let left_display = format!("{left_content:content_width$}");
-        let right_display = format!("{right_content:content_width$}");
-
-        lines.push(Line::from(vec![
+        let mut left = vec![
             Span::styled(
                 left_num_str,
                 left_num_style.add_modifier(ratatui::style::Modifier::DIM),
@@ -246,8 +331,24 @@ fn build_hunk_lines<'a>(
                 left_prefix,
                 left_content_style.add_modifier(ratatui::style::Modifier::BOLD),
             ),
-            Span::styled(left_display, left_content_style),
-            Span::styled("│", Style::default().fg(theme.border)),
+        ];
+        let left_colors = hunk_colors
+            .as_ref()
+            .filter(|_| !flash_left)
+            .and_then(|c| c.old.get(&sbs.left_num?));
+        left.extend(content_spans(
+            &sbs.left_content,
+            &sbs.left_emphasis,
+            h_offset,
+            content_width,
+            side_styles(
+                sbs.left_kind.as_ref(),
+                left_colors,
+                left_content_style,
+                theme,
+            ),
+        ));
+        let mut right = vec![
             Span::styled(
                 right_num_str,
                 right_num_style.add_modifier(ratatui::style::Modifier::DIM),
@@ -256,13 +357,257 @@ fn build_hunk_lines<'a>(
                 right_prefix,
                 right_content_style.add_modifier(ratatui::style::Modifier::BOLD),
             ),
-            Span::styled(right_display, right_content_style),
-        ]));
+        ];
+        let right_colors = hunk_colors
+            .as_ref()
+            .filter(|_| !flash_right)
+            .and_then(|c| c.new.get(&sbs.right_num?));
+        right.extend(content_spans(
+            &sbs.right_content,
+            &sbs.right_emphasis,
+            h_offset,
+            content_width,
+            side_styles(
+                sbs.right_kind.as_ref(),
+                right_colors,
+                right_content_style,
+                theme,
+            ),
+        ));
+
+        // Context lines show on both sides; their note goes on the right.
+        let left_noted = sbs
+            .left_index
+            .filter(|_| matches!(sbs.left_kind, Some(DiffKind::Deleted)))
+            .is_some_and(|i| noted.contains(&indices[i]));
+        if left_noted {
+            mark_noted(&mut left, theme);
+        }
+        if sbs.right_index.is_some_and(|i| noted.contains(&indices[i])) {
+            mark_noted(&mut right, theme);
+        }
+        if let Some(selection) = selection {
+            mark_selected(
+                &mut left,
+                sbs.left_index.map(|i| indices[i]),
+                selection,
+                theme,
+            );
+            mark_selected(
+                &mut right,
+                sbs.right_index.map(|i| indices[i]),
+                selection,
+                theme,
+            );
+        }
+        let mut spans = left;
+        spans.push(Span::styled("│", Style::default().fg(theme.border)));
+        spans.extend(right);
+        lines.push(Line::from(spans));
+    }
+
+    lines
+}
+
+/// Single-column rendering: both line numbers, then the line itself, with
+/// deletions and additions in the order the diff lists them.
+fn build_unified_lines<'a>(
+    hunk: &crate::types::DiffHunk,
+    relative_path: &str,
+    app: &App,
+    theme: &'a Theme,
+    is_flashing: bool,
+    width: u16,
+    marks: LineMarks,
+) -> Vec<Line<'a>> {
+    let LineMarks { selection, noted } = marks;
+    let collapse_context = app.hunk_state.collapse_context;
+    let total_fixed: u16 = 10;
+    let content_width = width.saturating_sub(total_fixed) as usize;
+    let h_offset = app.diff_horizontal_offset;
+    let hunk_colors = hunk_syntax_colors(app, relative_path, hunk);
+
+    let mut lines = Vec::new();
+    for (index, line) in hunk
+        .lines
+        .iter()
+        .enumerate()
+        .filter(|(_, l)| !collapse_context || l.kind != DiffKind::Context)
+    {
+        let flash = is_flashing && line.kind != DiffKind::Context;
+        let (num_style, content_style, prefix) = get_line_styles(Some(&line.kind), flash, theme);
+        let colors = hunk_colors
+            .as_ref()
+            .filter(|_| !flash)
+            .and_then(|c| match line.kind {
+                DiffKind::Deleted => c.old.get(&line.old_line_number?),
+                _ => c.new.get(&line.new_line_number?),
+            });
+
+        let num_style = num_style.add_modifier(ratatui::style::Modifier::DIM);
+        let mut spans = vec![
+            Span::styled(line_number_label(line.old_line_number), num_style),
+            Span::styled(" ", num_style),
+            Span::styled(line_number_label(line.new_line_number), num_style),
+            Span::styled(
+                prefix,
+                content_style.add_modifier(ratatui::style::Modifier::BOLD),
+            ),
+        ];
+        spans.extend(content_spans(
+            &line.content,
+            &line.emphasis,
+            h_offset,
+            content_width,
+            side_styles(Some(&line.kind), colors, content_style, theme),
+        ));
+        if noted.contains(&index) {
+            mark_noted(&mut spans, theme);
+        }
+        if let Some(selection) = selection {
+            mark_selected(&mut spans, Some(index), selection, theme);
+        }
+        lines.push(Line::from(spans));
     }
 
     lines
 }
 
+/// Highlights a row side showing hunk line `index` when it is part of the
+/// visual selection; the cursor line also gets a reversed line number.
+fn mark_selected(
+    spans: &mut [Span],
+    index: Option<usize>,
+    selection: LineSelection,
+    theme: &Theme,
+) {
+    let Some(index) = index.filter(|&i| selection.contains(i)) else {
+        return;
+    };
+    for span in spans.iter_mut() {
+        span.style = span.style.bg(theme.border_focused);
+    }
+    if index == selection.cursor {
+        if let Some(number) = spans.first_mut() {
+            number.style = number
+                .style
+                .add_modifier(ratatui::style::Modifier::REVERSED);
+        }
+    }
+}
+
+/// Marks a row side whose line has a note, in place of the line number's
+/// leading blank, or over its first digit for numbers of four digits.
+fn mark_noted(spans: &mut [Span], theme: &Theme) {
+    let Some(number) = spans.first_mut() else {
+        return;
+    };
+    let rest: String = number.content.chars().skip(1).collect();
+    number.content = format!("{NOTE_MARKER}{rest}").into();
+    number.style = Style::default()
+        .fg(theme.status_paused)
+        .add_modifier(ratatui::style::Modifier::BOLD);
+}
+
+fn line_number_label(number: Option<usize>) -> String {
+    number
+        .map(|n| format!("{n:>4}"))
+        .unwrap_or_else(|| "  · ".to_string())
+}
+
+fn hunk_syntax_colors(
+    app: &App,
+    relative_path: &str,
+    hunk: &crate::types::DiffHunk,
+) -> Option<Rc<HunkColors>> {
+    if app.config.display.syntax_highlighting {
+        app.syntax.highlight_hunk(relative_path, hunk)
+    } else {
+        None
+    }
+}
+
+/// How one side of a diff row is painted.
+struct SideStyles<'c> {
+    base: Style,
+    emphasis: Style,
+    colors: Option<&'c LineColors>,
+}
+
+/// Chooses between plain diff coloring and syntax coloring. With syntax
+/// colors the tokens keep their foreground and the diff color moves to a
+/// tinted background; themes without RGB colors keep plain coloring for
+/// changed lines.
+fn side_styles<'c>(
+    kind: Option<&DiffKind>,
+    colors: Option<&'c LineColors>,
+    content_style: Style,
+    theme: &Theme,
+) -> SideStyles<'c> {
+    let bold = ratatui::style::Modifier::BOLD;
+    let plain = SideStyles {
+        base: content_style,
+        emphasis: content_style.add_modifier(ratatui::style::Modifier::REVERSED | bold),
+        colors: None,
+    };
+    let (Some(kind), Some(colors)) = (kind, colors) else {
+        return plain;
+    };
+
+    if *kind == DiffKind::Context {
+        return SideStyles {
+            colors: Some(colors),
+            ..plain
+        };
+    }
+    match (
+        theme.diff_background(kind, false),
+        theme.diff_background(kind, true),
+    ) {
+        (Some(bg), Some(strong)) => SideStyles {
+            base: content_style.bg(bg),
+            emphasis: content_style.bg(strong).add_modifier(bold),
+            colors: Some(colors),
+        },
+        _ => plain,
+    }
+}
+
+/// Renders one side's visible content, padded to `width`. Changed words get
+/// the line color as a background so small edits stand out in long lines.
+fn content_spans<'a>(
+    content: &str,
+    emphasis: &[(usize, usize)],
+    offset: usize,
+    width: usize,
+    styles: SideStyles,
+) -> Vec<Span<'a>> {
+    let mut used = 0;
+    let mut spans: Vec<Span<'a>> = Vec::new();
+    for (text, emphasized) in split_emphasis(content, emphasis, offset, width) {
+        let style = if emphasized {
+            styles.emphasis
+        } else {
+            styles.base
+        };
+        let start = offset + used;
+        used += text.chars().count();
+        match styles.colors {
+            Some(colors) => {
+                for (run, color) in split_colors(&text, start, colors) {
+                    let run_style = color.map_or(style, |c| style.fg(c));
+                    spans.push(Span::styled(run, run_style));
+                }
+            }
+            None => spans.push(Span::styled(text, style)),
+        }
+    }
+    if used < width {
+        spans.push(Span::styled(" ".repeat(width - used), styles.base));
+    }
+    spans
+}
+
 fn get_line_styles(
     kind: Option<&DiffKind>,
     is_flashing: bool,
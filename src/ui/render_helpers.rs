@@ -118,18 +118,24 @@ mod tests {
                     new_line_number: Some(1),
                     kind: DiffKind::Context,
                     content: "a".to_string(),
+                    emphasis: Vec::new(),
+                    missing_newline: false,
                 },
                 DiffLine {
                     old_line_number: Some(2),
                     new_line_number: None,
                     kind: DiffKind::Deleted,
                     content: "b".to_string(),
+                    emphasis: Vec::new(),
+                    missing_newline: false,
                 },
                 DiffLine {
                     old_line_number: None,
                     new_line_number: Some(2),
                     kind: DiffKind::Added,
                     content: "c".to_string(),
+                    emphasis: Vec::new(),
+                    missing_newline: false,
                 },
             ],
         }];
@@ -150,6 +156,8 @@ mod tests {
                 new_line_number: Some(1),
                 kind: DiffKind::Context,
                 content: "a".to_string(),
+                emphasis: Vec::new(),
+                missing_newline: false,
             }],
         }];
 
@@ -173,12 +181,16 @@ mod tests {
                     new_line_number: Some(1),
                     kind: DiffKind::Context,
                     content: "a".to_string(),
+                    emphasis: Vec::new(),
+                    missing_newline: false,
                 },
                 DiffLine {
                     old_line_number: Some(2),
                     new_line_number: None,
                     kind: DiffKind::Deleted,
                     content: "b".to_string(),
+                    emphasis: Vec::new(),
+                    missing_newline: false,
                 },
             ],
         }];
@@ -0,0 +1,208 @@
+use ratatui::style::Color;
+use std::cell::RefCell;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::path::Path;
+use std::rc::Rc;
+use std::sync::OnceLock;
+use syntect::easy::HighlightLines;
+use syntect::highlighting::{Theme as SyntaxTheme, ThemeSet};
+use syntect::parsing::{SyntaxReference, SyntaxSet};
+
+use crate::types::{DiffHunk, DiffKind, DiffLine};
+
+/// Bundled syntect theme used for token colors; diff colors come from `Theme`.
+const SYNTAX_THEME: &str = "base16-ocean.dark";
+const MAX_CACHED_HUNKS: usize = 512;
+
+/// Foreground colors for one line as char ranges `[start, end)`.
+pub type LineColors = Vec<(usize, usize, Color)>;
+
+/// Token colors for both sides of a hunk, keyed by line number.
+#[derive(Debug, Default)]
+pub struct HunkColors {
+    pub old: HashMap<usize, LineColors>,
+    pub new: HashMap<usize, LineColors>,
+}
+
+struct Assets {
+    syntaxes: SyntaxSet,
+    theme: SyntaxTheme,
+}
+
+fn assets() -> &'static Assets {
+    static ASSETS: OnceLock<Assets> = OnceLock::new();
+    ASSETS.get_or_init(|| {
+        let mut themes = ThemeSet::load_defaults().themes;
+        Assets {
+            syntaxes: SyntaxSet::load_defaults_newlines(),
+            theme: themes.remove(SYNTAX_THEME).unwrap_or_default(),
+        }
+    })
+}
+
+/// Highlights hunks by the language of their file, caching the result so the
+/// render loop only pays for each hunk once.
+#[derive(Default)]
+pub struct SyntaxHighlighter {
+    cache: RefCell<HashMap<u64, Rc<HunkColors>>>,
+}
+
+impl SyntaxHighlighter {
+    /// Token colors for `hunk`, or `None` when the language of `relative_path`
+    /// is unknown.
+    pub fn highlight_hunk(&self, relative_path: &str, hunk: &DiffHunk) -> Option<Rc<HunkColors>> {
+        let assets = assets();
+        let syntax = find_syntax(&assets.syntaxes, relative_path)?;
+
+        let mut hasher = DefaultHasher::new();
+        syntax.name.hash(&mut hasher);
+        for line in &hunk.lines {
+            (line.old_line_number, line.new_line_number, &line.content).hash(&mut hasher);
+        }
+        let key = hasher.finish();
+
+        if let Some(colors) = self.cache.borrow().get(&key) {
+            return Some(Rc::clone(colors));
+        }
+
+        let old_side = hunk.lines.iter().filter(|l| l.kind != DiffKind::Added);
+        let new_side = hunk.lines.iter().filter(|l| l.kind != DiffKind::Deleted);
+        let colors = Rc::new(HunkColors {
+            old: highlight_side(assets, syntax, old_side, |l| l.old_line_number),
+            new: highlight_side(assets, syntax, new_side, |l| l.new_line_number),
+        });
+
+        let mut cache = self.cache.borrow_mut();
+        if cache.len() >= MAX_CACHED_HUNKS {
+            cache.clear();
+        }
+        cache.insert(key, Rc::clone(&colors));
+        Some(colors)
+    }
+}
+
+fn find_syntax<'a>(syntaxes: &'a SyntaxSet, relative_path: &str) -> Option<&'a SyntaxReference> {
+    let path = Path::new(relative_path);
+    let token = path.extension().or_else(|| path.file_name())?.to_str()?;
+    syntaxes.find_syntax_by_extension(token)
+}
+
+/// Highlights one side of a hunk as a continuous run of lines, so constructs
+/// spanning several lines keep their state within the hunk.
+fn highlight_side<'a>(
+    assets: &Assets,
+    syntax: &SyntaxReference,
+    lines: impl Iterator<Item = &'a DiffLine>,
+    line_number: impl Fn(&DiffLine) -> Option<usize>,
+) -> HashMap<usize, LineColors> {
+    let mut highlighter = HighlightLines::new(syntax, &assets.theme);
+    let mut result = HashMap::new();
+
+    for line in lines {
+        let text = format!("{}\n", line.content);
+        let Ok(regions) = highlighter.highlight_line(&text, &assets.syntaxes) else {
+            break;
+        };
+        let Some(number) = line_number(line) else {
+            continue;
+        };
+
+        let mut colors = LineColors::new();
+        let mut pos = 0;
+        for (style, piece) in regions {
+            let len = piece.trim_end_matches('\n').chars().count();
+            if len > 0 {
+                let fg = style.foreground;
+                colors.push((pos, pos + len, Color::Rgb(fg.r, fg.g, fg.b)));
+            }
+            pos += len;
+        }
+        result.insert(number, colors);
+    }
+
+    result
+}
+
+/// Splits `text`, which starts at char `start` of its line, into runs sharing
+/// the same token color.
+pub fn split_colors(
+    text: &str,
+    start: usize,
+    colors: &[(usize, usize, Color)],
+) -> Vec<(String, Option<Color>)> {
+    let mut segments: Vec<(String, Option<Color>)> = Vec::new();
+    for (i, ch) in text.chars().enumerate() {
+        let idx = start + i;
+        let color = colors
+            .iter()
+            .find(|&&(from, to, _)| idx >= from && idx < to)
+            .map(|&(_, _, color)| color);
+        match segments.last_mut() {
+            Some((run, run_color)) if *run_color == color => run.push(ch),
+            _ => segments.push((ch.to_string(), color)),
+        }
+    }
+    segments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(old: Option<usize>, new: Option<usize>, kind: DiffKind, content: &str) -> DiffLine {
+        DiffLine {
+            old_line_number: old,
+            new_line_number: new,
+            kind,
+            content: content.to_string(),
+            emphasis: Vec::new(),
+            missing_newline: false,
+        }
+    }
+
+    fn hunk() -> DiffHunk {
+        DiffHunk {
+            old_start: 1,
+            old_count: 2,
+            new_start: 1,
+            new_count: 2,
+            lines: vec![
+                line(Some(1), Some(1), DiffKind::Context, "fn main() {"),
+                line(Some(2), None, DiffKind::Deleted, "    let x = 1;"),
+                line(None, Some(2), DiffKind::Added, "    let x = 2;"),
+            ],
+        }
+    }
+
+    #[test]
+    fn test_highlight_known_language() {
+        let highlighter = SyntaxHighlighter::default();
+        let colors = highlighter.highlight_hunk("src/main.rs", &hunk()).unwrap();
+
+        assert!(colors.old.contains_key(&1));
+        assert!(colors.old.contains_key(&2));
+        assert!(colors.new.contains_key(&2));
+        let spans = &colors.new[&2];
+        assert_eq!(spans.last().unwrap().1, "    let x = 2;".len());
+    }
+
+    #[test]
+    fn test_unknown_language_falls_back() {
+        let highlighter = SyntaxHighlighter::default();
+        assert!(highlighter
+            .highlight_hunk("notes.unknownext", &hunk())
+            .is_none());
+    }
+
+    #[test]
+    fn test_split_colors_groups_runs() {
+        let red = Color::Rgb(255, 0, 0);
+        let segments = split_colors("let x", 2, &[(0, 3, red)]);
+        assert_eq!(
+            segments,
+            vec![("l".to_string(), Some(red)), ("et x".to_string(), None)]
+        );
+    }
+}
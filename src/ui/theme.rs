@@ -1,4 +1,11 @@
 use ratatui::style::Color;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::config::{Config, CustomColors, ThemeConfig};
+use crate::types::DiffKind;
 
 #[derive(Debug, Clone)]
 pub struct Theme {
@@ -29,6 +36,100 @@ impl Theme {
         }
     }
 
+    /// Resolve the theme selected in `config`, including a user-defined
+    /// `custom` theme. Colors that fail to parse are reported and left at the
+    /// base theme's value.
+    pub fn from_config(config: &ThemeConfig, registry: &ThemeRegistry) -> (Self, Vec<String>) {
+        match config.custom {
+            Some(ref custom) if custom.name().eq_ignore_ascii_case(&config.name) => {
+                Self::from_custom(custom, registry)
+            }
+            _ => match registry.get(&config.name) {
+                Some(theme) => (theme.clone(), Vec::new()),
+                None => (Self::by_name(&config.name), Vec::new()),
+            },
+        }
+    }
+
+    pub fn from_custom(custom: &CustomColors, registry: &ThemeRegistry) -> (Self, Vec<String>) {
+        let base = custom.base.as_deref().unwrap_or("nord");
+        let mut theme = registry
+            .get(base)
+            .cloned()
+            .unwrap_or_else(|| Self::by_name(base));
+        theme.name = custom.name().to_string();
+        let errors = theme.apply_custom(custom);
+        (theme, errors)
+    }
+
+    fn apply_custom(&mut self, custom: &CustomColors) -> Vec<String> {
+        let slots = [
+            ("added_line", &custom.added_line, &mut self.added),
+            ("deleted_line", &custom.deleted_line, &mut self.deleted),
+            ("context_line", &custom.context_line, &mut self.context),
+            ("line_number", &custom.line_number, &mut self.line_number),
+            ("border", &custom.border, &mut self.border),
+            (
+                "border_focused",
+                &custom.border_focused,
+                &mut self.border_focused,
+            ),
+            ("text", &custom.text, &mut self.text),
+            ("text_dim", &custom.text_dim, &mut self.text_dim),
+            ("background", &custom.background, &mut self.background),
+            ("header_bg", &custom.header_bg, &mut self.header_bg),
+            ("footer_bg", &custom.footer_bg, &mut self.footer_bg),
+            (
+                "status_paused",
+                &custom.status_paused,
+                &mut self.status_paused,
+            ),
+            (
+                "status_running",
+                &custom.status_running,
+                &mut self.status_running,
+            ),
+        ];
+
+        let mut errors = Vec::new();
+        for (field, value, slot) in slots {
+            if let Some(value) = value {
+                match parse_color(value) {
+                    Ok(color) => *slot = color,
+                    Err(e) => errors.push(format!("theme.custom.{field}: {e}")),
+                }
+            }
+        }
+        errors
+    }
+
+    /// Names listed in the theme selector: the built-ins, then theme files,
+    /// then the custom theme from the config.
+    pub fn names(config: &ThemeConfig, registry: &ThemeRegistry) -> Vec<String> {
+        let mut names: Vec<String> = Self::available_themes()
+            .into_iter()
+            .map(String::from)
+            .collect();
+        names.extend(registry.names().map(String::from));
+        if let Some(ref custom) = config.custom {
+            names.push(custom.name().to_string());
+        }
+        names
+    }
+
+    /// Background for a changed line when syntax colors take over the
+    /// foreground: the diff color mixed into the theme background, stronger
+    /// for emphasized words. `None` unless both colors are RGB.
+    pub fn diff_background(&self, kind: &DiffKind, emphasized: bool) -> Option<Color> {
+        let color = match kind {
+            DiffKind::Added => self.added,
+            DiffKind::Deleted => self.deleted,
+            DiffKind::Context => return None,
+        };
+        let amount = if emphasized { 0.45 } else { 0.2 };
+        blend(color, self.background, amount)
+    }
+
     pub fn available_themes() -> Vec<&'static str> {
         vec![
             "nord",
@@ -134,3 +235,324 @@ impl Theme {
         }
     }
 }
+
+/// On-disk theme definition, one `*.json` file per theme in the themes
+/// directory. Every color must be given.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ThemeFile {
+    pub name: String,
+    pub added: String,
+    pub deleted: String,
+    pub context: String,
+    pub line_number: String,
+    pub border: String,
+    pub border_focused: String,
+    pub text: String,
+    pub text_dim: String,
+    pub background: String,
+    pub header_bg: String,
+    pub footer_bg: String,
+    pub status_paused: String,
+    pub status_running: String,
+}
+
+impl ThemeFile {
+    pub fn into_theme(self) -> Result<Theme, String> {
+        let color =
+            |field: &str, value: &str| parse_color(value).map_err(|e| format!("{field}: {e}"));
+        Ok(Theme {
+            added: color("added", &self.added)?,
+            deleted: color("deleted", &self.deleted)?,
+            context: color("context", &self.context)?,
+            line_number: color("line_number", &self.line_number)?,
+            border: color("border", &self.border)?,
+            border_focused: color("border_focused", &self.border_focused)?,
+            text: color("text", &self.text)?,
+            text_dim: color("text_dim", &self.text_dim)?,
+            background: color("background", &self.background)?,
+            header_bg: color("header_bg", &self.header_bg)?,
+            footer_bg: color("footer_bg", &self.footer_bg)?,
+            status_paused: color("status_paused", &self.status_paused)?,
+            status_running: color("status_running", &self.status_running)?,
+            name: self.name,
+        })
+    }
+}
+
+/// Themes loaded from the themes directory, keyed by file stem.
+#[derive(Debug, Clone, Default)]
+pub struct ThemeRegistry {
+    themes: Vec<(String, Theme)>,
+    /// Files that could not be read or parsed.
+    pub errors: Vec<String>,
+    /// Directory the themes were read from; `None` for no user themes.
+    dir: Option<PathBuf>,
+}
+
+impl ThemeRegistry {
+    pub fn themes_dir() -> PathBuf {
+        Config::config_dir().join("themes")
+    }
+
+    pub fn load() -> Self {
+        Self::load_from(&Self::themes_dir())
+    }
+
+    pub fn load_from(dir: &Path) -> Self {
+        let mut registry = Self {
+            dir: Some(dir.to_path_buf()),
+            ..Self::default()
+        };
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(_) => return registry,
+        };
+
+        let mut paths: Vec<PathBuf> = entries
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| p.extension().is_some_and(|ext| ext == "json"))
+            .collect();
+        paths.sort();
+
+        for path in paths {
+            let key = match path.file_stem() {
+                Some(stem) => stem.to_string_lossy().to_lowercase(),
+                None => continue,
+            };
+            if Theme::available_themes().contains(&key.as_str()) {
+                registry
+                    .errors
+                    .push(format!("{}: shadows built-in theme", path.display()));
+                continue;
+            }
+            let theme = fs::read_to_string(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|content| {
+                    serde_json::from_str::<ThemeFile>(&content).map_err(|e| e.to_string())
+                })
+                .and_then(ThemeFile::into_theme);
+            match theme {
+                Ok(theme) => registry.themes.push((key, theme)),
+                Err(e) => {
+                    tracing::warn!("Failed to load theme {:?}: {}", path, e);
+                    registry.errors.push(format!("{}: {e}", path.display()));
+                }
+            }
+        }
+
+        registry
+    }
+
+    /// Read the themes again from the same directory, picking up edits.
+    pub fn reload(&self) -> Self {
+        match &self.dir {
+            Some(dir) => Self::load_from(dir),
+            None => Self::default(),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Theme> {
+        self.themes
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, theme)| theme)
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.themes.iter().map(|(key, _)| key.as_str())
+    }
+}
+
+/// Mix `amount` of `fg` into `bg`. Only RGB colors can be mixed.
+pub fn blend(fg: Color, bg: Color, amount: f32) -> Option<Color> {
+    match (fg, bg) {
+        (Color::Rgb(r1, g1, b1), Color::Rgb(r2, g2, b2)) => {
+            let mix = |a: u8, b: u8| (a as f32 * amount + b as f32 * (1.0 - amount)).round() as u8;
+            Some(Color::Rgb(mix(r1, r2), mix(g1, g2), mix(b1, b2)))
+        }
+        _ => None,
+    }
+}
+
+/// Parse a color given as `#rrggbb`, `#rgb`, a 256-color index or an ANSI
+/// color name.
+pub fn parse_color(s: &str) -> Result<Color, String> {
+    let s = s.trim();
+    if let Some(hex) = s.strip_prefix('#') {
+        let digits: Vec<u8> = hex
+            .chars()
+            .map(|c| c.to_digit(16).map(|d| d as u8))
+            .collect::<Option<_>>()
+            .ok_or_else(|| format!("invalid hex color '{s}'"))?;
+        return match digits.as_slice() {
+            [r, g, b] => Ok(Color::Rgb(r * 17, g * 17, b * 17)),
+            [r1, r2, g1, g2, b1, b2] => Ok(Color::Rgb(r1 * 16 + r2, g1 * 16 + g2, b1 * 16 + b2)),
+            _ => Err(format!(
+                "invalid hex color '{s}' (expected #rgb or #rrggbb)"
+            )),
+        };
+    }
+    if s.chars().all(|c| c.is_ascii_digit()) && !s.is_empty() {
+        return s
+            .parse::<u8>()
+            .map(Color::Indexed)
+            .map_err(|_| format!("color index '{s}' out of range (0-255)"));
+    }
+    Color::from_str(s).map_err(|_| format!("unknown color '{s}'"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blend_mixes_rgb_only() {
+        let mixed = blend(Color::Rgb(200, 100, 0), Color::Rgb(0, 0, 0), 0.5);
+        assert_eq!(mixed, Some(Color::Rgb(100, 50, 0)));
+        assert_eq!(blend(Color::Green, Color::Black, 0.5), None);
+        assert!(Theme::monochrome()
+            .diff_background(&DiffKind::Added, false)
+            .is_none());
+        assert!(Theme::nord()
+            .diff_background(&DiffKind::Context, false)
+            .is_none());
+    }
+
+    #[test]
+    fn test_parse_color_hex() {
+        assert_eq!(parse_color("#a3be8c"), Ok(Color::Rgb(163, 190, 140)));
+        assert_eq!(parse_color("#fff"), Ok(Color::Rgb(255, 255, 255)));
+        assert!(parse_color("#12345").is_err());
+        assert!(parse_color("#zzzzzz").is_err());
+    }
+
+    #[test]
+    fn test_parse_color_named_and_indexed() {
+        assert_eq!(parse_color("red"), Ok(Color::Red));
+        assert_eq!(parse_color("Dark Gray"), Ok(Color::DarkGray));
+        assert_eq!(parse_color("208"), Ok(Color::Indexed(208)));
+        assert!(parse_color("256").is_err());
+        assert!(parse_color("chartreuse-ish").is_err());
+    }
+
+    #[test]
+    fn test_from_config_applies_custom_over_base() {
+        let config = ThemeConfig {
+            name: "custom".to_string(),
+            custom: Some(CustomColors {
+                base: Some("dracula".to_string()),
+                added_line: Some("#00ff00".to_string()),
+                ..Default::default()
+            }),
+        };
+        let (theme, errors) = Theme::from_config(&config, &ThemeRegistry::default());
+        assert!(errors.is_empty());
+        assert_eq!(theme.name, "custom");
+        assert_eq!(theme.added, Color::Rgb(0, 255, 0));
+        assert_eq!(theme.deleted, Theme::dracula_modified().deleted);
+    }
+
+    #[test]
+    fn test_from_config_reports_invalid_colors() {
+        let config = ThemeConfig {
+            name: "mine".to_string(),
+            custom: Some(CustomColors {
+                name: Some("mine".to_string()),
+                text: Some("not-a-color".to_string()),
+                ..Default::default()
+            }),
+        };
+        let (theme, errors) = Theme::from_config(&config, &ThemeRegistry::default());
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].contains("theme.custom.text"));
+        assert_eq!(theme.text, Theme::nord().text);
+    }
+
+    #[test]
+    fn test_custom_not_applied_to_builtin_selection() {
+        let config = ThemeConfig {
+            name: "monochrome".to_string(),
+            custom: Some(CustomColors {
+                added_line: Some("#00ff00".to_string()),
+                ..Default::default()
+            }),
+        };
+        let (theme, _) = Theme::from_config(&config, &ThemeRegistry::default());
+        assert_eq!(theme.name, "Monochrome");
+        assert_eq!(
+            Theme::names(&config, &ThemeRegistry::default())
+                .last()
+                .unwrap(),
+            "custom"
+        );
+    }
+
+    fn write_theme_file(dir: &Path, stem: &str, added: &str) {
+        let file = ThemeFile {
+            name: "House".to_string(),
+            added: added.to_string(),
+            deleted: "#bf616a".to_string(),
+            context: "darkgray".to_string(),
+            line_number: "cyan".to_string(),
+            border: "gray".to_string(),
+            border_focused: "white".to_string(),
+            text: "white".to_string(),
+            text_dim: "gray".to_string(),
+            background: "black".to_string(),
+            header_bg: "236".to_string(),
+            footer_bg: "236".to_string(),
+            status_paused: "yellow".to_string(),
+            status_running: "green".to_string(),
+        };
+        let content = serde_json::to_string_pretty(&file).unwrap();
+        fs::write(dir.join(format!("{stem}.json")), content).unwrap();
+    }
+
+    #[test]
+    fn test_registry_loads_theme_files() {
+        let temp = tempfile::TempDir::new().unwrap();
+        write_theme_file(temp.path(), "house", "#00ff00");
+        fs::write(temp.path().join("notes.txt"), "ignored").unwrap();
+
+        let registry = ThemeRegistry::load_from(temp.path());
+        assert!(registry.errors.is_empty(), "{:?}", registry.errors);
+        assert_eq!(registry.names().collect::<Vec<_>>(), vec!["house"]);
+
+        let config = ThemeConfig {
+            name: "house".to_string(),
+            custom: None,
+        };
+        let (theme, _) = Theme::from_config(&config, &registry);
+        assert_eq!(theme.name, "House");
+        assert_eq!(theme.added, Color::Rgb(0, 255, 0));
+        assert!(Theme::names(&config, &registry).contains(&"house".to_string()));
+    }
+
+    #[test]
+    fn test_registry_reports_bad_files() {
+        let temp = tempfile::TempDir::new().unwrap();
+        write_theme_file(temp.path(), "broken", "not-a-color");
+        write_theme_file(temp.path(), "nord", "#00ff00");
+        fs::write(temp.path().join("partial.json"), r#"{"name": "x"}"#).unwrap();
+
+        let registry = ThemeRegistry::load_from(temp.path());
+        assert_eq!(registry.names().count(), 0);
+        assert_eq!(registry.errors.len(), 3);
+    }
+
+    #[test]
+    fn test_registry_reload_reads_the_same_dir() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let registry = ThemeRegistry::load_from(temp.path());
+        write_theme_file(temp.path(), "house", "#00ff00");
+        assert_eq!(registry.reload().names().collect::<Vec<_>>(), vec!["house"]);
+        assert_eq!(ThemeRegistry::default().reload().names().count(), 0);
+    }
+
+    #[test]
+    fn test_registry_missing_dir_is_empty() {
+        let registry = ThemeRegistry::load_from(Path::new("/nonexistent/gwatch/themes"));
+        assert_eq!(registry.names().count(), 0);
+        assert!(registry.errors.is_empty());
+    }
+}
@@ -1,10 +1,12 @@
 use anyhow::Result;
-use ignore::gitignore::Gitignore;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::{Match, WalkBuilder};
 use notify::{Config, RecommendedWatcher, RecursiveMode, Watcher};
 use std::collections::HashMap;
 use std::panic::AssertUnwindSafe;
 use std::path::{Path, PathBuf};
-use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock as SyncRwLock};
 use std::time::{Duration, Instant, SystemTime};
 use tokio::runtime::Handle;
 use tokio::sync::mpsc;
@@ -27,14 +29,14 @@ impl FileWatcher {
         let last_events: Arc<RwLock<HashMap<PathBuf, Instant>>> =
             Arc::new(RwLock::new(HashMap::new()));
 
-        let gitignore = load_gitignore(&repo_root);
+        let rules = Arc::new(SyncRwLock::new(IgnoreRules::load(&repo_root)));
+        let global_excludes = rules
+            .read()
+            .ok()
+            .and_then(|r| r.global_path().map(Path::to_path_buf));
         let git_dir = repo_root.join(".git");
 
-        let extra_ignores: Vec<glob::Pattern> = config
-            .ignore_patterns
-            .iter()
-            .filter_map(|p| glob::Pattern::new(p).ok())
-            .collect();
+        let extra_ignores = ExtraIgnores::new(&config.ignore_patterns);
 
         let repo_root_clone = repo_root.clone();
         let last_events_clone = last_events.clone();
@@ -45,7 +47,7 @@ impl FileWatcher {
                 let tx = tx.clone();
                 let git_dir = git_dir.clone();
                 let repo_root_clone = repo_root_clone.clone();
-                let gitignore = gitignore.clone();
+                let rules = rules.clone();
                 let extra_ignores = extra_ignores.clone();
                 let last_events_clone = last_events_clone.clone();
                 let handle = handle.clone();
@@ -59,19 +61,40 @@ impl FileWatcher {
                         }
                     };
 
+                    // Renames arrive as `Modify(Name(_))`; each side is then
+                    // handled as a change or a removal of its own path, and
+                    // the git engine pairs them up by content similarity.
                     if !matches!(
                         event.kind,
-                        notify::EventKind::Modify(_) | notify::EventKind::Create(_)
+                        notify::EventKind::Modify(_)
+                            | notify::EventKind::Create(_)
+                            | notify::EventKind::Remove(_)
                     ) {
                         return;
                     }
 
+                    let rules_changed = rules
+                        .read()
+                        .map(|r| event.paths.iter().any(|p| r.is_rules_file(p)))
+                        .unwrap_or(false);
+                    if rules_changed {
+                        let rules = rules.clone();
+                        let repo_root = repo_root_clone.clone();
+                        handle.spawn_blocking(move || {
+                            let rebuilt = IgnoreRules::load(&repo_root);
+                            if let Ok(mut rules) = rules.write() {
+                                *rules = rebuilt;
+                                tracing::info!("Ignore rules reloaded");
+                            }
+                        });
+                    }
+
                     for path in event.paths {
-                        if !path.exists() || !path.is_file() {
+                        if path.is_dir() {
                             continue;
                         }
 
-                        if path.starts_with(&git_dir) {
+                        if path.starts_with(&git_dir) || !path.starts_with(&repo_root_clone) {
                             continue;
                         }
 
@@ -80,14 +103,11 @@ impl FileWatcher {
                             Err(_) => path.clone(),
                         };
 
-                        if let Some(ref gi) = gitignore {
-                            if gi.matched_path_or_any_parents(&relative, false).is_ignore() {
-                                continue;
-                            }
+                        if rules.read().map(|r| r.is_ignored(&path)).unwrap_or(false) {
+                            continue;
                         }
 
-                        let relative_str = relative.to_string_lossy();
-                        if extra_ignores.iter().any(|p| p.matches(&relative_str)) {
+                        if extra_ignores.matches(&relative) {
                             continue;
                         }
 
@@ -113,7 +133,7 @@ impl FileWatcher {
 
                             tokio::time::sleep(debounce).await;
 
-                            if path.exists() {
+                            if !path.is_dir() {
                                 let _ = tx.send(FileChangeEvent {
                                     path,
                                     timestamp: SystemTime::now(),
@@ -132,16 +152,218 @@ impl FileWatcher {
 
         watcher.watch(&repo_root, RecursiveMode::Recursive)?;
 
+        // The global excludes file usually lives outside the repository.
+        if let Some(dir) = global_excludes.as_deref().and_then(Path::parent) {
+            if !dir.starts_with(&repo_root) {
+                if let Err(e) = watcher.watch(dir, RecursiveMode::NonRecursive) {
+                    tracing::debug!("Not watching global excludes in {:?}: {}", dir, e);
+                }
+            }
+        }
+
+        Ok(Self { _watcher: watcher })
+    }
+}
+
+/// Glob patterns from `watcher.ignore_patterns`, matched against paths
+/// relative to the repository root on top of git's own ignore rules.
+#[derive(Debug, Clone, Default)]
+pub struct ExtraIgnores(Vec<glob::Pattern>);
+
+impl ExtraIgnores {
+    pub fn new(patterns: &[String]) -> Self {
+        Self(
+            patterns
+                .iter()
+                .filter_map(|p| glob::Pattern::new(p).ok())
+                .collect(),
+        )
+    }
+
+    pub fn matches(&self, relative: &Path) -> bool {
+        let relative = relative.to_string_lossy();
+        self.0.iter().any(|p| p.matches(&relative))
+    }
+}
+
+/// Watches the parts of `.git` that move the base of every diff: `HEAD`, the
+/// index and refs. Commits, `git add`, checkouts and rebases each touch several
+/// of them, so notifications are coalesced into one per burst.
+pub struct RepoStateWatcher {
+    _watcher: RecommendedWatcher,
+}
+
+impl RepoStateWatcher {
+    pub fn new(
+        git_dir: &Path,
+        common_dir: &Path,
+        config: &WatcherConfig,
+        tx: mpsc::UnboundedSender<()>,
+    ) -> Result<Self> {
+        let debounce = Duration::from_millis(config.debounce_ms);
+        let generation = Arc::new(AtomicU64::new(0));
+        let handle = Handle::current();
+        let refs_dir = common_dir.join("refs");
+
+        let watched_refs = refs_dir.clone();
+        let mut watcher = RecommendedWatcher::new(
+            move |res: Result<notify::Event, notify::Error>| {
+                let event = match res {
+                    Ok(e) => e,
+                    Err(e) => {
+                        tracing::warn!("notify error: {}", e);
+                        return;
+                    }
+                };
+                if matches!(event.kind, notify::EventKind::Access(_))
+                    || !event.paths.iter().any(|p| is_repo_state(p, &watched_refs))
+                {
+                    return;
+                }
+
+                let current = generation.fetch_add(1, Ordering::SeqCst) + 1;
+                let generation = generation.clone();
+                let tx = tx.clone();
+                handle.spawn(async move {
+                    tokio::time::sleep(debounce).await;
+                    if generation.load(Ordering::SeqCst) == current {
+                        let _ = tx.send(());
+                    }
+                });
+            },
+            Config::default(),
+        )?;
+
+        watcher.watch(git_dir, RecursiveMode::NonRecursive)?;
+        if common_dir != git_dir {
+            watcher.watch(common_dir, RecursiveMode::NonRecursive)?;
+        }
+        if refs_dir.is_dir() {
+            watcher.watch(&refs_dir, RecursiveMode::Recursive)?;
+        }
+
         Ok(Self { _watcher: watcher })
     }
 }
 
-fn load_gitignore(repo_root: &Path) -> Option<Gitignore> {
-    let gitignore_path = repo_root.join(".gitignore");
-    if gitignore_path.exists() {
-        let (gi, _) = Gitignore::new(&gitignore_path);
-        Some(gi)
-    } else {
-        None
+/// Whether `path` is one of the files git updates when HEAD, the index or a
+/// ref moves. Lock files are skipped; git renames them into place when done.
+fn is_repo_state(path: &Path, refs_dir: &Path) -> bool {
+    let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+        return false;
+    };
+    if name.ends_with(".lock") {
+        return false;
     }
+    matches!(name, "HEAD" | "index" | "packed-refs") || path.starts_with(refs_dir)
+}
+
+/// Ignore rules gathered the way git does. `.gitignore` files apply to their
+/// own directory and below, with deeper files taking precedence; then come
+/// `.git/info/exclude` and the `core.excludesFile` global file.
+pub struct IgnoreRules {
+    repo_root: PathBuf,
+    /// One matcher per `.gitignore`, deepest directory first.
+    nested: Vec<Gitignore>,
+    exclude: Option<Gitignore>,
+    global: Option<Gitignore>,
+    global_path: Option<PathBuf>,
+}
+
+impl IgnoreRules {
+    pub fn load(repo_root: &Path) -> Self {
+        let mut nested: Vec<Gitignore> = WalkBuilder::new(repo_root)
+            .hidden(false)
+            .ignore(false)
+            .filter_entry(|e| e.file_name() != ".git")
+            .build()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_name() == ".gitignore")
+            .filter_map(|entry| {
+                let (gi, err) = Gitignore::new(entry.path());
+                if let Some(e) = err {
+                    tracing::warn!("Problem reading {:?}: {}", entry.path(), e);
+                }
+                (!gi.is_empty()).then_some(gi)
+            })
+            .collect();
+        nested.sort_by_key(|gi| std::cmp::Reverse(gi.path().components().count()));
+
+        let global_path = global_excludes_path(repo_root);
+        Self {
+            repo_root: repo_root.to_path_buf(),
+            nested,
+            exclude: load_excludes(repo_root, &repo_root.join(".git/info/exclude")),
+            global: global_path
+                .as_deref()
+                .and_then(|p| load_excludes(repo_root, p)),
+            global_path,
+        }
+    }
+
+    /// Whether git would ignore `path`, given as an absolute path inside the
+    /// repository.
+    pub fn is_ignored(&self, path: &Path) -> bool {
+        if !path.starts_with(&self.repo_root) {
+            return false;
+        }
+        let matchers = self
+            .nested
+            .iter()
+            .chain(self.exclude.as_ref())
+            .chain(self.global.as_ref());
+        for gi in matchers {
+            // `matched_path_or_any_parents` panics on paths outside the
+            // matcher's root.
+            let Ok(local) = path.strip_prefix(gi.path()) else {
+                continue;
+            };
+            match gi.matched_path_or_any_parents(local, false) {
+                Match::None => continue,
+                m => return m.is_ignore(),
+            }
+        }
+        false
+    }
+
+    /// Whether a change to `path` means the rules have to be rebuilt.
+    pub fn is_rules_file(&self, path: &Path) -> bool {
+        path.file_name().is_some_and(|n| n == ".gitignore")
+            || path == self.repo_root.join(".git/info/exclude")
+            || self.global_path.as_deref() == Some(path)
+    }
+
+    pub fn global_path(&self) -> Option<&Path> {
+        self.global_path.as_deref()
+    }
+}
+
+/// Matcher for an excludes file whose patterns are relative to the
+/// repository root, as for `.git/info/exclude` and `core.excludesFile`.
+fn load_excludes(repo_root: &Path, file: &Path) -> Option<Gitignore> {
+    if !file.is_file() {
+        return None;
+    }
+    let mut builder = GitignoreBuilder::new(repo_root);
+    if let Some(e) = builder.add(file) {
+        tracing::warn!("Problem reading {:?}: {}", file, e);
+    }
+    builder.build().ok().filter(|gi| !gi.is_empty())
+}
+
+/// `core.excludesFile` from the repository's git config, or git's default of
+/// `$XDG_CONFIG_HOME/git/ignore`.
+fn global_excludes_path(repo_root: &Path) -> Option<PathBuf> {
+    let configured = git2::Repository::open(repo_root)
+        .and_then(|repo| repo.config())
+        .or_else(|_| git2::Config::open_default())
+        .ok()
+        .and_then(|config| config.get_path("core.excludesFile").ok());
+    configured.or_else(|| {
+        std::env::var_os("XDG_CONFIG_HOME")
+            .filter(|v| !v.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| dirs::home_dir().map(|home| home.join(".config")))
+            .map(|base| base.join("git").join("ignore"))
+    })
 }
@@ -1,7 +1,11 @@
 use gwatch::config::Config;
 use gwatch::review_state::ReviewState;
-use gwatch::types::{DisplayedEvent, FileDiff};
-use gwatch::ui::app::{App, AppState};
+use gwatch::types::{
+    CommitInfo, DiffHunk, DiffKind, DiffLine, DiffMode, DisplayedEvent, FileDiff, IndexAction,
+    SaveSnapshot, StagedFile,
+};
+use gwatch::ui::app::{App, AppState, LineSelection};
+use gwatch::ui::theme::ThemeRegistry;
 use std::path::PathBuf;
 use std::time::{Duration, Instant};
 
@@ -10,6 +14,7 @@ fn test_app() -> App {
         Config::default(),
         PathBuf::from("/tmp/test"),
         ReviewState::default(),
+        ThemeRegistry::default(),
     )
 }
 
@@ -24,6 +29,8 @@ fn test_add_event_respects_max_events() {
             relative_path: format!("file{i}.rs"),
             timestamp: chrono::Utc::now(),
             diff: FileDiff::default(),
+            mode: DiffMode::All,
+            snapshot: SaveSnapshot::default(),
         });
     }
 
@@ -38,6 +45,8 @@ fn test_add_event_triggers_flash() {
         relative_path: "file.rs".to_string(),
         timestamp: chrono::Utc::now(),
         diff: FileDiff::default(),
+        mode: DiffMode::All,
+        snapshot: SaveSnapshot::default(),
     });
 
     assert!(app.is_flashing());
@@ -105,7 +114,411 @@ fn test_clear_history() {
         relative_path: "file.rs".to_string(),
         timestamp: chrono::Utc::now(),
         diff: FileDiff::default(),
+        mode: DiffMode::All,
+        snapshot: SaveSnapshot::default(),
     });
     app.clear_history();
     assert!(app.events.is_empty());
 }
+
+#[test]
+fn test_save_settings_rejects_invalid_custom_color() {
+    let mut app = test_app();
+    let mut config = Config::default();
+    config.theme.name = "custom".to_string();
+    config.theme.custom = Some(gwatch::config::CustomColors {
+        added_line: Some("#12".to_string()),
+        ..Default::default()
+    });
+    app.settings_editor.content = serde_json::to_string(&config).unwrap();
+
+    assert!(!app.save_settings());
+    let error = app.settings_editor.error_message.clone().unwrap();
+    assert!(error.contains("theme.custom.added_line"));
+}
+
+fn event(name: &str) -> DisplayedEvent {
+    DisplayedEvent {
+        file_path: PathBuf::from(format!("/test/{name}")),
+        relative_path: name.to_string(),
+        timestamp: chrono::Utc::now(),
+        diff: FileDiff::default(),
+        mode: DiffMode::All,
+        snapshot: SaveSnapshot::default(),
+    }
+}
+
+#[test]
+fn test_events_queue_while_paused() {
+    let mut app = test_app();
+    app.add_event(event("first.rs"));
+    app.toggle_pause();
+
+    app.add_event(event("second.rs"));
+    app.add_event(event("third.rs"));
+    assert_eq!(app.events.len(), 1);
+    assert_eq!(app.pending_events.len(), 2);
+
+    app.toggle_pause();
+    assert!(app.pending_events.is_empty());
+    let order: Vec<_> = app
+        .events
+        .iter()
+        .map(|e| e.relative_path.as_str())
+        .collect();
+    assert_eq!(order, ["third.rs", "second.rs", "first.rs"]);
+    assert_eq!(app.scroll_offset, 0);
+}
+
+#[test]
+fn test_resume_can_keep_selection() {
+    let mut app = test_app();
+    app.config.display.jump_to_newest_on_resume = false;
+    app.add_event(event("old.rs"));
+    app.add_event(event("selected.rs"));
+    app.toggle_pause();
+
+    app.add_event(event("new.rs"));
+    app.toggle_pause();
+    assert_eq!(
+        app.get_current_event().unwrap().relative_path,
+        "selected.rs"
+    );
+}
+
+#[test]
+fn test_seed_event_keeps_time_order_without_flash() {
+    let mut app = test_app();
+    let now = chrono::Utc::now();
+    let at = |name: &str, secs: i64| DisplayedEvent {
+        timestamp: now - chrono::Duration::seconds(secs),
+        ..event(name)
+    };
+
+    app.seed_event(at("middle.rs", 20));
+    app.seed_event(at("newest.rs", 10));
+    app.seed_event(at("oldest.rs", 30));
+
+    let order: Vec<_> = app
+        .events
+        .iter()
+        .map(|e| e.relative_path.as_str())
+        .collect();
+    assert_eq!(order, ["newest.rs", "middle.rs", "oldest.rs"]);
+    assert!(!app.is_flashing());
+    assert_eq!(app.get_current_event().unwrap().relative_path, "middle.rs");
+}
+
+#[test]
+fn test_start_replay_begins_at_oldest() {
+    let mut app = test_app();
+    app.max_events = 2;
+    let events = vec![event("first.rs"), event("second.rs"), event("third.rs")];
+    app.start_replay(PathBuf::from("session.jsonl"), events);
+
+    assert!(app.is_replay());
+    assert_eq!(app.events.len(), 3);
+    assert_eq!(app.get_current_event().unwrap().relative_path, "first.rs");
+}
+
+fn line(kind: DiffKind) -> DiffLine {
+    DiffLine {
+        old_line_number: None,
+        new_line_number: None,
+        kind,
+        content: String::new(),
+        emphasis: Vec::new(),
+        missing_newline: false,
+    }
+}
+
+fn event_with_lines(kinds: &[DiffKind]) -> DisplayedEvent {
+    DisplayedEvent {
+        diff: FileDiff {
+            hunks: vec![DiffHunk {
+                lines: kinds.iter().cloned().map(line).collect(),
+                ..DiffHunk::default()
+            }],
+            ..FileDiff::default()
+        },
+        mode: DiffMode::Unstaged,
+        ..event("lib.rs")
+    }
+}
+
+#[test]
+fn test_index_action_follows_mode() {
+    use DiffKind::{Added, Context};
+    let mut app = test_app();
+    app.add_event(DisplayedEvent {
+        mode: DiffMode::Staged,
+        ..event_with_lines(&[Context, Added])
+    });
+
+    app.toggle_line_selection();
+    app.request_index_action(IndexAction::Stage);
+    assert!(app.take_index_requests().is_empty());
+    assert!(app.status_message().unwrap().is_error);
+
+    app.request_index_action(IndexAction::Unstage);
+    let requests = app.take_index_requests();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].target(), "1 line of hunk 1/1 of lib.rs");
+}
+
+#[test]
+fn test_line_selection_moves_within_hunk() {
+    use DiffKind::{Added, Context, Deleted};
+    let mut app = test_app();
+    app.add_event(event_with_lines(&[Context, Deleted, Added, Added, Context]));
+
+    app.toggle_line_selection();
+    let selection = app.hunk_state.selection.unwrap();
+    assert_eq!((selection.anchor, selection.cursor), (1, 1));
+
+    assert!(app.move_selection(true));
+    assert!(app.move_selection(true));
+    assert!(app.move_selection(true));
+    assert!(!app.move_selection(true));
+    assert_eq!(app.hunk_state.selection.unwrap().range(), 1..=4);
+
+    // Hidden context lines are skipped, and the cursor leaves them.
+    app.toggle_context_collapsed();
+    assert_eq!(app.hunk_state.selection.unwrap().cursor, 3);
+    assert!(app.move_selection(false));
+    assert!(app.move_selection(false));
+    assert!(!app.move_selection(false));
+
+    app.toggle_line_selection();
+    assert!(!app.is_selecting());
+}
+
+#[test]
+fn test_index_action_on_selected_lines() {
+    use DiffKind::{Added, Context, Deleted};
+    let mut app = test_app();
+    app.add_event(event_with_lines(&[Context, Deleted, Added, Added, Context]));
+
+    app.toggle_line_selection();
+    app.move_selection(true);
+    app.request_index_action(IndexAction::Stage);
+    let requests = app.take_index_requests();
+    assert_eq!((requests[0].hunk, requests[0].lines.clone()), (0, 1..=2));
+    assert_eq!(requests[0].target(), "2 lines of hunk 1/1 of lib.rs");
+    assert!(!app.is_selecting());
+
+    // Only context selected.
+    app.hunk_state.selection = Some(LineSelection {
+        anchor: 0,
+        cursor: 0,
+    });
+    app.request_index_action(IndexAction::Stage);
+    assert!(app.take_index_requests().is_empty());
+    assert!(app.status_message().unwrap().is_error);
+
+    // Nothing is staged without a selection.
+    app.cancel_line_selection();
+    app.request_index_action(IndexAction::Stage);
+    assert!(app.take_index_requests().is_empty());
+    assert!(app.status_message().unwrap().text.contains("Select lines"));
+}
+
+#[test]
+fn test_index_action_needs_the_newest_save() {
+    use DiffKind::{Added, Context};
+    let mut app = test_app();
+    let older = event_with_lines(&[Context, Added]);
+    app.add_event(older.clone());
+    app.add_event(DisplayedEvent {
+        timestamp: older.timestamp + chrono::Duration::seconds(1),
+        ..older.clone()
+    });
+
+    // The older save no longer matches the working tree.
+    app.scroll_up();
+    app.toggle_line_selection();
+    app.request_index_action(IndexAction::Stage);
+    assert!(app.take_index_requests().is_empty());
+    assert!(app.status_message().unwrap().text.contains("changed since"));
+
+    // Nor does the newest shown save once a paused one is pending.
+    app.scroll_down();
+    app.toggle_pause();
+    app.add_event(DisplayedEvent {
+        timestamp: older.timestamp + chrono::Duration::seconds(2),
+        ..older
+    });
+    app.toggle_line_selection();
+    app.request_index_action(IndexAction::Stage);
+    assert!(app.take_index_requests().is_empty());
+
+    app.toggle_pause();
+    app.toggle_line_selection();
+    app.request_index_action(IndexAction::Stage);
+    assert_eq!(app.take_index_requests().len(), 1);
+}
+
+fn commit_info(staged: &[&str]) -> CommitInfo {
+    CommitInfo {
+        staged: staged
+            .iter()
+            .map(|path| StagedFile {
+                status: 'M',
+                path: path.to_string(),
+            })
+            .collect(),
+        signature: Ok("Test <test@example.com>".to_string()),
+        head_message: Some("Previous commit\n".to_string()),
+    }
+}
+
+#[test]
+fn test_submit_commit_checks_before_queueing() {
+    let mut app = test_app();
+    app.open_commit_editor();
+    assert_eq!(app.state, AppState::CommitEditor);
+    assert!(app.take_commit_info_request());
+
+    app.commit_editor.message = "Subject".to_string();
+    app.submit_commit();
+    assert!(app.take_commit_request().is_none());
+    assert!(app.commit_editor.error_message.is_some());
+
+    app.set_commit_info(commit_info(&[]));
+    app.submit_commit();
+    assert_eq!(
+        app.commit_editor.error_message.as_deref(),
+        Some("Nothing staged to commit")
+    );
+
+    app.set_commit_info(commit_info(&["a.txt"]));
+    app.commit_editor.message = " \n".to_string();
+    app.submit_commit();
+    assert_eq!(
+        app.commit_editor.error_message.as_deref(),
+        Some("The commit message is empty")
+    );
+
+    app.commit_editor.message = "Subject".to_string();
+    app.submit_commit();
+    let request = app.take_commit_request().unwrap();
+    assert_eq!(request.message, "Subject");
+    assert!(!request.amend);
+
+    app.finish_commit(Err("hook failed".to_string()));
+    assert_eq!(app.state, AppState::CommitEditor);
+    app.finish_commit(Ok("abc1234".to_string()));
+    assert_eq!(app.state, AppState::Running);
+    assert!(app.commit_editor.message.is_empty());
+    assert_eq!(app.status_message().unwrap().text, "Committed abc1234");
+}
+
+#[test]
+fn test_toggle_amend_fills_in_the_last_message() {
+    let mut app = test_app();
+    app.open_commit_editor();
+    app.set_commit_info(commit_info(&[]));
+
+    app.toggle_amend();
+    assert!(app.commit_editor.amend);
+    assert_eq!(app.commit_editor.message, "Previous commit");
+    // Amending needs nothing staged.
+    app.submit_commit();
+    assert!(app.take_commit_request().unwrap().amend);
+
+    app.toggle_amend();
+    assert!(!app.commit_editor.amend);
+    assert!(app.commit_editor.message.is_empty());
+
+    app.commit_editor.message = "Draft".to_string();
+    app.toggle_amend();
+    assert_eq!(app.commit_editor.message, "Draft");
+
+    let mut app = test_app();
+    app.open_commit_editor();
+    app.set_commit_info(CommitInfo {
+        head_message: None,
+        ..commit_info(&[])
+    });
+    app.toggle_amend();
+    assert!(!app.commit_editor.amend);
+    assert!(app.commit_editor.error_message.is_some());
+}
+
+fn changed_hunk(content: &str) -> DiffHunk {
+    DiffHunk {
+        lines: vec![DiffLine {
+            content: content.to_string(),
+            ..line(DiffKind::Added)
+        }],
+        ..DiffHunk::default()
+    }
+}
+
+#[test]
+fn test_review_marks_follow_hunk_content() {
+    let mut app = test_app();
+    let mut event = event("lib.rs");
+    event.diff.hunks = vec![changed_hunk("one"), changed_hunk("two")];
+    app.add_event(event.clone());
+    assert_eq!(app.review_progress(), Some((0, 2)));
+
+    app.hunk_state.focused_hunk = 1;
+    app.toggle_focused_hunk_reviewed();
+    assert_eq!(app.review_progress(), Some((1, 2)));
+    assert!(app.is_hunk_reviewed(&event, &event.diff.hunks[1]));
+    assert!(!app.is_event_reviewed(&event));
+
+    app.toggle_current_reviewed();
+    assert_eq!(app.review_progress(), Some((2, 2)));
+    assert!(app.is_event_reviewed(&event));
+
+    // Editing the second hunk voids its mark but not the first one's.
+    let mut edited = event.clone();
+    edited.diff.hunks[1] = changed_hunk("two, edited");
+    app.add_event(edited.clone());
+    assert_eq!(app.review_progress(), Some((1, 2)));
+    assert!(!app.is_event_reviewed(&edited));
+
+    app.clear_all_reviewed();
+    assert_eq!(app.review_progress(), Some((0, 2)));
+}
+
+#[test]
+fn test_notes_on_hunks_and_lines() {
+    use DiffKind::{Added, Context, Deleted};
+    let mut app = test_app();
+    let event = event_with_lines(&[Context, Deleted, Added, Context]);
+    let hunk = event.diff.hunks[0].clone();
+    app.add_event(event.clone());
+
+    app.open_note_editor();
+    assert_eq!(app.state, AppState::NoteEditor);
+    app.note_editor.text = "  Whole hunk \n".to_string();
+    app.save_note();
+    assert_eq!(app.state, AppState::Running);
+    assert_eq!(app.review_state.notes[0].text, "Whole hunk");
+    assert!(app.review_state.notes[0].line.is_none());
+
+    app.toggle_line_selection();
+    app.move_selection(true);
+    app.open_note_editor();
+    assert!(app.note_editor.text.is_empty());
+    app.note_editor.text = "This line".to_string();
+    app.save_note();
+    assert!(!app.is_selecting());
+
+    let notes = app.hunk_notes(&event, &hunk);
+    assert_eq!(notes.len(), 2);
+    assert_eq!(notes[1].line_index(&hunk), Some(2));
+
+    // Opening the editor again on the hunk edits its note; saving it empty
+    // deletes it.
+    app.open_note_editor();
+    assert_eq!(app.note_editor.text, "Whole hunk");
+    assert_eq!(app.note_editor.cursor_col, 10);
+    app.note_editor.text.clear();
+    app.save_note();
+    assert_eq!(app.review_state.notes.len(), 1);
+    assert_eq!(app.review_state.notes[0].text, "This line");
+}
@@ -11,6 +11,7 @@ fn test_help_flag() {
     assert!(stdout.contains("gwatch"));
     assert!(stdout.contains("--path"));
     assert!(stdout.contains("--verbose"));
+    assert!(stdout.contains("--json"));
     assert!(stdout.contains("--help"));
     assert!(stdout.contains("--version"));
 }
@@ -43,3 +43,47 @@ fn test_config_path_is_json() {
     let path = Config::config_path();
     assert!(path.to_string_lossy().ends_with("config.json"));
 }
+
+#[test]
+fn test_baseline_config_keeps_its_default_keys() {
+    // As written on first run before keys could be remapped.
+    let baseline = r#"{
+  "theme": { "name": "nord", "custom": null },
+  "editor": { "command": "vim", "args": ["+{line}", "{file}"] },
+  "watcher": { "debounce_ms": 50, "max_events_buffer": 300, "ignore_patterns": ["target"] },
+  "display": {
+    "context_lines": 3,
+    "truncate_long_lines": false,
+    "max_line_length": 120,
+    "show_line_numbers": true,
+    "show_file_path": true,
+    "use_nerd_font_icons": true
+  },
+  "keybindings": {
+    "pause_resume": "space",
+    "scroll_up": "up",
+    "scroll_down": "down",
+    "open_editor": "enter",
+    "theme_selector": "t",
+    "settings": "s",
+    "clear_history": "c",
+    "quit": "q",
+    "help": "?"
+  },
+  "diff_viewer": { "viewer": "auto", "pager": null, "delta_args": [], "difftastic_args": [] }
+}"#;
+    let config = Config::parse(baseline).unwrap();
+    assert_eq!(config.keybindings.quit, "q, esc");
+    assert_eq!(config.keybindings.scroll_up, "up, k");
+    assert_eq!(config.keybindings.scroll_down, "down, j");
+
+    // A remapped key from back then is kept.
+    let remapped = baseline.replace(r#""quit": "q""#, r#""quit": "x""#);
+    assert_eq!(Config::parse(&remapped).unwrap().keybindings.quit, "x");
+
+    // A section saved since holds every action and is taken as written.
+    let mut config = Config::default();
+    config.keybindings.quit = "q".to_string();
+    let saved = serde_json::to_string(&config).unwrap();
+    assert_eq!(Config::parse(&saved).unwrap().keybindings.quit, "q");
+}
@@ -7,6 +7,8 @@ fn make_deleted_line(num: usize, content: &str) -> DiffLine {
         new_line_number: None,
         kind: DiffKind::Deleted,
         content: content.to_string(),
+        emphasis: Vec::new(),
+        missing_newline: false,
     }
 }
 
@@ -16,6 +18,8 @@ fn make_added_line(num: usize, content: &str) -> DiffLine {
         new_line_number: Some(num),
         kind: DiffKind::Added,
         content: content.to_string(),
+        emphasis: Vec::new(),
+        missing_newline: false,
     }
 }
 
@@ -25,6 +29,8 @@ fn make_context_line(old: usize, new: usize, content: &str) -> DiffLine {
         new_line_number: Some(new),
         kind: DiffKind::Context,
         content: content.to_string(),
+        emphasis: Vec::new(),
+        missing_newline: false,
     }
 }
 
@@ -0,0 +1,197 @@
+use chrono::Utc;
+use gwatch::diff_worker::{DiffJob, DiffOutcome, DiffWorker};
+use gwatch::types::{DiffKind, DiffMode, DiffRequest, FileDiff};
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use std::time::Duration;
+use tempfile::TempDir;
+use tokio::sync::mpsc;
+
+fn init_git_repo(dir: &Path) {
+    for args in [
+        vec!["init"],
+        vec!["config", "user.email", "test@example.com"],
+        vec!["config", "user.name", "Test"],
+    ] {
+        Command::new("git")
+            .args(args)
+            .current_dir(dir)
+            .output()
+            .expect("Failed to set up git repo");
+    }
+}
+
+fn git(dir: &Path, args: &[&str]) {
+    Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .expect("Failed to run git");
+}
+
+fn request(path: &Path) -> DiffRequest {
+    DiffRequest {
+        file_path: path.to_path_buf(),
+        timestamp: Utc::now(),
+        mode: DiffMode::All,
+        context_lines: 3,
+        previous: None,
+        content: None,
+    }
+}
+
+/// Changed lines of `diff`, prefixed like a patch.
+fn changes(diff: &FileDiff) -> Vec<String> {
+    diff.hunks
+        .iter()
+        .flat_map(|hunk| &hunk.lines)
+        .filter_map(|line| match line.kind {
+            DiffKind::Added => Some(format!("+{}", line.content)),
+            DiffKind::Deleted => Some(format!("-{}", line.content)),
+            DiffKind::Context => None,
+        })
+        .collect()
+}
+
+async fn next_outcome(rx: &mut mpsc::UnboundedReceiver<DiffOutcome>) -> Option<DiffOutcome> {
+    tokio::time::timeout(Duration::from_secs(5), rx.recv())
+        .await
+        .ok()
+        .flatten()
+}
+
+#[tokio::test]
+async fn test_worker_computes_diff() {
+    let temp = TempDir::new().unwrap();
+    init_git_repo(temp.path());
+    let file = temp.path().join("new.txt");
+    fs::write(&file, "hello\n").unwrap();
+
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let mut worker = DiffWorker::new(temp.path().to_path_buf(), tx);
+    worker.submit(DiffJob::NewEvent(request(&file)));
+    assert!(worker.pending_paths().contains(&file));
+
+    let outcome = next_outcome(&mut rx).await.expect("no diff delivered");
+    let outcome = worker.accept(outcome).expect("latest result rejected");
+    assert_eq!(outcome.relative_path, "new.txt");
+    assert_eq!(outcome.result.unwrap().stats.added_count, 1);
+    assert!(worker.pending_paths().is_empty());
+}
+
+#[tokio::test]
+async fn test_worker_discards_superseded_results() {
+    let temp = TempDir::new().unwrap();
+    init_git_repo(temp.path());
+    let file = temp.path().join("file.txt");
+    fs::write(&file, "one\n").unwrap();
+
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let mut worker = DiffWorker::new(temp.path().to_path_buf(), tx);
+    let first = request(&file);
+    let second = DiffRequest {
+        context_lines: 5,
+        ..request(&file)
+    };
+    worker.submit(DiffJob::NewEvent(first));
+    worker.submit(DiffJob::NewEvent(second));
+
+    let mut accepted = Vec::new();
+    while !worker.pending_paths().is_empty() {
+        let outcome = next_outcome(&mut rx).await.expect("no diff delivered");
+        accepted.extend(worker.accept(outcome));
+    }
+
+    assert_eq!(accepted.len(), 1);
+    assert_eq!(accepted[0].job.request().context_lines, 5);
+}
+
+#[tokio::test]
+async fn test_worker_diffs_against_previous_save() {
+    let temp = TempDir::new().unwrap();
+    init_git_repo(temp.path());
+    let file = temp.path().join("notes.txt");
+    fs::write(&file, "one\ntwo\nthree\n").unwrap();
+
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let mut worker = DiffWorker::new(temp.path().to_path_buf(), tx);
+    worker.submit(DiffJob::NewEvent(DiffRequest {
+        previous: Some("one\nthree\n".into()),
+        ..request(&file)
+    }));
+
+    let outcome = next_outcome(&mut rx).await.expect("no diff delivered");
+    let outcome = worker.accept(outcome).expect("latest result rejected");
+    assert_eq!(outcome.result.unwrap().stats.added_count, 3);
+    assert_eq!(
+        outcome.snapshot.content.as_deref(),
+        Some("one\ntwo\nthree\n")
+    );
+    let since_previous = outcome.snapshot.diff.expect("no diff since previous save");
+    assert_eq!(since_previous.stats.added_count, 1);
+    assert_eq!(since_previous.stats.deleted_count, 0);
+}
+
+#[tokio::test]
+async fn test_refresh_keeps_the_save_it_was_made_for() {
+    let temp = TempDir::new().unwrap();
+    init_git_repo(temp.path());
+    let file = temp.path().join("notes.txt");
+    fs::write(&file, "one\ntwo\n").unwrap();
+    git(temp.path(), &["add", "."]);
+    git(temp.path(), &["commit", "-m", "initial"]);
+
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let mut worker = DiffWorker::new(temp.path().to_path_buf(), tx);
+    let first = request(&file);
+    fs::write(&file, "one\ntwo\nthree\n").unwrap();
+    worker.submit(DiffJob::NewEvent(first.clone()));
+    let outcome = next_outcome(&mut rx).await.expect("no diff delivered");
+    let outcome = worker.accept(outcome).unwrap();
+    let first_diff = outcome.result.unwrap();
+    let content = outcome.snapshot.content;
+
+    // A second save, then more context for the first event.
+    fs::write(&file, "ONE\ntwo\n").unwrap();
+    worker.submit(DiffJob::Refresh(DiffRequest {
+        context_lines: 6,
+        content,
+        ..first
+    }));
+    let outcome = next_outcome(&mut rx).await.expect("no diff delivered");
+    let diff = worker.accept(outcome).unwrap().result.unwrap();
+    assert_eq!(diff.context_lines, 6);
+    assert_eq!(changes(&diff), changes(&first_diff));
+}
+
+#[tokio::test]
+async fn test_resync_rediffs_the_save_against_the_new_base() {
+    let temp = TempDir::new().unwrap();
+    init_git_repo(temp.path());
+    let file = temp.path().join("notes.txt");
+    fs::write(&file, "one\ntwo\n").unwrap();
+    git(temp.path(), &["add", "."]);
+    git(temp.path(), &["commit", "-m", "initial"]);
+
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let mut worker = DiffWorker::new(temp.path().to_path_buf(), tx);
+    let first = request(&file);
+    fs::write(&file, "one\ntwo\nthree\n").unwrap();
+    worker.submit(DiffJob::NewEvent(first.clone()));
+    let outcome = next_outcome(&mut rx).await.expect("no diff delivered");
+    let content = worker.accept(outcome).unwrap().snapshot.content;
+
+    // A later save is staged, then the mode switches to unstaged changes:
+    // the first save is compared with the index, not replaced by the file.
+    fs::write(&file, "ONE\ntwo\n").unwrap();
+    git(temp.path(), &["add", "."]);
+    worker.submit(DiffJob::Resync(DiffRequest {
+        mode: DiffMode::Unstaged,
+        content,
+        ..first
+    }));
+    let outcome = next_outcome(&mut rx).await.expect("no diff delivered");
+    let diff = worker.accept(outcome).unwrap().result.unwrap();
+    assert_eq!(changes(&diff), ["-ONE", "+one", "+three"]);
+}
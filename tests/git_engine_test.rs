@@ -1,6 +1,9 @@
 use gwatch::git_engine::GitEngine;
+use gwatch::types::{DiffMode, FileDiff, IndexAction};
+use std::collections::HashMap;
 use std::fs;
-use std::path::Path;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 use tempfile::TempDir;
 
@@ -302,6 +305,110 @@ fn test_diff_deleted_file() {
     assert!(diff.is_deleted);
 }
 
+#[test]
+fn test_diff_deleted_file_shows_removed_content() {
+    let (engine, temp) = create_test_repo();
+    let repo_path = temp.path().canonicalize().unwrap();
+    let file_path = repo_path.join("gone.txt");
+
+    fs::write(&file_path, "one\ntwo\nthree\n").unwrap();
+    run_git_command(&repo_path, &["add", "gone.txt"]);
+    run_git_command(&repo_path, &["commit", "-m", "add file"]);
+    fs::remove_file(&file_path).unwrap();
+
+    let diff = engine.compute_diff(&file_path).unwrap();
+    assert!(diff.is_deleted);
+    assert_eq!(diff.stats.deleted_count, 3);
+    assert_eq!(diff.stats.added_count, 0);
+}
+
+#[test]
+fn test_diff_detects_rename() {
+    let (engine, temp) = create_test_repo();
+    let repo_path = temp.path().canonicalize().unwrap();
+    let old_path = repo_path.join("old_name.txt");
+    let new_path = repo_path.join("new_name.txt");
+
+    let content: String = (1..=20).map(|i| format!("line {i}\n")).collect();
+    fs::write(&old_path, &content).unwrap();
+    run_git_command(&repo_path, &["add", "old_name.txt"]);
+    run_git_command(&repo_path, &["commit", "-m", "add file"]);
+
+    fs::remove_file(&old_path).unwrap();
+    fs::write(&new_path, content.replace("line 7\n", "line seven\n")).unwrap();
+
+    let diff = engine.compute_diff(&new_path).unwrap();
+    assert_eq!(diff.renamed_from.as_deref(), Some("old_name.txt"));
+    assert!(!diff.is_new_file);
+    assert_eq!(diff.stats.added_count, 1);
+    assert_eq!(diff.stats.deleted_count, 1);
+
+    // The old path is covered by the rename, not reported as a deletion.
+    let diff = engine.compute_diff(&old_path).unwrap();
+    assert!(!diff.is_deleted);
+    assert!(diff.hunks.is_empty());
+}
+
+#[test]
+fn test_rename_among_unrelated_changes() {
+    let (engine, temp) = create_test_repo();
+    let repo_path = temp.path().canonicalize().unwrap();
+    fs::create_dir(repo_path.join("dir")).unwrap();
+    for name in ["moved", "dir/one", "dir/two", "dir/three"] {
+        let content: String = (1..=20).map(|i| format!("{name} {i}\n")).collect();
+        fs::write(repo_path.join(format!("{name}.txt")), content).unwrap();
+    }
+    run_git_command(&repo_path, &["add", "."]);
+    run_git_command(&repo_path, &["commit", "-m", "add files"]);
+
+    // A directory is deleted, an unrelated file appears and one file moves
+    // into a new directory.
+    let moved: String = fs::read_to_string(repo_path.join("moved.txt")).unwrap();
+    fs::remove_dir_all(repo_path.join("dir")).unwrap();
+    fs::remove_file(repo_path.join("moved.txt")).unwrap();
+    fs::write(repo_path.join("scratch.txt"), "unrelated\n").unwrap();
+    fs::create_dir(repo_path.join("sub")).unwrap();
+    fs::write(repo_path.join("sub/moved.txt"), &moved).unwrap();
+
+    for name in ["one", "two", "three"] {
+        let diff = engine
+            .compute_diff(&repo_path.join(format!("dir/{name}.txt")))
+            .unwrap();
+        assert!(diff.is_deleted, "dir/{name}.txt");
+    }
+    let diff = engine
+        .compute_diff(&repo_path.join("sub/moved.txt"))
+        .unwrap();
+    assert_eq!(diff.renamed_from.as_deref(), Some("moved.txt"));
+    let diff = engine.compute_diff(&repo_path.join("scratch.txt")).unwrap();
+    assert!(diff.is_new_file);
+}
+
+#[test]
+fn test_rename_scans_are_reused_until_the_generation_moves_on() {
+    let (engine, temp) = create_test_repo();
+    let repo_path = temp.path().canonicalize().unwrap();
+    let content: String = (1..=20).map(|i| format!("line {i}\n")).collect();
+    fs::write(repo_path.join("a.txt"), &content).unwrap();
+    run_git_command(&repo_path, &["add", "a.txt"]);
+    run_git_command(&repo_path, &["commit", "-m", "add file"]);
+
+    // A new file scans for deletions before the rename's other half exists.
+    engine.reuse_rename_scans(1);
+    fs::write(repo_path.join("scratch.txt"), "unrelated\n").unwrap();
+    let diff = engine.compute_diff(&repo_path.join("scratch.txt")).unwrap();
+    assert!(diff.is_new_file);
+
+    fs::remove_file(repo_path.join("a.txt")).unwrap();
+    fs::write(repo_path.join("b.txt"), &content).unwrap();
+    let diff = engine.compute_diff(&repo_path.join("b.txt")).unwrap();
+    assert!(diff.is_new_file, "the scan is shared within a generation");
+
+    engine.reuse_rename_scans(2);
+    let diff = engine.compute_diff(&repo_path.join("b.txt")).unwrap();
+    assert_eq!(diff.renamed_from.as_deref(), Some("a.txt"));
+}
+
 #[test]
 fn test_diff_new_file() {
     let (engine, temp) = create_test_repo();
@@ -500,3 +607,511 @@ fn test_diff_hunk_line_numbers() {
     assert!(hunk.old_start > 0);
     assert!(hunk.new_start > 0);
 }
+
+#[test]
+fn test_compute_for_mode_respects_context_lines() {
+    let (engine, temp) = create_test_repo();
+    let repo_path = temp.path().canonicalize().unwrap();
+
+    let original: String = (1..=20).map(|i| format!("line {i}\n")).collect();
+    let test_file = repo_path.join("context.txt");
+    fs::write(&test_file, &original).unwrap();
+    git_add_commit(&repo_path, "Initial commit");
+
+    fs::write(&test_file, original.replace("line 10\n", "changed\n")).unwrap();
+
+    let narrow = engine
+        .compute_for_mode(&test_file, gwatch::types::DiffMode::All, 1)
+        .unwrap();
+    assert_eq!(narrow.context_lines, 1);
+    assert_eq!(narrow.hunks[0].lines.len(), 4); // 1 + deleted + added + 1
+
+    let wide = engine
+        .compute_for_mode(&test_file, gwatch::types::DiffMode::All, 5)
+        .unwrap();
+    assert_eq!(wide.hunks[0].lines.len(), 12); // 5 + deleted + added + 5
+    assert_eq!(wide.hunks[0].old_start, 5);
+}
+
+#[test]
+fn test_head_info_follows_checkout() {
+    let (engine, temp) = create_test_repo();
+    let repo_path = temp.path().canonicalize().unwrap();
+    run_git_command(&repo_path, &["symbolic-ref", "HEAD", "refs/heads/trunk"]);
+
+    let head = engine.head_info();
+    assert_eq!(head.branch.as_deref(), Some("trunk"));
+    assert!(head.commit.is_none());
+
+    fs::write(repo_path.join("file.txt"), "content\n").unwrap();
+    git_add_commit(&repo_path, "initial");
+    run_git_command(&repo_path, &["checkout", "-b", "feature"]);
+    let head = engine.head_info();
+    assert_eq!(head.branch.as_deref(), Some("feature"));
+    let commit = head.commit.expect("HEAD should have a commit");
+
+    run_git_command(&repo_path, &["checkout", "--detach"]);
+    let head = engine.head_info();
+    assert!(head.branch.is_none());
+    assert_eq!(head.commit, Some(commit));
+}
+
+#[test]
+fn test_changed_files_follows_mode_and_ignores() {
+    let (engine, temp) = create_test_repo();
+    let repo_path = temp.path().canonicalize().unwrap();
+    fs::write(repo_path.join(".gitignore"), "*.log\n").unwrap();
+    fs::write(repo_path.join("tracked.txt"), "one\n").unwrap();
+    fs::write(repo_path.join("staged.txt"), "one\n").unwrap();
+    git_add_commit(&repo_path, "initial");
+
+    fs::write(repo_path.join("tracked.txt"), "two\n").unwrap();
+    fs::write(repo_path.join("staged.txt"), "two\n").unwrap();
+    run_git_command(&repo_path, &["add", "staged.txt"]);
+    fs::create_dir(repo_path.join("new")).unwrap();
+    fs::write(repo_path.join("new/untracked.txt"), "fresh\n").unwrap();
+    fs::write(repo_path.join("debug.log"), "noise\n").unwrap();
+
+    let names = |mode| {
+        let mut names: Vec<String> = engine
+            .changed_files(mode)
+            .unwrap()
+            .iter()
+            .map(|p| {
+                p.strip_prefix(&repo_path)
+                    .unwrap()
+                    .to_string_lossy()
+                    .into_owned()
+            })
+            .collect();
+        names.sort();
+        names
+    };
+    assert_eq!(
+        names(DiffMode::All),
+        ["new/untracked.txt", "staged.txt", "tracked.txt"]
+    );
+    assert_eq!(
+        names(DiffMode::Unstaged),
+        ["new/untracked.txt", "tracked.txt"]
+    );
+    assert_eq!(names(DiffMode::Staged), ["staged.txt"]);
+}
+
+fn git_output(dir: &Path, args: &[&str]) -> String {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .expect("Failed to run git command");
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+/// Every line of hunk `hunk` of `diff`.
+fn all_lines(diff: &FileDiff, hunk: usize) -> RangeInclusive<usize> {
+    0..=diff.hunks[hunk].lines.len() - 1
+}
+
+#[test]
+fn test_apply_lines_stages_and_unstages_a_whole_hunk() {
+    let (engine, temp) = create_test_repo();
+    let repo_path = temp.path().canonicalize().unwrap();
+    let file = repo_path.join("file.txt");
+    let original: String = (1..=20).map(|i| format!("line {i}\n")).collect();
+    fs::write(&file, &original).unwrap();
+    git_add_commit(&repo_path, "initial");
+
+    let changed = original
+        .replace("line 2\n", "line two\nline two and a half\n")
+        .replace("line 19\n", "line nineteen\n");
+    fs::write(&file, &changed).unwrap();
+
+    let diff = engine
+        .compute_for_mode(&file, DiffMode::Unstaged, 1)
+        .unwrap();
+    assert_eq!(diff.hunks.len(), 2);
+    engine
+        .apply_lines(
+            &file,
+            &diff,
+            DiffMode::Unstaged,
+            1,
+            all_lines(&diff, 1),
+            IndexAction::Stage,
+        )
+        .unwrap();
+
+    let staged = git_output(&repo_path, &["diff", "--cached"]);
+    assert!(staged.contains("+line nineteen"));
+    assert!(!staged.contains("+line two"));
+    let unstaged = git_output(&repo_path, &["diff"]);
+    assert!(unstaged.contains("+line two"));
+    assert!(!unstaged.contains("+line nineteen"));
+
+    let diff = engine.compute_for_mode(&file, DiffMode::Staged, 1).unwrap();
+    engine
+        .apply_lines(
+            &file,
+            &diff,
+            DiffMode::Staged,
+            0,
+            all_lines(&diff, 0),
+            IndexAction::Unstage,
+        )
+        .unwrap();
+    assert!(git_output(&repo_path, &["diff", "--cached"]).is_empty());
+    assert_eq!(fs::read_to_string(&file).unwrap(), changed);
+}
+
+#[test]
+fn test_apply_lines_in_all_mode_with_staged_changes_above() {
+    let (engine, temp) = create_test_repo();
+    let repo_path = temp.path().canonicalize().unwrap();
+    let file = repo_path.join("file.txt");
+    let original: String = (1..=20).map(|i| format!("line {i}\n")).collect();
+    fs::write(&file, &original).unwrap();
+    git_add_commit(&repo_path, "initial");
+
+    // The index is a line longer than HEAD above the hunk being staged.
+    fs::write(&file, format!("top\n{original}")).unwrap();
+    run_git_command(&repo_path, &["add", "file.txt"]);
+    let changed = format!("top\n{}", original.replace("line 18\n", "line eighteen\n"));
+    fs::write(&file, &changed).unwrap();
+
+    let diff = engine.compute_for_mode(&file, DiffMode::All, 1).unwrap();
+    assert_eq!(diff.hunks.len(), 2);
+    engine
+        .apply_lines(
+            &file,
+            &diff,
+            DiffMode::All,
+            1,
+            all_lines(&diff, 1),
+            IndexAction::Stage,
+        )
+        .unwrap();
+    assert!(git_output(&repo_path, &["diff"]).is_empty());
+
+    // And the working tree is a line longer than the index when unstaging.
+    fs::write(&file, format!("very top\n{changed}")).unwrap();
+    let diff = engine.compute_for_mode(&file, DiffMode::All, 1).unwrap();
+    assert_eq!(diff.hunks.len(), 2);
+    engine
+        .apply_lines(
+            &file,
+            &diff,
+            DiffMode::All,
+            1,
+            all_lines(&diff, 1),
+            IndexAction::Unstage,
+        )
+        .unwrap();
+    let staged = git_output(&repo_path, &["diff", "--cached"]);
+    assert!(staged.contains("+top"));
+    assert!(!staged.contains("eighteen"));
+}
+
+#[test]
+fn test_apply_lines_without_context() {
+    let (engine, temp) = create_test_repo();
+    let repo_path = temp.path().canonicalize().unwrap();
+    let file = repo_path.join("file.txt");
+    fs::write(&file, "a\nb\nc\nd\ne\nf\ng\nh\n").unwrap();
+    git_add_commit(&repo_path, "initial");
+
+    // Zero context: the insertion carries no old line number of its own.
+    fs::write(&file, "a\nnew\nb\nc\nd\ne\nf\ng\nH\n").unwrap();
+    let diff = engine.compute_for_mode(&file, DiffMode::All, 0).unwrap();
+    assert_eq!(diff.hunks.len(), 2);
+    engine
+        .apply_lines(
+            &file,
+            &diff,
+            DiffMode::All,
+            0,
+            all_lines(&diff, 0),
+            IndexAction::Stage,
+        )
+        .unwrap();
+    assert_eq!(
+        git_output(&repo_path, &["show", ":file.txt"]),
+        "a\nnew\nb\nc\nd\ne\nf\ng\nh\n"
+    );
+
+    // A pure deletion, likewise without context.
+    run_git_command(&repo_path, &["reset", "-q"]);
+    fs::write(&file, "a\nc\nd\ne\nf\ng\nH\n").unwrap();
+    let diff = engine.compute_for_mode(&file, DiffMode::All, 0).unwrap();
+    engine
+        .apply_lines(
+            &file,
+            &diff,
+            DiffMode::All,
+            0,
+            all_lines(&diff, 0),
+            IndexAction::Stage,
+        )
+        .unwrap();
+    let staged = git_output(&repo_path, &["diff", "--cached"]);
+    assert!(staged.contains("-b\n"));
+    assert!(!staged.contains("+H"));
+}
+
+#[test]
+fn test_apply_lines_stages_and_unstages_part_of_a_hunk() {
+    let (engine, temp) = create_test_repo();
+    let repo_path = temp.path().canonicalize().unwrap();
+    let file = repo_path.join("file.txt");
+    let original: String = (1..=8).map(|i| format!("line {i}\n")).collect();
+    fs::write(&file, &original).unwrap();
+    git_add_commit(&repo_path, "initial");
+
+    let changed = original.replace("line 4\nline 5\n", "four\nfive\n");
+    fs::write(&file, &changed).unwrap();
+    let diff = engine
+        .compute_for_mode(&file, DiffMode::Unstaged, 1)
+        .unwrap();
+    let contents: Vec<_> = diff.hunks[0]
+        .lines
+        .iter()
+        .map(|l| l.content.as_str())
+        .collect();
+    assert_eq!(
+        contents,
+        ["line 3", "line 4", "line 5", "four", "five", "line 6"]
+    );
+
+    // Nothing but context selected.
+    assert!(engine
+        .apply_lines(
+            &file,
+            &diff,
+            DiffMode::Unstaged,
+            0,
+            0..=0,
+            IndexAction::Stage
+        )
+        .is_err());
+
+    // Both deletions and the first addition.
+    engine
+        .apply_lines(
+            &file,
+            &diff,
+            DiffMode::Unstaged,
+            0,
+            1..=3,
+            IndexAction::Stage,
+        )
+        .unwrap();
+    let index = git_output(&repo_path, &["show", ":file.txt"]);
+    assert_eq!(
+        index,
+        "line 1\nline 2\nline 3\nfour\nline 6\nline 7\nline 8\n"
+    );
+
+    // Back out the deletion of "line 4" only.
+    let diff = engine.compute_for_mode(&file, DiffMode::Staged, 1).unwrap();
+    engine
+        .apply_lines(
+            &file,
+            &diff,
+            DiffMode::Staged,
+            0,
+            1..=1,
+            IndexAction::Unstage,
+        )
+        .unwrap();
+    let index = git_output(&repo_path, &["show", ":file.txt"]);
+    assert_eq!(
+        index,
+        "line 1\nline 2\nline 3\nline 4\nfour\nline 6\nline 7\nline 8\n"
+    );
+    assert_eq!(fs::read_to_string(&file).unwrap(), changed);
+}
+
+#[test]
+fn test_apply_lines_at_end_without_newline() {
+    let (engine, temp) = create_test_repo();
+    let repo_path = temp.path().canonicalize().unwrap();
+    let file = repo_path.join("file.txt");
+    fs::write(&file, "a\nb\nc").unwrap();
+    git_add_commit(&repo_path, "initial");
+
+    fs::write(&file, "a\nb\nC").unwrap();
+    let diff = engine.compute_for_mode(&file, DiffMode::All, 1).unwrap();
+    engine
+        .apply_lines(
+            &file,
+            &diff,
+            DiffMode::All,
+            0,
+            all_lines(&diff, 0),
+            IndexAction::Stage,
+        )
+        .unwrap();
+    assert_eq!(git_output(&repo_path, &["show", ":file.txt"]), "a\nb\nC");
+
+    let diff = engine.compute_for_mode(&file, DiffMode::Staged, 1).unwrap();
+    engine
+        .apply_lines(
+            &file,
+            &diff,
+            DiffMode::Staged,
+            0,
+            all_lines(&diff, 0),
+            IndexAction::Unstage,
+        )
+        .unwrap();
+    assert_eq!(git_output(&repo_path, &["show", ":file.txt"]), "a\nb\nc");
+
+    fs::write(&file, "a\nb\nc").unwrap();
+
+    // Only the final newline changes.
+    fs::write(&file, "a\nb\nc\n").unwrap();
+    let diff = engine.compute_for_mode(&file, DiffMode::All, 1).unwrap();
+    engine
+        .apply_lines(
+            &file,
+            &diff,
+            DiffMode::All,
+            0,
+            all_lines(&diff, 0),
+            IndexAction::Stage,
+        )
+        .unwrap();
+    assert_eq!(git_output(&repo_path, &["show", ":file.txt"]), "a\nb\nc\n");
+    let diff = engine.compute_for_mode(&file, DiffMode::Staged, 1).unwrap();
+    engine
+        .apply_lines(
+            &file,
+            &diff,
+            DiffMode::Staged,
+            0,
+            all_lines(&diff, 0),
+            IndexAction::Unstage,
+        )
+        .unwrap();
+    assert_eq!(git_output(&repo_path, &["show", ":file.txt"]), "a\nb\nc");
+
+    // Keeping the old last line while adding after it gives it a newline.
+    run_git_command(&repo_path, &["reset", "-q"]);
+    fs::write(&file, "a\nb\nC").unwrap();
+    let diff = engine.compute_for_mode(&file, DiffMode::All, 1).unwrap();
+    engine
+        .apply_lines(&file, &diff, DiffMode::All, 0, 2..=2, IndexAction::Stage)
+        .unwrap();
+    assert_eq!(git_output(&repo_path, &["show", ":file.txt"]), "a\nb\nc\nC");
+}
+
+#[test]
+fn test_commit_and_amend() {
+    let (engine, temp) = create_test_repo();
+    let repo_path = temp.path().canonicalize().unwrap();
+    fs::write(repo_path.join("a.txt"), "one\n").unwrap();
+    git_add_commit(&repo_path, "initial");
+
+    let info = engine.commit_info();
+    assert!(info.staged.is_empty());
+    assert_eq!(info.signature, Ok("Test <test@example.com>".to_string()));
+    assert_eq!(info.head_message.as_deref().map(str::trim), Some("initial"));
+    assert!(engine.commit("Nothing here", false).is_err());
+
+    fs::write(repo_path.join("a.txt"), "two\n").unwrap();
+    fs::write(repo_path.join("b.txt"), "new\n").unwrap();
+    run_git_command(&repo_path, &["add", "a.txt", "b.txt"]);
+    let staged: Vec<_> = engine
+        .commit_info()
+        .staged
+        .into_iter()
+        .map(|f| (f.status, f.path))
+        .collect();
+    assert_eq!(
+        staged,
+        [('M', "a.txt".to_string()), ('A', "b.txt".to_string())]
+    );
+    assert!(engine.commit("  \n\n", false).is_err());
+
+    let short_id = engine.commit("Change a\n\nAnd add b.", false).unwrap();
+    let head = git_output(&repo_path, &["rev-parse", "--short", "HEAD"]);
+    assert_eq!(short_id, head.trim());
+    assert_eq!(
+        git_output(&repo_path, &["log", "-1", "--format=%B"]).trim(),
+        "Change a\n\nAnd add b."
+    );
+    assert!(engine.commit_info().staged.is_empty());
+
+    // Amending with nothing staged rewrites only the message.
+    engine.commit("Reworded", true).unwrap();
+    assert_eq!(
+        git_output(&repo_path, &["rev-list", "--count", "HEAD"]).trim(),
+        "2"
+    );
+    assert_eq!(
+        git_output(&repo_path, &["log", "-1", "--format=%s"]).trim(),
+        "Reworded"
+    );
+    assert_eq!(git_output(&repo_path, &["show", "HEAD:b.txt"]), "new\n");
+}
+
+#[test]
+fn test_commit_in_empty_repository() {
+    let (engine, temp) = create_test_repo();
+    let repo_path = temp.path().canonicalize().unwrap();
+    assert!(engine.commit_info().head_message.is_none());
+    assert!(engine.commit("First", false).is_err());
+    assert!(engine.commit("First", true).is_err());
+
+    fs::write(repo_path.join("a.txt"), "one\n").unwrap();
+    run_git_command(&repo_path, &["add", "a.txt"]);
+    engine.commit("First", false).unwrap();
+    assert_eq!(git_output(&repo_path, &["show", "HEAD:a.txt"]), "one\n");
+}
+
+#[test]
+fn test_repository_id_survives_a_move() {
+    let (engine, temp) = create_test_repo();
+    let repo_path = temp.path().canonicalize().unwrap();
+    let ids = TempDir::new().unwrap();
+    let ids = ids.path().join("repositories.json");
+    fs::write(repo_path.join("a.txt"), "one\n").unwrap();
+    git_add_commit(&repo_path, "initial");
+    let root = git_output(&repo_path, &["rev-parse", "HEAD"]);
+    assert_eq!(engine.repository_id(&ids), root.trim());
+
+    fs::write(repo_path.join("a.txt"), "two\n").unwrap();
+    git_add_commit(&repo_path, "second");
+    assert_eq!(engine.repository_id(&ids), root.trim());
+
+    let moved = TempDir::new().unwrap();
+    let target = moved.path().canonicalize().unwrap().join("moved");
+    fs::rename(&repo_path, &target).unwrap();
+    let engine = GitEngine::new(&target).unwrap();
+    assert_eq!(engine.repository_id(&ids), root.trim());
+
+    // Ids are kept by path, outside the repositories.
+    let known: HashMap<PathBuf, String> =
+        serde_json::from_str(&fs::read_to_string(&ids).unwrap()).unwrap();
+    assert_eq!(known.get(&repo_path), Some(&root.trim().to_string()));
+    assert_eq!(known.get(&target), Some(&root.trim().to_string()));
+}
+
+#[test]
+fn test_repository_id_is_kept_from_before_the_first_commit() {
+    let (engine, temp) = create_test_repo();
+    let repo_path = temp.path().canonicalize().unwrap();
+    let ids = TempDir::new().unwrap();
+    let ids = ids.path().join("repositories.json");
+    let unborn = engine.repository_id(&ids);
+    assert!(!unborn.is_empty());
+
+    fs::write(repo_path.join("a.txt"), "one\n").unwrap();
+    git_add_commit(&repo_path, "initial");
+    assert_eq!(engine.repository_id(&ids), unborn);
+
+    // An unreadable file is left alone, and the id is worked out again.
+    fs::write(&ids, "{").unwrap();
+    let root = git_output(&repo_path, &["rev-parse", "HEAD"]);
+    assert_eq!(engine.repository_id(&ids), root.trim());
+    assert_eq!(fs::read_to_string(&ids).unwrap(), "{");
+}
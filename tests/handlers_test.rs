@@ -1,9 +1,14 @@
 use crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyEventState, KeyModifiers};
 use gwatch::config::Config;
 use gwatch::review_state::ReviewState;
-use gwatch::types::{DiffHunk, DiffKind, DiffLine, DiffMode, DisplayedEvent, FileDiff};
+use gwatch::types::{CommitInfo, StagedFile};
+use gwatch::types::{
+    Comparison, DiffHunk, DiffKind, DiffLayout, DiffLine, DiffMode, DisplayedEvent, FileDiff,
+    FileSort, HistoryView, IndexAction, SaveSnapshot,
+};
 use gwatch::ui::app::{App, AppState};
 use gwatch::ui::handlers::{get_cursor_position, handle_key_event};
+use gwatch::ui::theme::ThemeRegistry;
 use std::path::PathBuf;
 
 fn test_app() -> App {
@@ -11,6 +16,7 @@ fn test_app() -> App {
         Config::default(),
         PathBuf::from("/tmp/test"),
         ReviewState::default(),
+        ThemeRegistry::default(),
     )
 }
 
@@ -38,6 +44,8 @@ fn app_with_diff() -> App {
                     new_line_number: Some(i),
                     kind: DiffKind::Context,
                     content: format!("line {i}"),
+                    emphasis: Vec::new(),
+                    missing_newline: false,
                 })
                 .collect(),
         }],
@@ -48,6 +56,8 @@ fn app_with_diff() -> App {
         relative_path: "file.rs".to_string(),
         timestamp: chrono::Utc::now(),
         diff,
+        mode: DiffMode::All,
+        snapshot: SaveSnapshot::default(),
     });
     app
 }
@@ -165,6 +175,8 @@ fn test_hunk_navigation() {
         relative_path: "file.rs".to_string(),
         timestamp: chrono::Utc::now(),
         diff,
+        mode: DiffMode::All,
+        snapshot: SaveSnapshot::default(),
     });
 
     handle_key_event(&mut app, key(KeyCode::Char(']'))).unwrap();
@@ -185,6 +197,8 @@ fn test_toggle_hunk_collapsed_z() {
             hunks: vec![DiffHunk::default()],
             ..Default::default()
         },
+        mode: DiffMode::All,
+        snapshot: SaveSnapshot::default(),
     });
 
     assert!(!app.hunk_state.is_collapsed(0));
@@ -208,6 +222,8 @@ fn test_clear_history_c() {
         relative_path: "file.rs".to_string(),
         timestamp: chrono::Utc::now(),
         diff: FileDiff::default(),
+        mode: DiffMode::All,
+        snapshot: SaveSnapshot::default(),
     });
 
     assert!(!app.events.is_empty());
@@ -230,6 +246,33 @@ fn test_cycle_diff_mode_m() {
     assert_eq!(app.diff_mode, DiffMode::Unstaged);
 }
 
+#[test]
+fn test_toggle_layout_v() {
+    let mut app = test_app();
+    assert_eq!(app.diff_layout, DiffLayout::Split);
+    handle_key_event(&mut app, key(KeyCode::Char('v'))).unwrap();
+    assert_eq!(app.diff_layout, DiffLayout::Unified);
+}
+
+#[test]
+fn test_toggle_comparison_b() {
+    let mut app = test_app();
+    assert_eq!(app.comparison, Comparison::Base);
+    handle_key_event(&mut app, key(KeyCode::Char('b'))).unwrap();
+    assert_eq!(app.comparison, Comparison::PreviousSave);
+}
+
+#[test]
+fn test_files_view_f_and_sort_o() {
+    let mut app = test_app();
+    handle_key_event(&mut app, key(KeyCode::Char('f'))).unwrap();
+    assert_eq!(app.history_view, HistoryView::Files);
+    handle_key_event(&mut app, key(KeyCode::Char('o'))).unwrap();
+    assert_eq!(app.file_sort, FileSort::Path);
+    handle_key_event(&mut app, key(KeyCode::Char('f'))).unwrap();
+    assert_eq!(app.history_view, HistoryView::Events);
+}
+
 #[test]
 fn test_open_help() {
     let mut app = test_app();
@@ -244,6 +287,59 @@ fn test_open_settings_s() {
     assert_eq!(app.state, AppState::SettingsEditor);
 }
 
+#[test]
+fn test_stage_keys_need_a_selection() {
+    let mut app = app_with_diff();
+    app.events[0].diff.hunks[0].lines[3].kind = DiffKind::Added;
+    handle_key_event(&mut app, key(KeyCode::Char('a'))).unwrap();
+    assert!(app.take_index_requests().is_empty());
+    assert!(app.status_message().unwrap().is_error);
+
+    handle_key_event(&mut app, key(KeyCode::Char('V'))).unwrap();
+    handle_key_event(&mut app, key(KeyCode::Char('u'))).unwrap();
+    let requests = app.take_index_requests();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].action, IndexAction::Unstage);
+}
+
+#[test]
+fn test_visual_selection_keys() {
+    let mut app = app_with_diff();
+    let lines = &mut app.events[0].diff.hunks[0].lines;
+    lines[3].kind = DiffKind::Deleted;
+    lines[4].kind = DiffKind::Added;
+    app.diff_layout = DiffLayout::Unified;
+
+    handle_key_event(&mut app, key(KeyCode::Char('V'))).unwrap();
+    assert_eq!(app.hunk_state.selection.unwrap().cursor, 3);
+    let scroll = app.diff_scroll_offset;
+    handle_key_event(&mut app, key(KeyCode::Char('j'))).unwrap();
+    assert_eq!(app.hunk_state.selection.unwrap().range(), 3..=4);
+    assert_eq!(app.diff_scroll_offset, scroll + 1);
+
+    handle_key_event(&mut app, key(KeyCode::Esc)).unwrap();
+    assert!(!app.is_selecting());
+    assert!(!app.should_quit);
+
+    handle_key_event(&mut app, key(KeyCode::Char('V'))).unwrap();
+    handle_key_event(&mut app, key(KeyCode::Char('a'))).unwrap();
+    let requests = app.take_index_requests();
+    assert_eq!(requests[0].lines, 3..=3);
+}
+
+#[test]
+fn test_replay_blocks_repository_actions() {
+    let mut app = app_with_diff();
+    let events = app.events.drain(..).collect();
+    app.start_replay(PathBuf::from("session.jsonl"), events);
+
+    handle_key_event(&mut app, key(KeyCode::Char('a'))).unwrap();
+    handle_key_event(&mut app, key(KeyCode::Char('m'))).unwrap();
+    assert!(app.take_index_requests().is_empty());
+    assert_eq!(app.diff_mode, DiffMode::default());
+    assert!(app.status_message().unwrap().is_error);
+}
+
 // === Theme selector keys ===
 
 #[test]
@@ -550,27 +646,32 @@ fn test_toggle_review_r() {
         relative_path: "file.rs".to_string(),
         timestamp: chrono::Utc::now(),
         diff: FileDiff::default(),
+        mode: DiffMode::All,
+        snapshot: SaveSnapshot::default(),
     });
 
     handle_key_event(&mut app, key(KeyCode::Char('r'))).unwrap();
-    assert!(app
-        .review_state
-        .is_reviewed(&PathBuf::from("/test/file.rs")));
+    assert!(app.is_event_reviewed(app.get_current_event().unwrap()));
 }
 
 #[test]
 fn test_clear_all_reviewed_shift_r() {
     let mut app = test_app();
-    app.review_state
-        .mark_reviewed(&PathBuf::from("/test/file.rs"));
-    assert!(app
-        .review_state
-        .is_reviewed(&PathBuf::from("/test/file.rs")));
+    let path = PathBuf::from("/test/file.rs");
+    app.review_state.mark_reviewed(&path, "hunk");
+    assert!(app.review_state.is_hunk_reviewed(&path, "hunk"));
 
     handle_key_event(&mut app, key(KeyCode::Char('R'))).unwrap();
-    assert!(!app
-        .review_state
-        .is_reviewed(&PathBuf::from("/test/file.rs")));
+    assert!(!app.review_state.is_hunk_reviewed(&path, "hunk"));
+}
+
+#[test]
+fn test_toggle_hunk_review_shift_m() {
+    let mut app = app_with_diff();
+    handle_key_event(&mut app, key(KeyCode::Char('M'))).unwrap();
+    assert_eq!(app.review_progress(), Some((1, 1)));
+    handle_key_event(&mut app, key(KeyCode::Char('M'))).unwrap();
+    assert_eq!(app.review_progress(), Some((0, 1)));
 }
 
 #[test]
@@ -581,12 +682,16 @@ fn test_event_navigation_p_n() {
         relative_path: "file1.rs".to_string(),
         timestamp: chrono::Utc::now(),
         diff: FileDiff::default(),
+        mode: DiffMode::All,
+        snapshot: SaveSnapshot::default(),
     });
     app.events.push_front(DisplayedEvent {
         file_path: PathBuf::from("/test/file2.rs"),
         relative_path: "file2.rs".to_string(),
         timestamp: chrono::Utc::now(),
         diff: FileDiff::default(),
+        mode: DiffMode::All,
+        snapshot: SaveSnapshot::default(),
     });
 
     // 'p' scrolls to previous (older) events, 'n' scrolls to next (newer)
@@ -655,3 +760,154 @@ fn test_theme_selector_unhandled_key() {
     handle_key_event(&mut app, key(KeyCode::F(5))).unwrap();
     assert_eq!(app.selected_theme_index, original_index);
 }
+
+// === Custom keybindings ===
+
+fn app_with_keybindings(keybindings: gwatch::config::KeybindingConfig) -> App {
+    let config = Config {
+        keybindings,
+        ..Config::default()
+    };
+    App::new(
+        config,
+        PathBuf::from("/tmp/test"),
+        ReviewState::default(),
+        ThemeRegistry::default(),
+    )
+}
+
+#[test]
+fn test_remapped_quit_key() {
+    let mut app = app_with_keybindings(gwatch::config::KeybindingConfig {
+        quit: "x".to_string(),
+        ..Default::default()
+    });
+
+    handle_key_event(&mut app, key(KeyCode::Char('q'))).unwrap();
+    assert!(!app.should_quit);
+    handle_key_event(&mut app, key(KeyCode::Char('x'))).unwrap();
+    assert!(app.should_quit);
+}
+
+#[test]
+fn test_key_sequence_binding() {
+    let mut app = app_with_keybindings(gwatch::config::KeybindingConfig {
+        clear_history: "g c".to_string(),
+        ..Default::default()
+    });
+    app.events.push_front(DisplayedEvent {
+        file_path: PathBuf::from("/test/file.rs"),
+        relative_path: "file.rs".to_string(),
+        timestamp: chrono::Utc::now(),
+        diff: FileDiff::default(),
+        mode: DiffMode::All,
+        snapshot: SaveSnapshot::default(),
+    });
+
+    handle_key_event(&mut app, key(KeyCode::Char('c'))).unwrap();
+    assert!(!app.events.is_empty());
+    handle_key_event(&mut app, key(KeyCode::Char('g'))).unwrap();
+    handle_key_event(&mut app, key(KeyCode::Char('c'))).unwrap();
+    assert!(app.events.is_empty());
+}
+
+#[test]
+fn test_remapped_save_settings() {
+    let mut app = app_with_keybindings(gwatch::config::KeybindingConfig {
+        save_settings: "ctrl+w".to_string(),
+        ..Default::default()
+    });
+    app.open_settings_editor();
+    app.settings_editor.content = "{ invalid }".to_string();
+
+    handle_key_event(
+        &mut app,
+        KeyEvent {
+            code: KeyCode::Char('w'),
+            modifiers: KeyModifiers::CONTROL,
+            kind: KeyEventKind::Press,
+            state: KeyEventState::NONE,
+        },
+    )
+    .unwrap();
+
+    // Save was attempted (and rejected) instead of inserting a character
+    assert!(app.settings_editor.error_message.is_some());
+    assert_eq!(app.settings_editor.content, "{ invalid }");
+}
+
+fn ctrl(c: char) -> KeyEvent {
+    KeyEvent {
+        code: KeyCode::Char(c),
+        modifiers: KeyModifiers::CONTROL,
+        kind: KeyEventKind::Press,
+        state: KeyEventState::NONE,
+    }
+}
+
+#[test]
+fn test_commit_editor_keys() {
+    let mut app = test_app();
+    handle_key_event(&mut app, key(KeyCode::Char('C'))).unwrap();
+    assert_eq!(app.state, AppState::CommitEditor);
+    assert!(app.take_commit_info_request());
+    app.set_commit_info(CommitInfo {
+        staged: vec![StagedFile {
+            status: 'A',
+            path: "a.txt".to_string(),
+        }],
+        signature: Ok("Test <test@example.com>".to_string()),
+        head_message: None,
+    });
+
+    for c in "Café".chars() {
+        handle_key_event(&mut app, key(KeyCode::Char(c))).unwrap();
+    }
+    handle_key_event(&mut app, key(KeyCode::Enter)).unwrap();
+    assert_eq!(
+        (app.commit_editor.cursor_line, app.commit_editor.cursor_col),
+        (1, 0)
+    );
+    handle_key_event(&mut app, key(KeyCode::Enter)).unwrap();
+    handle_key_event(&mut app, key(KeyCode::Char('!'))).unwrap();
+    handle_key_event(&mut app, key(KeyCode::Up)).unwrap();
+    handle_key_event(&mut app, key(KeyCode::Up)).unwrap();
+    handle_key_event(&mut app, key(KeyCode::End)).unwrap();
+    handle_key_event(&mut app, key(KeyCode::Backspace)).unwrap();
+    assert_eq!(app.commit_editor.message, "Caf\n\n!");
+
+    handle_key_event(&mut app, ctrl('s')).unwrap();
+    let request = app.take_commit_request().unwrap();
+    assert_eq!(request.message, "Caf\n\n!");
+    assert_eq!(app.state, AppState::CommitEditor);
+
+    handle_key_event(&mut app, ctrl('a')).unwrap();
+    assert!(app.commit_editor.error_message.is_some());
+    handle_key_event(&mut app, key(KeyCode::Esc)).unwrap();
+    assert_eq!(app.state, AppState::Running);
+    assert!(!app.should_quit);
+
+    // The draft survives closing the editor.
+    handle_key_event(&mut app, key(KeyCode::Char('C'))).unwrap();
+    assert_eq!(app.commit_editor.message, "Caf\n\n!");
+}
+
+#[test]
+fn test_note_editor_keys() {
+    let mut app = app_with_diff();
+    handle_key_event(&mut app, key(KeyCode::Char('N'))).unwrap();
+    assert_eq!(app.state, AppState::NoteEditor);
+    for c in "ok?".chars() {
+        handle_key_event(&mut app, key(KeyCode::Char(c))).unwrap();
+    }
+    handle_key_event(&mut app, key(KeyCode::Esc)).unwrap();
+    assert_eq!(app.state, AppState::Running);
+    assert!(app.review_state.notes.is_empty());
+
+    handle_key_event(&mut app, key(KeyCode::Char('N'))).unwrap();
+    handle_key_event(&mut app, key(KeyCode::Char('q'))).unwrap();
+    assert!(!app.should_quit);
+    handle_key_event(&mut app, ctrl('s')).unwrap();
+    assert_eq!(app.state, AppState::Running);
+    assert_eq!(app.review_state.notes[0].text, "q");
+}
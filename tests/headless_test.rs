@@ -0,0 +1,119 @@
+use gwatch::git_engine::GitEngine;
+use gwatch::headless;
+use gwatch::types::DiffMode;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use tempfile::TempDir;
+
+fn git(dir: &Path, args: &[&str]) {
+    Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .expect("Failed to run git command");
+}
+
+fn create_test_repo() -> (GitEngine, TempDir) {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let repo_path = temp_dir.path().canonicalize().unwrap();
+    git(&repo_path, &["init"]);
+    git(&repo_path, &["config", "user.email", "test@example.com"]);
+    git(&repo_path, &["config", "user.name", "Test"]);
+    fs::create_dir(repo_path.join("src")).unwrap();
+    fs::write(repo_path.join("src/lib.rs"), "one\n").unwrap();
+    fs::write(repo_path.join("README.md"), "readme\n").unwrap();
+    fs::write(repo_path.join("clean.txt"), "clean\n").unwrap();
+    git(&repo_path, &["add", "."]);
+    git(&repo_path, &["commit", "-m", "initial"]);
+    let engine = GitEngine::new(&repo_path).expect("Failed to create GitEngine");
+    (engine, temp_dir)
+}
+
+fn relative_paths(events: &[gwatch::types::DisplayedEvent]) -> Vec<&str> {
+    let mut paths: Vec<_> = events.iter().map(|e| e.relative_path.as_str()).collect();
+    paths.sort();
+    paths
+}
+
+#[test]
+fn test_snapshot_of_all_changed_files() {
+    let (engine, temp) = create_test_repo();
+    let root = temp.path().canonicalize().unwrap();
+    fs::write(root.join("src/lib.rs"), "two\n").unwrap();
+    fs::write(root.join("README.md"), "changed\n").unwrap();
+
+    let events = headless::snapshot(&engine, &[], DiffMode::All, 3).unwrap();
+    assert_eq!(relative_paths(&events), ["README.md", "src/lib.rs"]);
+    assert!(events.iter().all(|e| e.diff.has_changes()));
+}
+
+#[test]
+fn test_snapshot_of_given_paths() {
+    let (engine, temp) = create_test_repo();
+    let root = temp.path().canonicalize().unwrap();
+    fs::write(root.join("src/lib.rs"), "two\n").unwrap();
+    fs::write(root.join("src/new.rs"), "new\n").unwrap();
+    fs::write(root.join("README.md"), "changed\n").unwrap();
+
+    let paths = [root.join("src"), root.join("clean.txt")];
+    let events = headless::snapshot(&engine, &paths, DiffMode::All, 3).unwrap();
+    assert_eq!(relative_paths(&events), ["src/lib.rs", "src/new.rs"]);
+
+    git(&root, &["add", "src/lib.rs"]);
+    let events = headless::snapshot(&engine, &paths, DiffMode::Staged, 3).unwrap();
+    assert_eq!(relative_paths(&events), ["src/lib.rs"]);
+    assert_eq!(events[0].mode, DiffMode::Staged);
+}
+
+#[test]
+fn test_plain_patches_apply_with_git() {
+    let (engine, temp) = create_test_repo();
+    let root = temp.path().canonicalize().unwrap();
+    let moved: String = (1..=20).map(|i| format!("line {i}\n")).collect();
+    fs::write(root.join("moved.txt"), &moved).unwrap();
+    git(&root, &["add", "moved.txt"]);
+    git(&root, &["commit", "-m", "add moved"]);
+
+    fs::write(root.join("src/lib.rs"), "one\ntwo\n").unwrap();
+    fs::write(root.join("src/new.rs"), "new\n").unwrap();
+    fs::remove_file(root.join("clean.txt")).unwrap();
+    fs::remove_file(root.join("moved.txt")).unwrap();
+    fs::write(
+        root.join("renamed.txt"),
+        moved.replace("line 7\n", "line seven\n"),
+    )
+    .unwrap();
+
+    let events = headless::snapshot(&engine, &[], DiffMode::All, 3).unwrap();
+    assert_eq!(
+        relative_paths(&events),
+        ["clean.txt", "renamed.txt", "src/lib.rs", "src/new.rs"]
+    );
+    let patch: String = events
+        .iter()
+        .map(|event| headless::format_patch(event, None))
+        .collect();
+
+    // The index still holds HEAD, so the patch applies to it.
+    let mut child = Command::new("git")
+        .args(["apply", "--check", "--cached", "-"])
+        .current_dir(&root)
+        .stdin(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(patch.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(
+        output.status.success(),
+        "{}\n{patch}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
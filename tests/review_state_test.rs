@@ -1,23 +1,26 @@
-use gwatch::review_state::ReviewState;
-use std::path::PathBuf;
+use gwatch::review_state::{ReviewScope, ReviewState};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, UNIX_EPOCH};
 
 #[test]
 fn test_toggle_reviewed() {
     let mut state = ReviewState::default();
     let path = PathBuf::from("/test/file.rs");
+    let keys = vec!["a".to_string(), "b".to_string()];
 
-    assert!(!state.is_reviewed(&path));
-    state.toggle_reviewed(&path);
-    assert!(state.is_reviewed(&path));
-    state.toggle_reviewed(&path);
-    assert!(!state.is_reviewed(&path));
+    assert!(!state.is_reviewed(&path, &keys));
+    state.toggle_reviewed(&path, &keys);
+    assert!(state.is_reviewed(&path, &keys));
+    state.toggle_reviewed(&path, &keys);
+    assert!(!state.is_reviewed(&path, &keys));
 }
 
 #[test]
 fn test_load_nonexistent_returns_default() {
     let path = PathBuf::from("/nonexistent/path/state.json");
     let state = ReviewState::load_from(&path).unwrap();
-    assert!(state.reviewed_files.is_empty());
+    assert!(state.reviewed_hunks.is_empty());
 }
 
 #[test]
@@ -26,3 +29,162 @@ fn test_state_path_contains_gwatch() {
     assert!(path.to_string_lossy().contains("gwatch"));
     assert!(path.to_string_lossy().ends_with("review_state.json"));
 }
+
+#[test]
+fn test_load_file_level_marks_as_unreviewed() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let path = dir.path().join("review_state.json");
+    fs::write(
+        &path,
+        r#"{"reviewed_files":{"/test/file.rs":{"reviewed_at":"2024-01-01T00:00:00Z"}}}"#,
+    )
+    .unwrap();
+    let state = ReviewState::load_from(&path).unwrap();
+    assert_eq!(state.reviewed_count(), 0);
+}
+
+fn scope(branch: Option<&str>) -> ReviewScope {
+    ReviewScope {
+        repository: "0123abcd".to_string(),
+        branch: branch.map(str::to_string),
+    }
+}
+
+#[test]
+fn test_marks_are_scoped_by_repository_and_branch() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let legacy = dir.path().join("review_state.json");
+    let repo = Path::new("/repo");
+    let file = Path::new("src/lib.rs");
+
+    let mut shared = ReviewState::load_in(dir.path(), &legacy, &scope(None), repo);
+    shared.mark_reviewed(file, "shared");
+    shared.save().unwrap();
+    let mut main = ReviewState::load_in(dir.path(), &legacy, &scope(Some("main")), repo);
+    assert!(!main.is_hunk_reviewed(file, "shared"));
+    main.mark_reviewed(file, "main");
+    main.save().unwrap();
+    let mut topic = ReviewState::load_in(dir.path(), &legacy, &scope(Some("topic")), repo);
+    topic.mark_reviewed(file, "topic");
+    topic.save().unwrap();
+    assert!(dir.path().join("0123abcd.json").exists());
+
+    let shared = ReviewState::load_in(dir.path(), &legacy, &scope(None), repo);
+    assert!(shared.is_hunk_reviewed(file, "shared"));
+    assert_eq!(shared.reviewed_count(), 1);
+    let main = ReviewState::load_in(dir.path(), &legacy, &scope(Some("main")), repo);
+    assert!(main.is_hunk_reviewed(file, "main"));
+    assert!(!main.is_hunk_reviewed(file, "topic"));
+
+    let other = ReviewScope {
+        repository: "ffff".to_string(),
+        branch: None,
+    };
+    let other = ReviewState::load_in(dir.path(), &legacy, &other, repo);
+    assert_eq!(other.reviewed_count(), 0);
+}
+
+#[test]
+fn test_save_leaves_an_unreadable_file_alone() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let legacy = dir.path().join("review_state.json");
+    let mut main = ReviewState::load_in(
+        dir.path(),
+        &legacy,
+        &scope(Some("main")),
+        Path::new("/repo"),
+    );
+    let file = dir.path().join("0123abcd.json");
+    fs::write(&file, "{\"branches\":").unwrap();
+
+    main.mark_reviewed(Path::new("src/lib.rs"), "main");
+    assert!(main.save().is_err());
+    assert_eq!(fs::read_to_string(&file).unwrap(), "{\"branches\":");
+}
+
+#[test]
+fn test_migrates_marks_from_the_global_file() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let legacy = dir.path().join("review_state.json");
+    let mut old = ReviewState::default();
+    old.mark_reviewed(Path::new("/repo/src/lib.rs"), "a");
+    old.mark_reviewed(Path::new("/elsewhere/main.rs"), "b");
+    old.save_to(&legacy).unwrap();
+
+    let state = ReviewState::load_in(dir.path(), &legacy, &scope(None), Path::new("/repo"));
+    assert!(state.is_hunk_reviewed(Path::new("src/lib.rs"), "a"));
+    assert_eq!(state.reviewed_count(), 1);
+
+    // Marks of other repositories wait in the old file for their turn.
+    let left = ReviewState::load_from(&legacy).unwrap();
+    assert!(left.is_hunk_reviewed(Path::new("/elsewhere/main.rs"), "b"));
+    assert!(!left.is_hunk_reviewed(Path::new("/repo/src/lib.rs"), "a"));
+
+    // The migrated marks were saved; loading again finds them there.
+    let again = ReviewState::load_in(dir.path(), &legacy, &scope(None), Path::new("/repo"));
+    assert!(again.is_hunk_reviewed(Path::new("src/lib.rs"), "a"));
+
+    ReviewState::load_in(dir.path(), &legacy, &scope(None), Path::new("/elsewhere"));
+    assert!(!legacy.exists());
+    assert!(dir.path().join("review_state.json.bak").exists());
+}
+
+#[test]
+fn test_migrates_file_level_marks() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let legacy = dir.path().join("review_state.json");
+    fs::write(
+        &legacy,
+        r#"{"reviewed_files":{
+            "/repo/src/lib.rs":{"reviewed_at":"2024-01-01T00:00:00Z"},
+            "/repo/src/main.rs":{"reviewed_at":"2024-01-01T00:00:00Z"}
+        }}"#,
+    )
+    .unwrap();
+
+    let mut state = ReviewState::load_in(dir.path(), &legacy, &scope(None), Path::new("/repo"));
+    assert_eq!(state.reviewed_files.len(), 2);
+    assert!(!legacy.exists());
+    assert!(dir.path().join("review_state.json.bak").exists());
+    let saved = ReviewState::load_in(dir.path(), &legacy, &scope(None), Path::new("/repo"));
+    assert_eq!(saved.reviewed_files.len(), 2);
+
+    // Unmodified since it was marked: every current hunk is reviewed.
+    let keys = vec!["a".to_string(), "b".to_string()];
+    let before = UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+    assert!(state.adopt_file_mark(Path::new("src/lib.rs"), Some(before), &keys));
+    assert!(state.is_reviewed(Path::new("src/lib.rs"), &keys));
+
+    // Modified since: the mark no longer says anything and is dropped.
+    let after = UNIX_EPOCH + Duration::from_secs(1_800_000_000);
+    assert!(state.adopt_file_mark(Path::new("src/main.rs"), Some(after), &keys));
+    assert_eq!(state.reviewed_of(Path::new("src/main.rs"), &keys), 0);
+    assert!(state.reviewed_files.is_empty());
+    assert!(!state.adopt_file_mark(Path::new("src/lib.rs"), Some(before), &keys));
+}
+
+#[test]
+fn test_notes_are_saved_with_the_marks() {
+    use gwatch::notes::Note;
+    use gwatch::types::DiffHunk;
+
+    let dir = tempfile::TempDir::new().unwrap();
+    let legacy = dir.path().join("review_state.json");
+    let repo = Path::new("/repo");
+    let mut state = ReviewState::load_in(dir.path(), &legacy, &scope(Some("topic")), repo);
+    let mut note = Note::new(Path::new("src/lib.rs"), &DiffHunk::default(), None);
+    note.text = "Check this".to_string();
+    state.set_note(None, Some(note.clone()));
+    state.clear_all();
+    state.save().unwrap();
+
+    let mut state = ReviewState::load_in(dir.path(), &legacy, &scope(Some("topic")), repo);
+    assert_eq!(state.notes, [note.clone()]);
+    let mut edited = note.clone();
+    edited.text = "Checked".to_string();
+    state.set_note(Some(&note), Some(edited));
+    assert_eq!(state.notes.len(), 1);
+    assert_eq!(state.notes[0].text, "Checked");
+    state.set_note(Some(&note), None);
+    assert!(state.notes.is_empty());
+}
@@ -0,0 +1,76 @@
+use gwatch::session::{self, SessionRecorder};
+use gwatch::types::{DiffMode, DisplayedEvent, FileDiff, SaveSnapshot};
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use tempfile::TempDir;
+
+fn event(name: &str) -> DisplayedEvent {
+    DisplayedEvent {
+        file_path: PathBuf::from(format!("/repo/{name}")),
+        relative_path: name.to_string(),
+        timestamp: chrono::Utc::now(),
+        diff: FileDiff::default(),
+        mode: DiffMode::Unstaged,
+        snapshot: SaveSnapshot::default(),
+    }
+}
+
+fn record(path: &std::path::Path, names: &[&str]) {
+    let mut recorder = SessionRecorder::create(path).unwrap();
+    for name in names {
+        recorder.record(&event(name)).unwrap();
+    }
+}
+
+fn names(events: &[DisplayedEvent]) -> Vec<&str> {
+    events.iter().map(|e| e.relative_path.as_str()).collect()
+}
+
+#[test]
+fn test_recording_round_trip() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("session.jsonl");
+    record(&path, &["first.rs", "second.rs"]);
+
+    let events = session::load(&path).unwrap();
+    assert_eq!(names(&events), ["first.rs", "second.rs"]);
+    assert_eq!(events[0].mode, DiffMode::Unstaged);
+}
+
+#[test]
+fn test_compressed_recording_round_trip() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("session.jsonl.gz");
+    record(&path, &["first.rs", "second.rs"]);
+
+    assert_eq!(&fs::read(&path).unwrap()[..2], [0x1f, 0x8b]);
+    let events = session::load(&path).unwrap();
+    assert_eq!(names(&events), ["first.rs", "second.rs"]);
+
+    // Detected by content, not by name.
+    let renamed = dir.path().join("session.bin");
+    fs::rename(&path, &renamed).unwrap();
+    assert_eq!(session::load(&renamed).unwrap().len(), 2);
+}
+
+#[test]
+fn test_truncated_recording_keeps_complete_events() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("session.jsonl");
+    record(&path, &["first.rs", "second.rs"]);
+    let mut file = fs::OpenOptions::new().append(true).open(&path).unwrap();
+    file.write_all(b"{\"file_path\":\"/repo/cut").unwrap();
+
+    let events = session::load(&path).unwrap();
+    assert_eq!(names(&events), ["first.rs", "second.rs"]);
+}
+
+#[test]
+fn test_load_rejects_other_files() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("notes.txt");
+    fs::write(&path, "not a recording\n").unwrap();
+    assert!(session::load(&path).is_err());
+    assert!(session::load(&dir.path().join("missing.jsonl")).is_err());
+}
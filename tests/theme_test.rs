@@ -66,3 +66,20 @@ fn test_theme_colors_are_set() {
 
     assert_ne!(format!("{:?}", nord.added), format!("{:?}", dracula.added));
 }
+
+#[test]
+fn test_names_include_custom_theme() {
+    use gwatch::config::{CustomColors, ThemeConfig};
+    use gwatch::ui::theme::ThemeRegistry;
+
+    let config = ThemeConfig {
+        name: "nord".to_string(),
+        custom: Some(CustomColors {
+            name: Some("house".to_string()),
+            ..Default::default()
+        }),
+    };
+    let names = Theme::names(&config, &ThemeRegistry::default());
+    assert_eq!(names.len(), Theme::available_themes().len() + 1);
+    assert!(names.contains(&"house".to_string()));
+}
@@ -1,7 +1,8 @@
 use gwatch::config::WatcherConfig;
 use gwatch::types::FileChangeEvent;
-use gwatch::watcher::FileWatcher;
+use gwatch::watcher::{FileWatcher, IgnoreRules, RepoStateWatcher};
 use std::fs;
+use std::process::Command;
 use std::time::Duration;
 use tempfile::TempDir;
 use tokio::sync::mpsc;
@@ -56,3 +57,107 @@ async fn test_watcher_invalid_path() {
     );
     assert!(result.is_err());
 }
+
+#[tokio::test]
+async fn test_watcher_reports_removed_files() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let repo_path = temp_dir.path().to_path_buf();
+    let file_path = repo_path.join("doomed.txt");
+    fs::write(&file_path, "bye").expect("Failed to write file");
+
+    let (tx, mut rx) = mpsc::unbounded_channel::<FileChangeEvent>();
+    let config = WatcherConfig {
+        debounce_ms: 10,
+        max_events_buffer: 100,
+        ignore_patterns: vec![],
+    };
+    let _watcher =
+        FileWatcher::new(repo_path.clone(), &config, tx).expect("Failed to create watcher");
+
+    fs::remove_file(&file_path).expect("Failed to remove file");
+
+    match tokio::time::timeout(Duration::from_millis(1000), rx.recv()).await {
+        Ok(Some(e)) => assert_eq!(e.path.file_name(), file_path.file_name()),
+        Ok(None) => panic!("Channel closed without event"),
+        Err(_) => {
+            tracing::warn!("Timed out waiting for removal event, this is expected on some systems");
+        }
+    }
+}
+
+fn git_init(dir: &std::path::Path) {
+    Command::new("git")
+        .arg("init")
+        .current_dir(dir)
+        .output()
+        .expect("Failed to run git init");
+}
+
+#[test]
+fn test_ignore_rules_nested_gitignore() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let root = temp_dir.path();
+    git_init(root);
+    fs::create_dir_all(root.join("sub/deeper")).unwrap();
+    fs::write(root.join(".gitignore"), "*.log\n").unwrap();
+    fs::write(root.join("sub/.gitignore"), "generated.rs\n").unwrap();
+    fs::write(root.join("sub/deeper/.gitignore"), "!keep.log\n").unwrap();
+
+    let rules = IgnoreRules::load(root);
+    assert!(rules.is_ignored(&root.join("app.log")));
+    assert!(rules.is_ignored(&root.join("sub/generated.rs")));
+    assert!(rules.is_ignored(&root.join("sub/deeper/generated.rs")));
+    assert!(!rules.is_ignored(&root.join("generated.rs")));
+    assert!(!rules.is_ignored(&root.join("sub/deeper/keep.log")));
+    assert!(!rules.is_ignored(&root.join("sub/main.rs")));
+}
+
+#[test]
+fn test_ignore_rules_info_exclude() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let root = temp_dir.path();
+    git_init(root);
+    let exclude = root.join(".git/info/exclude");
+    fs::create_dir_all(exclude.parent().unwrap()).unwrap();
+
+    assert!(!IgnoreRules::load(root).is_ignored(&root.join("scratch/notes.md")));
+
+    fs::write(&exclude, "scratch/\n").unwrap();
+    let rules = IgnoreRules::load(root);
+    assert!(rules.is_rules_file(&exclude));
+    assert!(rules.is_rules_file(&root.join("sub/.gitignore")));
+    assert!(!rules.is_rules_file(&root.join("src/main.rs")));
+    assert!(rules.is_ignored(&root.join("scratch/notes.md")));
+}
+
+#[tokio::test]
+async fn test_repo_state_watcher_reports_index_changes() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let repo_path = temp_dir.path().to_path_buf();
+    git_init(&repo_path);
+    fs::write(repo_path.join("file.txt"), "content").expect("Failed to write file");
+
+    let (tx, mut rx) = mpsc::unbounded_channel::<()>();
+    let config = WatcherConfig {
+        debounce_ms: 10,
+        max_events_buffer: 100,
+        ignore_patterns: vec![],
+    };
+    let git_dir = repo_path.join(".git");
+    let _watcher =
+        RepoStateWatcher::new(&git_dir, &git_dir, &config, tx).expect("Failed to create watcher");
+
+    Command::new("git")
+        .args(["add", "file.txt"])
+        .current_dir(&repo_path)
+        .output()
+        .expect("Failed to run git add");
+
+    match tokio::time::timeout(Duration::from_millis(1000), rx.recv()).await {
+        Ok(Some(())) => {}
+        Ok(None) => panic!("Channel closed without event"),
+        Err(_) => {
+            tracing::warn!("Timed out waiting for index event, this is expected on some systems");
+        }
+    }
+}